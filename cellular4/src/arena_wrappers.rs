@@ -1,13 +1,24 @@
-use std::marker::PhantomData;
+use std::{
+    any,
+    cell::RefCell,
+    collections::hash_map::DefaultHasher,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    mem,
+};
 
 use generational_arena::*;
 use log::warn;
 use mutagen::*;
 use rand::prelude::*;
 use rand::{distributions::weighted::WeightedIndex, seq::IteratorRandom};
-use serde::{Deserialize, Serialize};
+use scoped_tls::scoped_thread_local;
+use serde::{
+    de::Error as _, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer,
+};
 
-use crate::{constants::*, mutagen_args::*, node::*, node_set::*};
+use crate::{constants::*, inspector, mutagen_args::*, node::*, node_set::*};
 
 pub trait Storage<T> {
     fn arena(&self) -> &Arena<ArenaSlot<T>>;
@@ -29,6 +40,16 @@ impl<T> Metarena<T> {
     pub fn len(&self) -> usize {
         self.value.len()
     }
+
+    /// Locks the slots that are locked in `other`. Restored arenas keep their indices, so a slot
+    /// found here by the same index still holds the node that was locked.
+    pub fn copy_locks_from(&mut self, other: &Metarena<T>) {
+        for (index, _) in other.value.iter().filter(|(_, slot)| slot.locked) {
+            if let Some(restored) = self.value.get_mut(index) {
+                restored.locked = true;
+            }
+        }
+    }
 }
 
 impl<T> Default for Metarena<T> {
@@ -61,18 +82,216 @@ where
 pub struct ArenaSlot<T> {
     value: T,
     last_accessed: usize,
+    /// Keeps this node from mutating, set by hotkey, remote or by hand in a saved tree. Its parent
+    /// can still replace it.
+    #[serde(default)]
+    locked: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl<T> ArenaSlot<T> {
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    pub fn set_locked(&mut self, locked: bool) {
+        self.locked = locked;
+    }
+
+    /// Puts `value` in this slot in place of its node, under every parent of that node. The old
+    /// node's children are left to be culled.
+    pub fn replace(&mut self, value: T, current_t: usize) {
+        self.value = value;
+        self.last_accessed = current_t;
+    }
+}
+
+#[derive(Debug)]
 pub struct NodeBox<T> {
     index: Index,
     depth: usize,
     _marker: PhantomData<T>,
 }
 
+scoped_thread_local!(static RESOLVE_NODE_BOXES: Vec<NodeSet>);
+
+/// Runs `f` with every `NodeBox` serialising as the node it points at in `nodes`, rather than as an
+/// index. Nodes are wrapped in [dot_serde::Shared] so anything reachable from more than one parent
+/// is only graphed once.
+// scoped-tls can only hold sized values, so this can't take a slice
+#[allow(clippy::ptr_arg)]
+pub fn with_resolved_node_boxes<F: FnOnce() -> R, R>(nodes: &Vec<NodeSet>, f: F) -> R {
+    RESOLVE_NODE_BOXES.set(nodes, f)
+}
+
+scoped_thread_local!(static INLINE_NODE_BOXES: Vec<NodeSet>);
+
+/// Runs `f` with every `NodeBox` serialising as the node it points at in `nodes`, with nothing
+/// around it. Nodes reachable from more than one parent are written out once per parent.
+#[allow(clippy::ptr_arg)]
+pub fn with_inlined_node_boxes<F: FnOnce() -> R, R>(nodes: &Vec<NodeSet>, f: F) -> R {
+    INLINE_NODE_BOXES.set(nodes, f)
+}
+
+scoped_thread_local!(static LOCATE_NODE_BOXES: Vec<NodeSet>);
+
+/// Runs `f` with every `NodeBox` serialising as a `NodeBox` struct with the `node_type`, `depth`
+/// and `index` of the slot it points at, and the node in that slot as its `value`
+#[allow(clippy::ptr_arg)]
+pub fn with_located_node_boxes<F: FnOnce() -> R, R>(nodes: &Vec<NodeSet>, f: F) -> R {
+    LOCATE_NODE_BOXES.set(nodes, f)
+}
+
+/// Where `NodeBox`es being deserialised put their nodes, see [with_allocated_node_boxes]
+struct NodeAllocator {
+    nodes: Vec<NodeSet>,
+    /// The depth the next node goes in
+    depth: usize,
+    current_t: usize,
+}
+
+scoped_thread_local!(static ALLOCATE_NODE_BOXES: RefCell<NodeAllocator>);
+
+/// Runs `f` with every `NodeBox` deserialising from the node it points at, which is put in a new
+/// slot in `nodes`. The first nodes go in at `depth`, and each child goes one deeper than its
+/// parent.
+pub fn with_allocated_node_boxes<F: FnOnce() -> R, R>(
+    nodes: &mut Vec<NodeSet>,
+    depth: usize,
+    current_t: usize,
+    f: F,
+) -> R {
+    let allocator = RefCell::new(NodeAllocator {
+        nodes: mem::take(nodes),
+        depth,
+        current_t,
+    });
+
+    let result = ALLOCATE_NODE_BOXES.set(&allocator, f);
+    *nodes = allocator.into_inner().nodes;
+
+    result
+}
+
+/// The name of a node type without its module path, e.g. `CoordMapNodes`
+fn node_type_name<T>() -> &'static str {
+    // Node types aren't generic, so the last part of the path is their whole name
+    any::type_name::<T>().rsplit("::").next().unwrap()
+}
+
+impl<T> NodeBox<T> {
+    /// Identifies the slot this points at, unique across node types and depths
+    fn slot_id(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        any::type_name::<T>().hash(&mut hasher);
+        self.depth.hash(&mut hasher);
+        self.index.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl<T> Serialize for NodeBox<T>
+where
+    T: Serialize,
+    NodeSet: Storage<T>,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if INLINE_NODE_BOXES.is_set() {
+            INLINE_NODE_BOXES.with(|nodes| {
+                nodes[self.depth].arena()[self.index]
+                    .value
+                    .serialize(serializer)
+            })
+        } else if LOCATE_NODE_BOXES.is_set() {
+            LOCATE_NODE_BOXES.with(|nodes| {
+                let mut state = serializer.serialize_struct("NodeBox", 4)?;
+                state.serialize_field("node_type", node_type_name::<T>())?;
+                state.serialize_field("depth", &self.depth)?;
+                state.serialize_field("index", &self.index)?;
+                state.serialize_field("value", &nodes[self.depth].arena()[self.index].value)?;
+                state.end()
+            })
+        } else if RESOLVE_NODE_BOXES.is_set() {
+            RESOLVE_NODE_BOXES.with(|nodes| {
+                let value = &nodes[self.depth].arena()[self.index].value;
+                dot_serde::Shared::new(self.slot_id(), value).serialize(serializer)
+            })
+        } else {
+            let mut state = serializer.serialize_struct("NodeBox", 3)?;
+            state.serialize_field("index", &self.index)?;
+            state.serialize_field("depth", &self.depth)?;
+            state.serialize_field("_marker", &self._marker)?;
+            state.end()
+        }
+    }
+}
+
+/// How a `NodeBox` is saved in snapshots
+#[derive(Deserialize)]
+#[serde(rename = "NodeBox")]
+struct IndexedNodeBox {
+    index: Index,
+    depth: usize,
+}
+
+impl<'de, T> Deserialize<'de> for NodeBox<T>
+where
+    T: Deserialize<'de>,
+    NodeSet: Storage<T>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !ALLOCATE_NODE_BOXES.is_set() {
+            let IndexedNodeBox { index, depth } = IndexedNodeBox::deserialize(deserializer)?;
+
+            return Ok(Self {
+                index,
+                depth,
+                _marker: PhantomData,
+            });
+        }
+
+        ALLOCATE_NODE_BOXES.with(|allocator| {
+            let (depth, current_t, max_depth) = {
+                let allocator = allocator.borrow();
+                (allocator.depth, allocator.current_t, allocator.nodes.len())
+            };
+
+            if depth >= max_depth {
+                return Err(D::Error::custom(format!(
+                    "Nodes can't be nested more than {} deep",
+                    max_depth
+                )));
+            }
+
+            // The borrow can't be held while the children are read, they need it too
+            allocator.borrow_mut().depth = depth + 1;
+            let value = T::deserialize(deserializer);
+            allocator.borrow_mut().depth = depth;
+
+            let index = allocator.borrow_mut().nodes[depth]
+                .arena_mut()
+                .insert(ArenaSlot {
+                    value: value?,
+                    last_accessed: current_t,
+                    locked: false,
+                });
+
+            Ok(Self {
+                index,
+                depth,
+                _marker: PhantomData,
+            })
+        })
+    }
+}
+
 impl<T> Node for NodeBox<T>
 where
     T: Node,
+    T::Output: Debug,
     NodeSet: Storage<T>,
 {
     type Output = T::Output;
@@ -92,17 +311,21 @@ where
             ldbg!(arg.current_t);
         }
 
-        slot.value.compute(ComArg {
-            nodes: children,
-            data: arg.data,
-            depth: self.depth + 1,
-            coordinate_set: arg.coordinate_set,
-            history: arg.history,
-            current_t: arg.current_t,
-            mic_spectrograms: arg.mic_spectrograms,
-            gamepads: arg.gamepads,
-            mouse_position: arg.mouse_position,
-            camera_frames: arg.camera_frames,
+        inspector::traced(node_type_name::<T>(), self.depth, || {
+            slot.value.compute(ComArg {
+                nodes: children,
+                data: arg.data,
+                depth: self.depth + 1,
+                coordinate_set: arg.coordinate_set,
+                history: arg.history,
+                current_t: arg.current_t,
+                mic_spectrograms: arg.mic_spectrograms,
+                gamepads: arg.gamepads,
+                mouse_position: arg.mouse_position,
+                camera_frames: arg.camera_frames,
+                osc_values: arg.osc_values,
+                midi_values: arg.midi_values,
+            })
         })
     }
 }
@@ -161,6 +384,8 @@ where
             gamepads,
             mouse_position,
             camera_frames,
+            osc_values,
+            midi_values,
         } = arg;
 
         let nodes_len = nodes.len();
@@ -206,6 +431,8 @@ where
                 gamepads,
                 mouse_position,
                 camera_frames,
+                osc_values,
+                midi_values,
             };
 
             ldbg!(crate::node::mutagen_functions::leaf_node_weight(
@@ -235,9 +462,12 @@ where
                     gamepads,
                     mouse_position,
                     camera_frames,
+                    osc_values,
+                    midi_values,
                 },
             ),
             last_accessed: current_t,
+            locked: false,
         });
 
         Self {
@@ -261,6 +491,10 @@ where
     fn mutate_rng<R: Rng + ?Sized>(&mut self, rng: &mut R, arg: Self::MutArg) {
         let depth_skipped = self.depth - arg.depth;
 
+        if arg.nodes[depth_skipped].arena()[self.index].locked {
+            return;
+        }
+
         if rng.gen_bool(CONSTS.node_regenerate_chance) {
             *self = Self::generate_rng(rng, arg.into());
         } else {
@@ -281,12 +515,111 @@ where
                     gamepads: arg.gamepads,
                     mouse_position: arg.mouse_position,
                     camera_frames: arg.camera_frames,
+                    osc_values: arg.osc_values,
+                    midi_values: arg.midi_values,
                 },
             );
         }
     }
 }
 
+impl<'a, T> Crossover<'a> for NodeBox<T>
+where
+    NodeSet: Storage<T>,
+    T: Crossover<'a, CrossArg = CrossArg<'a>>,
+{
+    type CrossArg = CrossArg<'a>;
+
+    fn crossover_rng<R: Rng + ?Sized>(&mut self, rng: &mut R, other: &Self, arg: Self::CrossArg) {
+        if rng.gen_bool(CONSTS.node_crossover_chance) {
+            *self = other.transplant(arg);
+        } else if self.depth == other.depth {
+            // Only recurse when both nodes sit at the same depth, so anything transplanted further
+            // down lands at the depth it was generated for
+            let depth_skipped = self.depth - arg.depth;
+            let (current, children) = arg.nodes[depth_skipped..].split_first_mut().unwrap();
+            let (other_current, other_children) =
+                arg.other_nodes[depth_skipped..].split_first().unwrap();
+
+            current.arena_mut()[self.index].value.crossover_rng(
+                rng,
+                &other_current.arena()[other.index].value,
+                CrossArg {
+                    nodes: children,
+                    other_nodes: other_children,
+                    depth: self.depth + 1,
+                    current_t: arg.current_t,
+                },
+            );
+        }
+    }
+
+    /// Copies the node this points at in `arg.other_nodes`, and everything under it, into new slots
+    /// in `arg.nodes`. Nodes shared by several parents are copied once per parent.
+    fn transplant(&self, arg: Self::CrossArg) -> Self {
+        let depth_skipped = self.depth - arg.depth;
+        let (current, children) = arg.nodes[depth_skipped..].split_first_mut().unwrap();
+        let (other_current, other_children) =
+            arg.other_nodes[depth_skipped..].split_first().unwrap();
+
+        let other_slot = &other_current.arena()[self.index];
+        let value = other_slot.value.transplant(CrossArg {
+            nodes: children,
+            other_nodes: other_children,
+            depth: self.depth + 1,
+            current_t: arg.current_t,
+        });
+
+        let index = current.arena_mut().insert(ArenaSlot {
+            value,
+            last_accessed: arg.current_t,
+            locked: other_slot.locked,
+        });
+
+        Self {
+            index,
+            depth: self.depth,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> TreeDistance<'a> for NodeBox<T>
+where
+    NodeSet: Storage<T>,
+    T: TreeDistance<'a, DistArg = DistArg<'a>>,
+{
+    type DistArg = DistArg<'a>;
+
+    fn tree_distance(&self, other: &Self, arg: Self::DistArg) -> f64 {
+        let (current, children) = arg.nodes[self.depth - arg.depth..].split_first().unwrap();
+        let (other_current, other_children) = arg.other_nodes[other.depth - arg.other_depth..]
+            .split_first()
+            .unwrap();
+
+        current.arena()[self.index].value.tree_distance(
+            &other_current.arena()[other.index].value,
+            DistArg {
+                nodes: children,
+                other_nodes: other_children,
+                depth: self.depth + 1,
+                other_depth: other.depth + 1,
+            },
+        )
+    }
+
+    fn tree_size(&self, arg: Self::DistArg) -> usize {
+        let (current, children) = arg.nodes[self.depth - arg.depth..].split_first().unwrap();
+
+        1 + current.arena()[self.index].value.tree_size(DistArg {
+            nodes: children,
+            other_nodes: children,
+            depth: self.depth + 1,
+            other_depth: self.depth + 1,
+        })
+    }
+}
+
 impl<'a, T> Updatable<'a> for NodeBox<T>
 where
     NodeSet: Storage<T>,
@@ -315,6 +648,8 @@ where
                 gamepads: arg.gamepads,
                 mouse_position: arg.mouse_position,
                 camera_frames: arg.camera_frames,
+                osc_values: arg.osc_values,
+                midi_values: arg.midi_values,
             });
         }
     }
@@ -346,7 +681,76 @@ where
                 gamepads: arg.gamepads,
                 mouse_position: arg.mouse_position,
                 camera_frames: arg.camera_frames,
+                osc_values: arg.osc_values,
+                midi_values: arg.midi_values,
             });
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    fn insert<T>(nodes: &mut [NodeSet], depth: usize, value: T) -> NodeBox<T>
+    where
+        NodeSet: Storage<T>,
+    {
+        let index = nodes[depth].arena_mut().insert(ArenaSlot {
+            value,
+            last_accessed: 0,
+            locked: false,
+        });
+
+        NodeBox {
+            index,
+            depth,
+            _marker: PhantomData,
+        }
+    }
+
+    #[test]
+    fn transplants_remap_into_the_new_arenas() {
+        let mut other_nodes: Vec<NodeSet> = (0..3).map(|_| NodeSet::new()).collect();
+        // Pad the other arena so indices can't line up by accident
+        insert(&mut other_nodes, 2, AngleNodes::FromGametic);
+        let leaf = insert(
+            &mut other_nodes,
+            2,
+            AngleNodes::Constant {
+                value: Angle::new(1.0),
+            },
+        );
+        let root = insert(
+            &mut other_nodes,
+            1,
+            AngleNodes::MirrorOverYAxis { child: leaf },
+        );
+
+        let mut nodes: Vec<NodeSet> = (0..3).map(|_| NodeSet::new()).collect();
+        let transplanted = root.transplant(CrossArg {
+            nodes: &mut nodes,
+            other_nodes: &other_nodes,
+            depth: 0,
+            current_t: 7,
+        });
+
+        assert_eq!(transplanted.depth, 1);
+        assert_eq!(Storage::<AngleNodes>::arena(&nodes[2]).len(), 1);
+
+        let slot = &Storage::<AngleNodes>::arena(&nodes[1])[transplanted.index];
+        assert_eq!(slot.last_accessed, 7);
+
+        match &slot.value {
+            AngleNodes::MirrorOverYAxis { child } => {
+                assert_eq!(child.depth, 2);
+                assert!(matches!(
+                    Storage::<AngleNodes>::arena(&nodes[2])[child.index].value,
+                    AngleNodes::Constant { .. }
+                ));
+            }
+            value => panic!("Transplanted the wrong node: {:?}", value),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::rc::Rc;
+
+use failure::{ensure, Fallible};
+use ggez::{
+    graphics::{self, Color as GgColor, DrawParam},
+    Context, GameResult,
+};
+use rand::prelude::*;
+
+use crate::{
+    headless::{HeadlessRenderer, NodeInputs},
+    prelude::*,
+    snapshot::Snapshot,
+};
+
+/// Fewer candidates than this leave little to choose between
+pub const MIN_CANDIDATES: usize = 4;
+/// Candidates are picked with the number keys, so there can't be more than there are keys
+pub const MAX_CANDIDATES: usize = 9;
+
+/// Forks a tree into mutated candidates that render side by side at a low resolution, so a user can
+/// steer the evolution by picking their favourite.
+pub struct Breeder {
+    candidates: Vec<HeadlessRenderer>,
+}
+
+impl Breeder {
+    /// `parent` is a serialised [Snapshot] of the tree to fork. The candidates read the game's
+    /// `inputs`, as the parent might have nodes that need them.
+    pub fn new<R: Rng + ?Sized>(
+        rng: &mut R,
+        parent: &[u8],
+        image_preloader: &Rc<Preloader<Image>>,
+        inputs: NodeInputs,
+    ) -> Fallible<Self> {
+        let count = CONSTS.breeder_candidate_count;
+        ensure!(
+            (MIN_CANDIDATES..=MAX_CANDIDATES).contains(&count),
+            "breeder_candidate_count must be between {} and {}, not {}",
+            MIN_CANDIDATES,
+            MAX_CANDIDATES,
+            count
+        );
+
+        let candidates = (0..count)
+            .map(|_| {
+                let mut candidate = HeadlessRenderer::with_size(
+                    Snapshot::from_bytes(parent)?,
+                    Rc::clone(image_preloader),
+                    CONSTS.breeder_cell_array_width,
+                    CONSTS.breeder_cell_array_height,
+                );
+                candidate.mutate_with(rng, inputs);
+
+                Ok(candidate)
+            })
+            .collect::<Fallible<_>>()?;
+
+        Ok(Self { candidates })
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    /// Advances every candidate by one frame
+    pub fn tick(&mut self, inputs: NodeInputs) {
+        for candidate in self.candidates.iter_mut() {
+            candidate.tick_with(inputs);
+        }
+    }
+
+    /// Draws each candidate's latest frame in its tile, filling the window
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        graphics::clear(ctx, GgColor::new(0.0, 0.0, 0.0, 1.0));
+
+        let screen = graphics::screen_coordinates(ctx);
+        let (tile_width, tile_height) = self.tile_size(screen.w, screen.h);
+        let (columns, _) = grid_size(self.len());
+
+        for (i, candidate) in self.candidates.iter().enumerate() {
+            let cell_array = &candidate.current_step().cell_array;
+            let (height, width, _) = cell_array.dim();
+            let texture = compute_texture(ctx, cell_array.view(), true);
+
+            graphics::draw(
+                ctx,
+                &texture,
+                DrawParam::new()
+                    .dest([
+                        (i % columns) as f32 * tile_width,
+                        (i / columns) as f32 * tile_height,
+                    ])
+                    .scale([tile_width / width as f32, tile_height / height as f32]),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// The candidate whose tile is under the window position `(x, y)`, in pixels as mouse events
+    /// give it. That's the window's current size, which the tiles are stretched to fill.
+    pub fn candidate_at(&self, ctx: &Context, x: f32, y: f32) -> Option<usize> {
+        let (window_width, window_height) = graphics::drawable_size(ctx);
+        let (tile_width, tile_height) = self.tile_size(window_width, window_height);
+        let (columns, rows) = grid_size(self.len());
+
+        if x < 0.0 || y < 0.0 {
+            return None;
+        }
+
+        let column = (x / tile_width) as usize;
+        let row = (y / tile_height) as usize;
+        let index = row * columns + column;
+
+        if column < columns && row < rows && index < self.len() {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    /// Ends breeding, keeping only the candidate at `index`
+    pub fn into_candidate(mut self, index: usize) -> HeadlessRenderer {
+        self.candidates.swap_remove(index)
+    }
+
+    /// The size of each tile when the grid fills `width` by `height`
+    fn tile_size(&self, width: f32, height: f32) -> (f32, f32) {
+        let (columns, rows) = grid_size(self.len());
+
+        (width / columns as f32, height / rows as f32)
+    }
+}
+
+/// The smallest roughly square grid that fits `count` tiles, as (columns, rows)
+fn grid_size(count: usize) -> (usize, usize) {
+    let columns = (count as f32).sqrt().ceil().max(1.0) as usize;
+    let rows = (count + columns - 1) / columns;
+
+    (columns, rows.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grids_fit_every_candidate() {
+        assert_eq!(grid_size(1), (1, 1));
+        assert_eq!(grid_size(4), (2, 2));
+        assert_eq!(grid_size(5), (3, 2));
+        assert_eq!(grid_size(6), (3, 2));
+        assert_eq!(grid_size(9), (3, 3));
+    }
+}
@@ -2,19 +2,57 @@ use std::collections::VecDeque;
 
 use failure::Fallible;
 use ndarray::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
 
+pub mod file;
 #[cfg(unix)]
 pub mod linux;
 #[cfg(windows)]
 pub mod windows;
 
+pub use file::*;
 #[cfg(unix)]
 pub use linux::*;
 #[cfg(windows)]
 pub use windows::*;
 
+/// A file camera if a `file` is given, otherwise a camera device
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum CameraConfig {
+    File(FileCameraConfig),
+    Device(DeviceCameraConfig),
+}
+
+pub enum Camera {
+    Device(DeviceCamera),
+    File(FileCamera),
+}
+
+impl GenericCamera for Camera {
+    type Config = CameraConfig;
+
+    fn new(config: Self::Config) -> Fallible<(Self, CameraFrames)> {
+        match config {
+            CameraConfig::Device(config) => {
+                DeviceCamera::new(config).map(|(camera, frames)| (Camera::Device(camera), frames))
+            }
+            CameraConfig::File(config) => {
+                FileCamera::new(config).map(|(camera, frames)| (Camera::File(camera), frames))
+            }
+        }
+    }
+
+    fn update(&mut self, frames: &mut CameraFrames, current_t: usize) -> Fallible<()> {
+        match self {
+            Camera::Device(camera) => camera.update(frames, current_t),
+            Camera::File(camera) => camera.update(frames, current_t),
+        }
+    }
+}
+
 pub trait GenericCamera: Sized {
     type Config;
 
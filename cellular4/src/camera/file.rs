@@ -0,0 +1,286 @@
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Child, ChildStdout, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender},
+    thread,
+    time::Duration,
+};
+
+use failure::{bail, ensure, format_err, Fallible};
+use image::{imageops, imageops::FilterType, ImageFormat};
+use log::{info, warn};
+use ndarray::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// How long a tic waits for the worker to decode a frame, so a slow decoder can't freeze the window
+const FRAME_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct FileCameraConfig {
+    /// A video file or a directory of images played in name order. Videos are decoded with
+    /// `ffmpeg`, which has to be installed and on the `PATH`.
+    file: String,
+    /// Defaults to the target fps
+    fps: Option<f32>,
+    n_frames: Option<usize>,
+}
+
+/// Plays a video or a directory of images on a loop, as if it were a camera.
+///
+/// Frames advance with the tics rather than in real time, so headless renders see the same
+/// frames every run as long as decoding keeps up. When it doesn't, the last frames stay up until
+/// the worker catches up.
+pub struct FileCamera {
+    frames_receiver: Receiver<Fallible<Array2<ByteColor>>>,
+    fps: f32,
+    /// The tic the first frame was shown on
+    start_t: Option<usize>,
+    /// How many frames have been taken from the worker so far
+    frames_read: usize,
+}
+
+impl GenericCamera for FileCamera {
+    type Config = FileCameraConfig;
+
+    fn new(config: Self::Config) -> Fallible<(Self, CameraFrames)> {
+        let path = Path::new(&config.file);
+        let fps = config.fps.unwrap_or(CONSTS.target_fps as f32);
+        let resolution = (
+            CONSTS.cell_array_width as u32,
+            CONSTS.cell_array_height as u32,
+        );
+        let n_frames = config.n_frames.unwrap_or(CONSTS.cell_array_history_length);
+
+        ensure!(fps > 0.0, "Camera fps must be above 0, got {}", fps);
+
+        info!(
+            "Initializing file camera from {}, resolution {}x{}, {} fps",
+            path.display(),
+            resolution.0,
+            resolution.1,
+            fps,
+        );
+
+        // Frames are decoded ahead on a worker, a few at a time
+        let (frames_sender, frames_receiver) = mpsc::sync_channel(n_frames.max(1));
+
+        if path.is_dir() {
+            let paths = image_paths(path)?;
+
+            thread::spawn(move || read_images(&paths, resolution, &frames_sender));
+        } else {
+            let mut ffmpeg = spawn_ffmpeg(path, fps, resolution)?;
+            let stdout = ffmpeg
+                .stdout
+                .take()
+                .ok_or_else(|| format_err!("No output from ffmpeg"))?;
+
+            thread::spawn(move || {
+                read_video(stdout, resolution, &frames_sender);
+                let _ = ffmpeg.kill();
+            });
+        }
+
+        Ok((
+            Self {
+                frames_receiver,
+                fps,
+                start_t: None,
+                frames_read: 0,
+            },
+            CameraFrames {
+                frames: (0..n_frames)
+                    .map(|_| Array2::default((resolution.1 as usize, resolution.0 as usize)))
+                    .collect(),
+                fps,
+                resolution,
+                current_t: 0,
+            },
+        ))
+    }
+
+    fn update(&mut self, frames: &mut CameraFrames, current_t: usize) -> Fallible<()> {
+        let elapsed_t = current_t - *self.start_t.get_or_insert(current_t);
+        let frames_due =
+            (elapsed_t as f32 / CONSTS.target_fps as f32 * self.fps).floor() as usize + 1;
+
+        while self.frames_read < frames_due {
+            let frame = match self.frames_receiver.recv_timeout(FRAME_TIMEOUT) {
+                Ok(frame) => frame?,
+                Err(RecvTimeoutError::Timeout) => {
+                    warn!(
+                        "File camera is {} frames behind",
+                        frames_due - self.frames_read
+                    );
+                    break;
+                }
+                Err(RecvTimeoutError::Disconnected) => bail!("File camera worker stopped"),
+            };
+
+            frames.frames.push_front(frame);
+            frames.frames.pop_back();
+
+            self.frames_read += 1;
+        }
+
+        frames.current_t = current_t;
+
+        Ok(())
+    }
+}
+
+fn image_paths(dir: &Path) -> Fallible<Vec<PathBuf>> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Fallible<Vec<_>>>()?;
+
+    paths.retain(|path| ImageFormat::from_path(path).is_ok());
+    paths.sort();
+
+    ensure!(!paths.is_empty(), "No images in {}", dir.display());
+
+    Ok(paths)
+}
+
+fn read_images(
+    paths: &[PathBuf],
+    resolution: (u32, u32),
+    sender: &SyncSender<Fallible<Array2<ByteColor>>>,
+) {
+    for path in paths.iter().cycle() {
+        let frame = image::open(path)
+            .map_err(|e| format_err!("Failed to read {}: {}", path.display(), e))
+            .map(|image| {
+                let image = imageops::resize(
+                    &image.to_rgba8(),
+                    resolution.0,
+                    resolution.1,
+                    FilterType::Triangle,
+                );
+
+                Array2::from_shape_fn((resolution.1 as usize, resolution.0 as usize), |(y, x)| {
+                    ByteColor::from(*image.get_pixel(x as u32, y as u32))
+                })
+            });
+
+        let failed = frame.is_err();
+
+        // The camera has been dropped if nothing's receiving
+        if sender.send(frame).is_err() || failed {
+            return;
+        }
+    }
+}
+
+fn spawn_ffmpeg(path: &Path, fps: f32, resolution: (u32, u32)) -> Fallible<Child> {
+    ensure!(
+        path.is_file(),
+        "{} isn't a file or directory",
+        path.display()
+    );
+
+    // Checked up front, otherwise a missing ffmpeg only shows up as the worker stopping
+    let installed = Command::new("ffmpeg")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_or(false, |status| status.success());
+
+    ensure!(
+        installed,
+        "Playing {} needs ffmpeg installed and on the PATH",
+        path.display()
+    );
+
+    Command::new("ffmpeg")
+        .args(&["-loglevel", "error", "-stream_loop", "-1", "-i"])
+        .arg(path)
+        .arg("-vf")
+        .arg(format!(
+            "fps={},scale={}:{}",
+            fps, resolution.0, resolution.1
+        ))
+        .args(&["-f", "rawvideo", "-pix_fmt", "rgba", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format_err!("Failed to run ffmpeg: {}", e))
+}
+
+fn read_video(
+    mut stdout: ChildStdout,
+    resolution: (u32, u32),
+    sender: &SyncSender<Fallible<Array2<ByteColor>>>,
+) {
+    let (width, height) = (resolution.0 as usize, resolution.1 as usize);
+    let mut buf = vec![0; width * height * 4];
+
+    loop {
+        let frame = stdout
+            .read_exact(&mut buf)
+            .map_err(|e| format_err!("Failed to read frame from ffmpeg: {}", e))
+            .map(|()| {
+                Array2::from_shape_fn((height, width), |(y, x)| {
+                    let i = (y * width + x) * 4;
+
+                    ByteColor {
+                        r: Byte::new(buf[i]),
+                        g: Byte::new(buf[i + 1]),
+                        b: Byte::new(buf[i + 2]),
+                        a: Byte::new(buf[i + 3]),
+                    }
+                })
+            });
+
+        let failed = frame.is_err();
+
+        if sender.send(frame).is_err() || failed {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+
+    #[test]
+    fn loops_image_directory_in_step_with_tics() {
+        let dir =
+            std::env::temp_dir().join(format!("cellular4_camera_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        for (i, value) in [0, 100, 200].iter().enumerate() {
+            RgbaImage::from_pixel(4, 4, Rgba([*value, *value, *value, 255]))
+                .save(dir.join(format!("{}.png", i)))
+                .unwrap();
+        }
+
+        let (mut camera, mut frames) = FileCamera::new(FileCameraConfig {
+            file: dir.to_string_lossy().into_owned(),
+            fps: Some(CONSTS.target_fps as f32),
+            n_frames: Some(2),
+        })
+        .unwrap();
+
+        let latest_red = |frames: &CameraFrames| frames.frames[0][[0, 0]].r.into_inner();
+
+        camera.update(&mut frames, 0).unwrap();
+        assert_eq!(latest_red(&frames), 0);
+
+        camera.update(&mut frames, 2).unwrap();
+        assert_eq!(latest_red(&frames), 200);
+
+        camera.update(&mut frames, 3).unwrap();
+        assert_eq!(latest_red(&frames), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
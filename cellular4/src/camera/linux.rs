@@ -13,25 +13,25 @@ use failure::{ensure, format_err, Fallible};
 use float_ord::FloatOrd;
 use log::{info, warn};
 use ndarray::{prelude::*, Zip};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
 
-#[derive(Deserialize, Debug, Clone)]
-pub struct CameraConfig {
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DeviceCameraConfig {
     device_path: Option<String>,
     n_frames: Option<usize>,
 }
 
-pub struct Camera {
+pub struct DeviceCamera {
     empty_frames_sender: SyncSender<Array2<ByteColor>>,
     ready_frames_receiver: Receiver<Array2<ByteColor>>,
     worker_thread: Option<JoinHandle<()>>,
     running: Arc<AtomicBool>,
 }
 
-impl GenericCamera for Camera {
-    type Config = CameraConfig;
+impl GenericCamera for DeviceCamera {
+    type Config = DeviceCameraConfig;
 
     fn new(config: Self::Config) -> Fallible<(Self, CameraFrames)> {
         let mut camera = rscam::Camera::new(
@@ -216,7 +216,7 @@ where
         })
 }
 
-impl Drop for Camera {
+impl Drop for DeviceCamera {
     fn drop(&mut self) {
         if let Some(handle) = self.worker_thread.take() {
             self.running.store(false, atomic::Ordering::Relaxed);
@@ -2,9 +2,9 @@ use failure::Fallible;
 
 use crate::prelude::*;
 
-pub struct Camera {}
+pub struct DeviceCamera {}
 
-impl GenericCamera for Camera {
+impl GenericCamera for DeviceCamera {
     fn new(config: Self::Config) -> Fallible<(Self, CameraFrames)> {
         todo!()
     }
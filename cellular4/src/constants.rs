@@ -1,26 +1,294 @@
-use std::fs;
+use std::{
+    collections::BTreeMap,
+    fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicPtr, Ordering},
+        Mutex,
+    },
+    time::SystemTime,
+};
 
+use failure::{ensure, format_err, Error, Fallible};
 use lazy_static::lazy_static;
-use serde::Deserialize;
+use log::{error, info, warn};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_yaml::{Mapping, Value};
 
-use crate::prelude::*;
+use crate::{prelude::*, update_stat::Heuristic};
 
 lazy_static! {
-    pub static ref CONSTS: Constants = {
-        let path = crate::util::local_path("constants.yml");
+    static ref INITIAL_CONSTANTS: Mutex<Option<LiveConstants>> = Mutex::new(None);
+    pub static ref CONSTS: LiveConstants =
+        INITIAL_CONSTANTS.lock().unwrap().take().unwrap_or_else(|| {
+            LiveConstants::load(ConstantsSource::default())
+                .unwrap_or_else(|e| panic!("Failed to load constants: {}", e))
+        });
+}
+
+/// Sets the constants that [CONSTS] will hold. Has no effect once [CONSTS] has been accessed, so
+/// this needs to be called before anything else starts up.
+pub fn init_constants(constants: LiveConstants) {
+    *INITIAL_CONSTANTS.lock().unwrap() = Some(constants);
+}
+
+/// Constants that are baked into something at startup, like the size of the cell array or the
+/// number of node arenas. Changes to these are ignored when reloading.
+const FIXED_CONSTANTS: &[&str] = &[
+    "initial_window_width",
+    "initial_window_height",
+    "vsync",
+    "fullscreen",
+    "console_width",
+    "fancy_terminal",
+    "cell_array_width",
+    "cell_array_height",
+    "cell_array_history_length",
+    "cell_array_lerp_length",
+    "byte_max_value",
+    "byte_possible_values",
+    "max_neighbour_array_count",
+    "max_neighbour_count",
+    "max_colors",
+    "max_leaf_depth",
+    "max_pipe_depth",
+    "max_branch_depth",
+    "image_path",
+    "mic",
+    "smithsonian_api_key",
+    "gfycat",
+    "camera",
+    "osc",
+    "midi",
+    "remote",
+    "mutagen_profiler",
+    "mutagen_profiler_graphs",
+];
+
+/// Where the constants come from: a file, with any command line overrides applied on top
+#[derive(Debug, Clone, Default)]
+pub struct ConstantsSource {
+    pub path: Option<PathBuf>,
+    /// `path` is a snapshot with the constants saved inside it. Snapshots saved without any fall
+    /// back to the default constants.
+    pub from_snapshot: bool,
+    pub overrides: Vec<ConstantOverride>,
+}
+
+impl ConstantsSource {
+    /// Reads the constants and applies the overrides, without checking that the result is a valid
+    /// [Constants]
+    pub fn load_value(&self) -> Fallible<Value> {
+        let mut value = read_yaml(&self.path())?;
+
+        if self.from_snapshot {
+            value = match value.get("constants") {
+                Some(constants) => constants.clone(),
+                None => read_yaml(&default_constants_path())?,
+            };
+        }
+
+        for constant_override in &self.overrides {
+            constant_override.apply(&mut value)?;
+        }
+
+        Ok(value)
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.path.clone().unwrap_or_else(default_constants_path)
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        fs::metadata(self.path()).and_then(|m| m.modified()).ok()
+    }
+}
+
+/// The current [Constants], which can be swapped out for a newer version of the file while running.
+///
+/// Derefs to the current constants. Replaced constants are leaked rather than freed, since
+/// references to them could still be around anywhere. Reloads only happen when someone edits the
+/// file, so this never amounts to much.
+pub struct LiveConstants {
+    current: AtomicPtr<Constants>,
+    reload_state: Mutex<ReloadState>,
+}
 
-        serde_yaml::from_str(&fs::read_to_string(&path).unwrap_or_else(|e| {
-            panic!(
-                "Couldn't read constants.yml in {}: {}",
-                path.to_string_lossy(),
-                e
-            )
-        }))
-        .unwrap_or_else(|e| panic!("Failed to parse constants.yml: {}", e))
-    };
+struct ReloadState {
+    source: ConstantsSource,
+    value: Value,
+    modified: Option<SystemTime>,
 }
 
-#[derive(Deserialize)]
+impl LiveConstants {
+    pub fn load(source: ConstantsSource) -> Fallible<Self> {
+        let modified = source.modified();
+        let value = source.load_value()?;
+        let constants = Constants::from_value(value.clone(), &source.overrides)?;
+
+        Ok(Self {
+            current: AtomicPtr::new(Box::into_raw(Box::new(constants))),
+            reload_state: Mutex::new(ReloadState {
+                source,
+                value,
+                modified,
+            }),
+        })
+    }
+
+    /// The constants as YAML, after overrides and any reloads
+    pub fn effective_value(&self) -> Value {
+        self.reload_state.lock().unwrap().value.clone()
+    }
+
+    /// Reloads the constants if the file has been modified since it was last read. Errors in the
+    /// file are logged and the current constants kept.
+    pub fn reload_if_changed(&self) {
+        let mut state = self.reload_state.lock().unwrap();
+        let modified = state.source.modified();
+
+        if modified == state.modified {
+            return;
+        }
+
+        state.modified = modified;
+        let path = state.source.path();
+
+        match self.try_reload(&mut state) {
+            Ok(()) => info!("Reloaded constants from {}", path.display()),
+            Err(e) => error!("Failed to reload constants from {}: {}", path.display(), e),
+        }
+    }
+
+    fn try_reload(&self, state: &mut ReloadState) -> Fallible<()> {
+        let mut value = state.source.load_value()?;
+
+        for &key in FIXED_CONSTANTS {
+            let old = state.value.get(key).cloned();
+
+            if value.get(key) != old.as_ref() {
+                warn!(
+                    "Ignoring the change to {}, it can't be changed while running",
+                    key
+                );
+
+                let mapping = as_mapping(&mut value)?;
+                let key = Value::from(key);
+
+                match old {
+                    Some(old) => mapping.insert(key, old),
+                    None => mapping.remove(&key),
+                };
+            }
+        }
+
+        let constants = Constants::from_value(value.clone(), &state.source.overrides)?;
+        self.current
+            .store(Box::into_raw(Box::new(constants)), Ordering::Release);
+        state.value = value;
+
+        Ok(())
+    }
+}
+
+impl Deref for LiveConstants {
+    type Target = Constants;
+
+    fn deref(&self) -> &Constants {
+        let current = self.current.load(Ordering::Acquire);
+        debug_assert!(!current.is_null());
+
+        // Safety: `current` always comes from Box::into_raw and is never freed
+        unsafe { &*current }
+    }
+}
+
+/// The constants file used when no `--config` is given: constants.yml if there is one, otherwise
+/// the checked in defaults
+pub fn default_constants_path() -> PathBuf {
+    let path = crate::util::local_path("constants.yml");
+
+    if path.exists() {
+        path
+    } else {
+        crate::util::local_path("constants.default.yml")
+    }
+}
+
+fn read_yaml(path: &Path) -> Fallible<Value> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format_err!("Couldn't read {}: {}", path.to_string_lossy(), e))?;
+
+    serde_yaml::from_str(&text)
+        .map_err(|e| format_err!("Failed to parse {}: {}", path.to_string_lossy(), e))
+}
+
+/// A single `key=value` override from the command line. Keys into nested configs are separated by
+/// dots, e.g. `mic.gamma=2.0`, and values are parsed as YAML.
+#[derive(Debug, Clone)]
+pub struct ConstantOverride {
+    pub key: Vec<String>,
+    pub value: Value,
+}
+
+impl ConstantOverride {
+    /// Sets the constant in `constants`. Unknown keys aren't caught here, they're rejected by
+    /// [Constants::from_value].
+    pub fn apply(&self, constants: &mut Value) -> Fallible<()> {
+        let (last, parents) = self.key.split_last().unwrap();
+        let mut mapping = as_mapping(constants)?;
+
+        for segment in parents {
+            let child = mapping
+                .entry(Value::String(segment.clone()))
+                .or_insert(Value::Null);
+
+            if child.is_null() {
+                *child = Value::Mapping(Mapping::new());
+            }
+
+            mapping = as_mapping(child)?;
+        }
+
+        mapping.insert(Value::String(last.clone()), self.value.clone());
+
+        Ok(())
+    }
+}
+
+fn as_mapping(value: &mut Value) -> Fallible<&mut Mapping> {
+    match value {
+        Value::Mapping(mapping) => Ok(mapping),
+        other => Err(format_err!("Expected a mapping, found {:?}", other)),
+    }
+}
+
+impl FromStr for ConstantOverride {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Fallible<Self> {
+        let mut parts = s.splitn(2, '=');
+        let key = parts.next().unwrap();
+        let value = parts
+            .next()
+            .ok_or_else(|| format_err!("Expected key=value, found '{}'", s))?;
+
+        ensure!(
+            !key.is_empty() && key.split('.').all(|segment| !segment.is_empty()),
+            "Invalid constant name '{}'",
+            key
+        );
+
+        Ok(Self {
+            key: key.split('.').map(String::from).collect(),
+            value: serde_yaml::from_str(value)?,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize)]
 pub struct Constants {
     pub target_fps: u32,
     pub tics_per_update: usize,
@@ -41,6 +309,15 @@ pub struct Constants {
 
     pub auto_mutate: bool,
     pub auto_mutate_above_cpu_usage: f64,
+    pub mutation_history_length: usize,
+    /// How likely each branch of the tree is to be the one mutated
+    pub mutation_weights: MutationWeights,
+    /// Multiplies each branch's mutation weight by how many nodes it has
+    pub mutation_weights_by_size: bool,
+
+    pub breeder_candidate_count: usize,
+    pub breeder_cell_array_width: usize,
+    pub breeder_cell_array_height: usize,
 
     pub lerp_aggressiveness: f32,
 
@@ -55,14 +332,8 @@ pub struct Constants {
 
     pub graph_mutation_divisor: usize,
 
-    pub activity_value_upper_bound: f64,
-    pub activity_value_lower_bound: f64,
-    pub alpha_value_upper_bound: f64,
-    pub alpha_value_lower_bound: f64,
-    pub local_similarity_upper_bound: f64,
-    pub local_similarity_lower_bound: f64,
-    pub global_similarity_upper_bound: f64,
-    pub global_similarity_lower_bound: f64,
+    /// Heuristics left out don't count towards mutating
+    pub heuristics: BTreeMap<Heuristic, HeuristicConfig>,
 
     pub image_path: String,
     pub image_download_probability: f64,
@@ -82,6 +353,9 @@ pub struct Constants {
 
     pub graph_convergence: f64,
     pub node_regenerate_chance: f64,
+    /// How likely crossover is to take a whole subtree from the other tree at each node, rather
+    /// than going further down
+    pub node_crossover_chance: f64,
 
     pub min_leaf_depth: usize,
     pub max_leaf_depth: usize,
@@ -97,12 +371,62 @@ pub struct Constants {
     pub smithsonian_api_key: Option<String>,
     pub gfycat: Option<GfycatConfig>,
     pub camera: Option<CameraConfig>,
+    pub osc: Option<OscConfig>,
+    pub midi: Option<MidiConfig>,
+    pub remote: Option<RemoteConfig>,
 
     pub mutagen_profiler: bool,
     pub mutagen_profiler_graphs: bool,
 }
 
-#[derive(Clone, Deserialize)]
+impl Constants {
+    /// Fails if any of the `overrides` applied to `value` don't name a constant. Anything else in
+    /// `value` that isn't a constant is tolerated, since old files can have leftovers.
+    pub fn from_value(value: Value, overrides: &[ConstantOverride]) -> Fallible<Self> {
+        let constants: Self = serde_yaml::from_value(value)?;
+        // Keys that aren't constants, at any depth, are dropped when deserialising
+        let known = serde_yaml::to_value(&constants)?;
+
+        for constant_override in overrides {
+            ensure!(
+                constant_override
+                    .key
+                    .iter()
+                    .try_fold(&known, |value, segment| value.get(segment.as_str()))
+                    .is_some(),
+                "Unknown constant '{}'",
+                constant_override.key.join(".")
+            );
+        }
+
+        Ok(constants)
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct HeuristicConfig {
+    /// How much this heuristic counts towards the mutation likelihood, relative to the others
+    pub weight: f64,
+    /// The ideal value, the further from this the more likely a mutation is
+    pub target: f64,
+    /// Going outside the bounds always causes a mutation
+    pub lower_bound: f64,
+    pub upper_bound: f64,
+}
+
+/// Weights for each field of a [NodeTree](crate::node_tree::NodeTree). They can't all be 0.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct MutationWeights {
+    pub root_node: f64,
+    pub root_coordinate_node: f64,
+    pub root_frame_renderer: f64,
+    pub compute_offset_node: f64,
+    pub fade_color_node: f64,
+    pub fade_color_alpha_multiplier: f64,
+    pub scaling_mode_node: f64,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct MicConfig {
     pub min_frequency: f32,
     pub max_frequency: f32,
@@ -110,9 +434,66 @@ pub struct MicConfig {
     pub lerp_factor: f32,
     pub range_decay_factor: f32,
     pub target_fps: f32,
+    /// WAV or FLAC files to read instead of the live input device, played one after the other
+    /// on a loop. Either a single path or a list of them.
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub files: Vec<String>,
+    /// Play the files back through the default output device as they're read
+    #[serde(default)]
+    pub play_files: bool,
+    /// How many beats are counted to a bar, to find downbeats
+    #[serde(default = "default_beats_per_bar")]
+    pub beats_per_bar: u32,
+    /// Mutate on every this many downbeats, instead of when the CPU usage or the stats say to
+    #[serde(default)]
+    pub mutate_every_downbeats: Option<u32>,
+}
+
+fn default_beats_per_bar() -> u32 {
+    4
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(path) => vec![path],
+        OneOrMany::Many(paths) => paths,
+    })
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
+pub struct OscConfig {
+    /// The UDP port to listen for OSC messages on
+    pub port: u16,
+    /// Addresses nodes can use before anything has been sent to them
+    #[serde(default)]
+    pub addresses: Vec<String>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MidiConfig {
+    /// Connects to the first input port with `name` in its name, or the first port if not given
+    Port { name: Option<String> },
+    /// Creates a virtual port for other programs to connect to, not supported on Windows
+    Virtual { name: String },
+    /// Plays back a recorded MIDI file on a loop
+    File { path: String },
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct RemoteConfig {
+    /// The port the control server listens on, it only accepts connections from localhost
+    pub port: u16,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct GfycatConfig {
     pub client_id: String,
     pub client_secret: String,
@@ -135,4 +516,141 @@ mod tests {
         )))
         .unwrap();
     }
+
+    fn default_constants_value() -> Value {
+        serde_yaml::from_str(include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../constants.default.yml"
+        )))
+        .unwrap()
+    }
+
+    #[test]
+    fn overrides_replace_constants() {
+        let mut value = default_constants_value();
+
+        let overrides: Vec<ConstantOverride> = ["cell_array_width=123", "auto_mutate=false"]
+            .iter()
+            .map(|arg| arg.parse().unwrap())
+            .collect();
+
+        for constant_override in &overrides {
+            constant_override.apply(&mut value).unwrap();
+        }
+
+        let constants = Constants::from_value(value, &overrides).unwrap();
+        assert_eq!(constants.cell_array_width, 123);
+        assert!(!constants.auto_mutate);
+    }
+
+    #[test]
+    fn nested_overrides_create_missing_configs() {
+        let mut value = default_constants_value();
+
+        "mic.gamma=0.5"
+            .parse::<ConstantOverride>()
+            .unwrap()
+            .apply(&mut value)
+            .unwrap();
+
+        assert_eq!(value["mic"]["gamma"], Value::from(0.5));
+    }
+
+    #[test]
+    fn mic_files_can_be_one_or_many() {
+        let mic = |files: &str| -> MicConfig {
+            serde_yaml::from_str(&format!(
+                "{{min_frequency: 20.0, max_frequency: 20000.0, gamma: 2.0, lerp_factor: 0.1, \
+                 range_decay_factor: 0.9, target_fps: 60.0, files: {}}}",
+                files
+            ))
+            .unwrap()
+        };
+
+        assert_eq!(mic("song.wav").files, vec!["song.wav"]);
+        assert_eq!(mic("[a.wav, b.flac]").files, vec!["a.wav", "b.flac"]);
+    }
+
+    #[test]
+    fn unknown_overrides_are_rejected() {
+        let mut value = default_constants_value();
+        let constant_override: ConstantOverride = "not_a_constant=1".parse().unwrap();
+        constant_override.apply(&mut value).unwrap();
+
+        assert!(Constants::from_value(value, &[constant_override]).is_err());
+        assert!("cell_array_width".parse::<ConstantOverride>().is_err());
+    }
+
+    #[test]
+    fn nested_overrides_are_checked_all_the_way_down() {
+        let load = |arg: &str| {
+            let mut value = default_constants_value();
+            let constant_override: ConstantOverride = arg.parse().unwrap();
+            constant_override.apply(&mut value).unwrap();
+
+            Constants::from_value(value, &[constant_override])
+        };
+
+        assert!(load("heuristics.alpha.weight=2.0").is_ok());
+        assert!(load("heuristics.alpha.wieght=2.0").is_err());
+        assert!(load("mutation_weights.root_nod=1.0").is_err());
+    }
+
+    #[test]
+    fn reloading_keeps_fixed_constants() {
+        let path =
+            std::env::temp_dir().join(format!("cellular4_reload_test_{}.yml", std::process::id()));
+        let mut value = default_constants_value();
+        fs::write(&path, serde_yaml::to_string(&value).unwrap()).unwrap();
+
+        let constants = LiveConstants::load(ConstantsSource {
+            path: Some(path.clone()),
+            ..ConstantsSource::default()
+        })
+        .unwrap();
+        let width = constants.cell_array_width;
+
+        for arg in &["cell_array_width=1", "lerp_aggressiveness=0.125"] {
+            let constant_override: ConstantOverride = arg.parse().unwrap();
+            constant_override.apply(&mut value).unwrap();
+        }
+
+        fs::write(&path, serde_yaml::to_string(&value).unwrap()).unwrap();
+        // The rewrite can land within the same mtime tick
+        constants.reload_state.lock().unwrap().modified = None;
+        constants.reload_if_changed();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(constants.cell_array_width, width);
+        assert_eq!(constants.lerp_aggressiveness, 0.125);
+    }
+
+    #[test]
+    fn constants_load_from_snapshots() {
+        let path = std::env::temp_dir().join(format!(
+            "cellular4_snapshot_constants_test_{}.yml",
+            std::process::id()
+        ));
+
+        let mut constants = default_constants_value();
+        "cell_array_width=77"
+            .parse::<ConstantOverride>()
+            .unwrap()
+            .apply(&mut constants)
+            .unwrap();
+
+        let mut snapshot = Mapping::new();
+        snapshot.insert(Value::from("seed"), Value::from(1));
+        snapshot.insert(Value::from("constants"), constants);
+        fs::write(&path, serde_yaml::to_string(&snapshot).unwrap()).unwrap();
+
+        let constants = LiveConstants::load(ConstantsSource {
+            path: Some(path.clone()),
+            from_snapshot: true,
+            overrides: Vec::new(),
+        });
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(constants.unwrap().cell_array_width, 77);
+    }
 }
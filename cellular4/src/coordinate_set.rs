@@ -5,7 +5,9 @@ use crate::{
 
 use std::f32::consts::PI;
 
-#[derive(Clone, Copy, Debug)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct CoordinateSet {
     //coordinates of update position
     //Needs to be floating point to allow for proper scaling
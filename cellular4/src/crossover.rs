@@ -0,0 +1,183 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use failure::{ensure, Fallible};
+use mutagen::{Crossover, TreeDistance};
+use rand::prelude::*;
+
+use crate::{
+    node_tree::NodeTreeLocks,
+    opts::Opts,
+    prelude::*,
+    snapshot::{save_slot_path, Snapshot, SnapshotRef},
+};
+
+/// Leaf values don't point at any nodes, so they cross by taking the other value half the time
+/// and can be copied across as they are. They're not part of a tree's structure, so they never
+/// add to the distance between two trees.
+macro_rules! leaf_impls {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl<'a> Crossover<'a> for $t {
+                type CrossArg = CrossArg<'a>;
+
+                fn crossover_rng<R: Rng + ?Sized>(
+                    &mut self,
+                    rng: &mut R,
+                    other: &Self,
+                    _arg: Self::CrossArg,
+                ) {
+                    if rng.gen_bool(0.5) {
+                        *self = other.clone();
+                    }
+                }
+
+                fn transplant(&self, _arg: Self::CrossArg) -> Self {
+                    self.clone()
+                }
+            }
+
+            impl<'a> TreeDistance<'a> for $t {
+                type DistArg = DistArg<'a>;
+
+                fn tree_distance(&self, _other: &Self, _arg: Self::DistArg) -> f64 {
+                    0.0
+                }
+            }
+        )*
+    };
+}
+
+leaf_impls!(
+    Angle,
+    Boolean,
+    Byte,
+    ColorBlendFunctions,
+    DistanceFunction,
+    ElementaryAutomataRule,
+    GamepadButton,
+    GamepadId,
+    LifeLikeAutomataRule,
+    MidiCc,
+    MidiNote,
+    Nibble,
+    NodeTreeLocks,
+    NoiseFunctions,
+    OscAddress,
+    PointSet,
+    SFloatNormaliser,
+    SInt,
+    SNFloat,
+    SNPoint,
+    UFloatNormaliser,
+    UNFloat,
+);
+
+/// Crosses the two snapshots given with `--cross` and saves the child. The child starts from the
+/// first snapshot, keeping its seed and constants, and takes a subtree from the second.
+pub fn try_cross(opts: &Opts) -> Fallible<()> {
+    ensure!(
+        opts.cross.len() == 2,
+        "--cross takes exactly two snapshots, got {}",
+        opts.cross.len()
+    );
+
+    let mut child = Snapshot::load(&opts.cross[0])?;
+    let other = Snapshot::load(&opts.cross[1])?;
+
+    child.crossover(&mut DeterministicRng::new(), &other);
+
+    let path = match &opts.cross_output {
+        Some(path) => path.clone(),
+        None => {
+            let created = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            save_slot_path(&format!("crossed_{}", created))
+        }
+    };
+
+    SnapshotRef {
+        seed: child.seed,
+        current_t: child.current_t,
+        node_tree: &child.node_tree,
+        nodes: &child.nodes,
+        data: &child.data,
+        constants: child.constants.clone(),
+    }
+    .save(&path)?;
+
+    println!("Saved the crossed tree to {}", path.display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::Path};
+
+    use structopt::StructOpt;
+
+    use super::*;
+    use crate::{
+        headless::{tests::fallback_images, HeadlessRenderer},
+        node_tree::NodeTreeField,
+    };
+
+    /// Generates a tree from `seed`, saved to `path`
+    fn generate(seed: u128, path: &Path) -> Snapshot {
+        *RNG_SEED.lock().unwrap() = seed;
+        let renderer = HeadlessRenderer::new(fallback_images());
+        renderer.snapshot().save(path).unwrap();
+
+        Snapshot::load(path).unwrap()
+    }
+
+    #[test]
+    fn never_crosses_locks() {
+        with_rng_seed(1234, || {
+            let path = env::temp_dir().join(format!(
+                "cellular4_crossover_locks_{}.yml",
+                std::process::id()
+            ));
+            let mut child = generate(1234, &path);
+            let mut other = generate(5678, &path);
+            fs::remove_file(&path).unwrap();
+
+            for &field in NodeTreeField::ALL.iter() {
+                other.node_tree.locks.set_locked(field, true);
+            }
+
+            for key in 0..20 {
+                child.crossover(&mut DeterministicRng::from_key(key), &other);
+            }
+
+            assert!(NodeTreeField::ALL
+                .iter()
+                .all(|&field| !child.node_tree.locks.is_locked(field)));
+        });
+    }
+
+    #[test]
+    fn saves_a_loadable_child() {
+        with_rng_seed(1234, || {
+            let dir = env::temp_dir().join(format!("cellular4_crossover_{}", std::process::id()));
+            let (a, b, child) = (dir.join("a.yml"), dir.join("b.yml"), dir.join("child.yml"));
+            generate(1234, &a);
+            generate(5678, &b);
+
+            try_cross(&Opts::from_iter(&[
+                "cellular4",
+                "--cross",
+                a.to_str().unwrap(),
+                b.to_str().unwrap(),
+                "--cross-output",
+                child.to_str().unwrap(),
+            ]))
+            .unwrap();
+
+            let child = Snapshot::load(&child).unwrap();
+            assert_eq!(child.seed, 1234);
+            child.node_tree.to_text(&child.nodes).unwrap();
+
+            fs::remove_dir_all(&dir).unwrap();
+        });
+    }
+}
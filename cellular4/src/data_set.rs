@@ -8,8 +8,9 @@ use crate::{
 };
 
 use generational_arena::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DataSet {
     //color
     bit_colors: Arena<ArenaSlot<BitColor>>,
@@ -5,7 +5,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ElementaryAutomataRule {
     pub pattern: [Boolean; 8],
 }
@@ -73,10 +73,10 @@ impl<'a> Mutatable<'a> for ElementaryAutomataRule {
     type MutArg = MutArg<'a>;
 
     fn mutate_rng<R: Rng + ?Sized>(&mut self, rng: &mut R, arg: Self::MutArg) {
-        if thread_rng().gen::<bool>() {
+        if rng.gen::<bool>() {
             *self = Self::generate_rng(rng, arg.into());
         } else {
-            let index = thread_rng().gen::<usize>() % 8;
+            let index = rng.gen::<usize>() % 8;
             self.pattern[index] = Boolean::new(!self.pattern[index].into_inner());
         }
     }
@@ -248,9 +248,9 @@ impl<'a> Mutatable<'a> for NeighbourCountAutomataRule {
     fn mutate_rng<R: Rng + ?Sized>(&mut self, rng: &mut R, arg: Self::MutArg) {
         // *self = Self::generate_rng(rng, arg.into());
         let n = self.neighbourhood.offsets().len() + 1;
-        let index_r = thread_rng().gen::<usize>() % n;
-        let index_g = thread_rng().gen::<usize>() % n;
-        let index_b = thread_rng().gen::<usize>() % n;
+        let index_r = rng.gen::<usize>() % n;
+        let index_g = rng.gen::<usize>() % n;
+        let index_b = rng.gen::<usize>() % n;
 
         self.truth_table[[index_r, index_g, index_b]] = BitColor::generate_rng(rng, arg.into());
     }
@@ -292,10 +292,10 @@ impl<'a> Mutatable<'a> for IndivAutomataRule {
     type MutArg = MutArg<'a>;
 
     fn mutate_rng<R: Rng + ?Sized>(&mut self, rng: &mut R, arg: Self::MutArg) {
-        if thread_rng().gen::<bool>() {
+        if rng.gen::<bool>() {
             *self = Self::generate_rng(rng, arg.into());
         } else {
-            self.rules[thread_rng().gen::<usize>() % self.neighbourhood.offsets().len()]
+            self.rules[rng.gen::<usize>() % self.neighbourhood.offsets().len()]
                 .mutate_rng(rng, arg);
         }
     }
@@ -355,10 +355,10 @@ impl<'a> Mutatable<'a> for LifeLikeAutomataRule {
     type MutArg = MutArg<'a>;
 
     fn mutate_rng<R: Rng + ?Sized>(&mut self, rng: &mut R, arg: Self::MutArg) {
-        if thread_rng().gen::<bool>() {
+        if rng.gen::<bool>() {
             *self = Self::generate_rng(rng, arg.into());
         } else {
-            self.color_rules[thread_rng().gen::<usize>() % 8].mutate_rng(rng, arg);
+            self.color_rules[rng.gen::<usize>() % 8].mutate_rng(rng, arg);
         }
     }
 }
@@ -5,7 +5,9 @@ use std::{
 };
 
 use bresenham::Bresenham;
-use mutagen::{Generatable, Mutatable, Reborrow, Updatable, UpdatableRecursively};
+use mutagen::{
+    Crossover, Generatable, Mutatable, Reborrow, TreeDistance, Updatable, UpdatableRecursively,
+};
 use nalgebra::*;
 use ndarray::prelude::*;
 use rand::prelude::*;
@@ -166,6 +168,31 @@ impl<'a, T: Mutatable<'a>> Mutatable<'a> for Buffer<T> {
     }
 }
 
+impl<'a, T: Default> Crossover<'a> for Buffer<T> {
+    type CrossArg = CrossArg<'a>;
+
+    fn crossover_rng<R: Rng + ?Sized>(
+        &mut self,
+        _rng: &mut R,
+        _other: &Self,
+        _arg: Self::CrossArg,
+    ) {
+    }
+
+    /// Buffers aren't saved with snapshots, so like loading one this only keeps the size
+    fn transplant(&self, _arg: Self::CrossArg) -> Self {
+        Buffer::new(Array2::default(self.array.dim()))
+    }
+}
+
+impl<'a, T> TreeDistance<'a> for Buffer<T> {
+    type DistArg = DistArg<'a>;
+
+    fn tree_distance(&self, _other: &Self, _arg: Self::DistArg) -> f64 {
+        0.0
+    }
+}
+
 impl<'a, T: Updatable<'a>> Updatable<'a> for Buffer<T> {
     type UpdateArg = T::UpdateArg;
 
@@ -1,4 +1,5 @@
 use mutagen::{Generatable, Mutatable, Updatable, UpdatableRecursively};
+use rand::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -17,10 +18,10 @@ pub enum ColorBlendFunctions {
 }
 
 impl ColorBlendFunctions {
-    pub fn blend(self, a: FloatColor, b: FloatColor) -> FloatColor {
+    pub fn blend<R: Rng + ?Sized>(self, a: FloatColor, b: FloatColor, rng: &mut R) -> FloatColor {
         match self {
             Self::Dissolve => {
-                if Boolean::random(&mut rand::thread_rng()).into_inner() {
+                if Boolean::random(rng).into_inner() {
                     a
                 } else {
                     b
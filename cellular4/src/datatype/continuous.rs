@@ -35,9 +35,12 @@ impl UNFloat {
         Self::new_unchecked(value.max(0.0).min(1.0))
     }
 
+    /// Replaces an out of range `value` with a random one. The rng is keyed on `value` so replays
+    /// match, which means the same out of range value always gives the same result rather than a
+    /// new one each call.
     pub fn new_random_clamped(value: f32) -> Self {
         if value < 0.0 || value > 1.0 {
-            Self::random(&mut rand::thread_rng())
+            Self::random(&mut DeterministicRng::from_key(value.to_bits().into()))
         } else {
             Self::new_unchecked(value)
         }
@@ -178,9 +181,12 @@ impl SNFloat {
         Self::new_unchecked(value.max(-1.0).min(1.0))
     }
 
+    /// Replaces an out of range `value` with a random one. The rng is keyed on `value` so replays
+    /// match, which means the same out of range value always gives the same result rather than a
+    /// new one each call.
     pub fn new_random_clamped(value: f32) -> Self {
         if value < -1.0 || value > 1.0 {
-            Self::random(&mut rand::thread_rng())
+            Self::random(&mut DeterministicRng::from_key(value.to_bits().into()))
         } else {
             Self::new_unchecked(value)
         }
@@ -503,4 +509,20 @@ mod tests {
             assert_eq!(i, i2);
         }
     }
+
+    #[test]
+    fn test_random_clamping() {
+        assert_eq!(UNFloat::new_random_clamped(0.5).into_inner(), 0.5);
+        assert_eq!(SNFloat::new_random_clamped(-0.5).into_inner(), -0.5);
+
+        for &value in &[-3.0, 1.5, 1e9] {
+            let un = UNFloat::new_random_clamped(value).into_inner();
+            let sn = SNFloat::new_random_clamped(value * 2.0).into_inner();
+
+            assert!((0.0..=1.0).contains(&un));
+            assert!((-1.0..=1.0).contains(&sn));
+            assert_eq!(UNFloat::new_random_clamped(value).into_inner(), un);
+            assert_eq!(SNFloat::new_random_clamped(value * 2.0).into_inner(), sn);
+        }
+    }
 }
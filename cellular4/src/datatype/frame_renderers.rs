@@ -117,7 +117,7 @@ impl FrameRenderers {
                 let scale_y = CONSTS.initial_window_height / CONSTS.cell_array_height as f32;
                 ggez::graphics::draw(
                     args.ctx,
-                    &args.history_step().computed_texture,
+                    args.history_step().texture(),
                     DrawParam::new()
                         .color(GgColor::new(
                             1.0,
@@ -142,7 +142,7 @@ impl FrameRenderers {
                     let scale_y = CONSTS.initial_window_height / CONSTS.cell_array_height as f32;
                     ggez::graphics::draw(
                         args.ctx,
-                        &args.history_step().computed_texture,
+                        args.history_step().texture(),
                         DrawParam::new()
                             .color(GgColor::new(1.0, 1.0, 1.0, 1.0 / args.history_len() as f32))
                             .offset([0.5, 0.5])
@@ -172,7 +172,7 @@ impl FrameRenderers {
                 let inversion_scalar = if invert { -1.0 } else { 1.0 };
                 ggez::graphics::draw(
                     args.ctx,
-                    &args.history_step().computed_texture,
+                    args.history_step().texture(),
                     DrawParam::new()
                         .color(GgColor::new(
                             1.0,
@@ -248,7 +248,7 @@ impl FrameRenderers {
 
                 ggez::graphics::draw(
                     args.ctx,
-                    &args.history_step().computed_texture,
+                    args.history_step().texture(),
                     DrawParam::new()
                         .color(GgColor::new(
                             1.0,
@@ -296,7 +296,7 @@ impl FrameRenderers {
 
                 ggez::graphics::draw(
                     args.ctx,
-                    &args.history_step().computed_texture,
+                    args.history_step().texture(),
                     DrawParam::new()
                         .color(GgColor::new(
                             1.0,
@@ -334,7 +334,7 @@ impl FrameRenderers {
                 let scale_y = CONSTS.initial_window_height / CONSTS.cell_array_height as f32;
                 ggez::graphics::draw(
                     args.ctx,
-                    &args.history_step().computed_texture,
+                    args.history_step().texture(),
                     DrawParam::new()
                         .color(GgColor::new(
                             1.0,
@@ -375,7 +375,7 @@ impl FrameRenderers {
                 let scale_y = CONSTS.initial_window_height / CONSTS.cell_array_height as f32;
                 ggez::graphics::draw(
                     args.ctx,
-                    &args.history_step().computed_texture,
+                    args.history_step().texture(),
                     DrawParam::new()
                         .color(GgColor::new(
                             1.0,
@@ -417,7 +417,7 @@ impl FrameRenderers {
 
                     ggez::graphics::draw(
                         args.ctx,
-                        &args.history_step().computed_texture,
+                        args.history_step().texture(),
                         DrawParam::new()
                             .color(GgColor::new(1.0, 1.0, 1.0, 1.0 / args.history_len() as f32))
                             .offset([0.5, 0.5])
@@ -593,7 +593,7 @@ impl FrameRenderers {
 
                 ggez::graphics::draw(
                     args.ctx,
-                    &args.history_step().computed_texture,
+                    args.history_step().texture(),
                     DrawParam::new()
                         .color(GgColor::new(1.0, 1.0, 1.0, alpha))
                         .dest([dest_x, dest_y])
@@ -154,6 +154,10 @@ impl Image {
         }))
     }
 
+    pub fn fallback() -> Self {
+        FALLBACK_IMAGE.clone()
+    }
+
     pub fn load_file<P: AsRef<Path>>(path: P) -> image::ImageResult<Self> {
         Ok(Self::new(
             ImageSource::Local(path.as_ref().to_owned()),
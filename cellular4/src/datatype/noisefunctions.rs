@@ -8,7 +8,7 @@ use serde::{de::Deserializer, ser::Serializer, Deserialize, Serialize};
 
 use crate::prelude::*;
 
-#[derive(Serialize, Deserialize, Generatable, Mutatable, Debug)]
+#[derive(Serialize, Deserialize, Generatable, Mutatable, Debug, Clone)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
 pub enum NoiseFunctions {
     BasicMulti(Noise<BasicMulti>),
@@ -78,8 +78,8 @@ impl PointSet {
         &self.points[0..n.min(self.points.len())]
     }
 
-    pub fn get_random_point(&self) -> SNPoint {
-        *self.points.choose(&mut thread_rng()).unwrap()
+    pub fn get_random_point<R: Rng + ?Sized>(&self, rng: &mut R) -> SNPoint {
+        *self.points.choose(rng).unwrap()
     }
 
     pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
@@ -600,7 +600,7 @@ impl PointSetGenerator {
     }
 
     fn load(&self) -> PointSet {
-        self.generate_point_set(&mut rand::thread_rng())
+        self.generate_point_set(&mut DeterministicRng::new())
     }
 }
 
@@ -0,0 +1,204 @@
+use std::{
+    cmp::Ordering,
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use failure::{ensure, Fallible};
+use log::{error, info};
+use rand::prelude::*;
+
+use crate::{
+    headless::HeadlessRenderer,
+    opts::{EvolutionOpts, Opts},
+    prelude::*,
+    snapshot::{save_slot_path, Snapshot},
+};
+
+/// Renders a tree for `ticks` tics and scores it by how little the heuristics wanted it mutated,
+/// from 0 to 1
+fn score(renderer: &mut HeadlessRenderer, ticks: usize) -> f64 {
+    let total: f64 = (0..ticks)
+        .map(|_| 1.0 - renderer.tick().mutation_likelihood())
+        .sum();
+
+    total / ticks as f64
+}
+
+/// Picks the indices of the `count` best scores, best first. Candidates closer than
+/// `min_distance` to a survivor that's already been picked are passed over, and only used to
+/// fill up the survivors if there aren't enough distinct candidates.
+fn select_survivors<F: Fn(usize, usize) -> f64>(
+    scores: &[f64],
+    count: usize,
+    distance: F,
+    min_distance: f64,
+) -> Vec<usize> {
+    let mut ranked: Vec<usize> = (0..scores.len()).collect();
+    ranked.sort_by(|&a, &b| scores[b].partial_cmp(&scores[a]).unwrap_or(Ordering::Equal));
+
+    let mut survivors = Vec::with_capacity(count);
+    let mut passed_over = Vec::new();
+
+    for candidate in ranked {
+        if survivors.len() == count {
+            break;
+        }
+
+        if survivors
+            .iter()
+            .all(|&survivor| distance(survivor, candidate) >= min_distance)
+        {
+            survivors.push(candidate);
+        } else {
+            passed_over.push(candidate);
+        }
+    }
+
+    let missing = count - survivors.len();
+    survivors.extend(passed_over.into_iter().take(missing));
+
+    survivors
+}
+
+/// Builds the next generation from the saved survivors. The survivors carry on unchanged, and
+/// the rest of the population is made of mutated copies of them, some crossed with a second
+/// survivor first.
+fn next_generation(
+    rng: &mut DeterministicRng,
+    opts: &EvolutionOpts,
+    survivors: &[Vec<u8>],
+    image_preloader: &Rc<Preloader<Image>>,
+) -> Fallible<Vec<HeadlessRenderer>> {
+    let renderer = |snapshot| {
+        HeadlessRenderer::with_size(
+            snapshot,
+            Rc::clone(image_preloader),
+            opts.evolve_width,
+            opts.evolve_height,
+        )
+    };
+
+    let mut population = Vec::with_capacity(opts.population);
+
+    for bytes in survivors {
+        population.push(renderer(Snapshot::from_bytes(bytes)?));
+    }
+
+    while population.len() < opts.population {
+        let mut child = Snapshot::from_bytes(survivors.choose(rng).unwrap())?;
+
+        if survivors.len() > 1 && rng.gen_bool(opts.crossover_rate) {
+            let other = Snapshot::from_bytes(survivors.choose(rng).unwrap())?;
+            child.crossover(rng, &other);
+        }
+
+        let mut child = renderer(child);
+        child.mutate(rng);
+        population.push(child);
+    }
+
+    Ok(population)
+}
+
+fn try_run(opts: Opts, image_preloader: Rc<Preloader<Image>>) -> Fallible<()> {
+    let opts = opts.evolution;
+
+    ensure!(opts.population > 0, "--population must be at least 1");
+    ensure!(opts.generations > 0, "--generations must be at least 1");
+    ensure!(opts.evolve_ticks > 0, "--evolve-ticks must be at least 1");
+    ensure!(
+        opts.survivors > 0 && opts.survivors <= opts.population,
+        "--survivors must be between 1 and --population ({}), got {}",
+        opts.population,
+        opts.survivors
+    );
+    ensure!(
+        (0.0..=1.0).contains(&opts.crossover_rate),
+        "--crossover-rate must be between 0 and 1, got {}",
+        opts.crossover_rate
+    );
+
+    let started = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let mut rng = DeterministicRng::new();
+
+    let mut population: Vec<_> = (0..opts.population)
+        .map(|_| {
+            HeadlessRenderer::generate(
+                &mut rng,
+                Rc::clone(&image_preloader),
+                opts.evolve_width,
+                opts.evolve_height,
+            )
+        })
+        .collect();
+
+    for generation in 0..opts.generations {
+        let scores: Vec<f64> = population
+            .iter_mut()
+            .map(|renderer| score(renderer, opts.evolve_ticks))
+            .collect();
+
+        let survivors = select_survivors(
+            &scores,
+            opts.survivors,
+            |a, b| population[a].tree_distance(&population[b]),
+            opts.min_tree_distance,
+        );
+
+        info!(
+            "Generation {}: best score {:.3}, mean score {:.3}",
+            generation,
+            scores[survivors[0]],
+            scores.iter().sum::<f64>() / scores.len() as f64
+        );
+
+        let mut survivor_bytes = Vec::with_capacity(survivors.len());
+
+        for (rank, &index) in survivors.iter().enumerate() {
+            let snapshot = population[index].snapshot();
+            snapshot.save(save_slot_path(&format!("evolved_{}_{}", started, rank)))?;
+            survivor_bytes.push(snapshot.to_bytes()?);
+        }
+
+        if generation + 1 < opts.generations {
+            population = next_generation(&mut rng, &opts, &survivor_bytes, &image_preloader)?;
+        }
+    }
+
+    info!(
+        "Saved the best {} trees as evolved_{}_<rank>",
+        opts.survivors, started
+    );
+
+    Ok(())
+}
+
+pub fn run(opts: Opts, image_preloader: Rc<Preloader<Image>>) {
+    try_run(opts, image_preloader).unwrap_or_else(|e| error!("Evolution failed: {}", e));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn survivors_are_ranked_by_score() {
+        let scores = [0.2, 0.9, 0.5, 0.7];
+
+        assert_eq!(select_survivors(&scores, 3, |_, _| 1.0, 0.1), vec![1, 3, 2]);
+    }
+
+    #[test]
+    fn close_survivors_are_passed_over_until_needed() {
+        let scores = [0.2, 0.9, 0.8, 0.7];
+        // 1 and 2 are the same tree
+        let distance = |a: usize, b: usize| if a + b == 3 { 0.0 } else { 1.0 };
+
+        assert_eq!(select_survivors(&scores, 2, distance, 0.1), vec![1, 3]);
+        assert_eq!(
+            select_survivors(&scores, 4, distance, 0.1),
+            vec![1, 3, 0, 2]
+        );
+    }
+}
@@ -0,0 +1,629 @@
+use std::{fs, path::Path, rc::Rc};
+
+use failure::Fallible;
+use ggez::mint::Point2;
+use image::RgbaImage;
+use log::{error, info, warn};
+use mutagen::{Generatable, Mutatable, TreeDistance};
+use rand::prelude::*;
+
+use crate::{
+    node,
+    node_tree::NodeTree,
+    opts::Opts,
+    prelude::*,
+    recorder::Recorder,
+    snapshot::{Snapshot, SnapshotRef},
+    update_stat::UpdateStat,
+};
+
+/// Runs the same per-cell compute loop as the windowed game, without a ggez context.
+///
+/// Each tic computes the whole cell array at once rather than a slice per frame, and time advances
+/// by exactly one target frame per tic, so a given seed always renders the same frames.
+/// Frame renderers aren't run, the frames written out are the raw cell arrays.
+pub struct HeadlessRenderer {
+    pub history: History,
+    pub next_history_step: HistoryStep,
+
+    pub nodes: Vec<NodeSet>,
+    pub data: DataSet,
+    pub node_tree: NodeTree,
+
+    pub current_t: usize,
+
+    gamepads: Gamepads,
+    mouse_position: Point2<f32>,
+    image_preloader: Rc<Preloader<Image>>,
+    profiler: Option<MutagenProfiler>,
+    inputs: FileInputs,
+}
+
+/// The inputs nodes read from, borrowed so breeder candidates can read the game's live ones
+#[derive(Clone, Copy)]
+pub struct NodeInputs<'a> {
+    pub mic_spectrograms: &'a Option<FrequencySpectrograms>,
+    pub camera_frames: &'a Option<CameraFrames>,
+    pub osc_values: &'a Option<OscValues>,
+    pub midi_values: &'a Option<MidiValues>,
+}
+
+/// Only inputs that read from files are used headless, as they advance with the tics rather than
+/// in real time
+#[derive(Default)]
+struct FileInputs {
+    mic: Option<FftMicReader>,
+    mic_spectrograms: Option<FrequencySpectrograms>,
+    camera: Option<FileCamera>,
+    camera_frames: Option<CameraFrames>,
+    /// OSC and MIDI arrive in real time, so these are always `None`
+    osc_values: Option<OscValues>,
+    midi_values: Option<MidiValues>,
+}
+
+impl FileInputs {
+    fn new() -> Self {
+        let (mic, mic_spectrograms) = match &CONSTS.mic {
+            Some(config) if !config.files.is_empty() => {
+                // Headless tics don't run in real time, so there's no point playing the files
+                let config = MicConfig {
+                    play_files: false,
+                    ..config.clone()
+                };
+
+                match FftMicReader::new(config) {
+                    Ok(mic) => (Some(mic), Some(FrequencySpectrograms::new(256))),
+                    Err(e) => {
+                        warn!("Failed to initialize mic: {}", e);
+                        (None, None)
+                    }
+                }
+            }
+            _ => (None, None),
+        };
+
+        let (camera, camera_frames) = match &CONSTS.camera {
+            Some(CameraConfig::File(config)) => match FileCamera::new(config.clone()) {
+                Ok((camera, camera_frames)) => (Some(camera), Some(camera_frames)),
+                Err(e) => {
+                    warn!("Failed to initialize camera: {}", e);
+                    (None, None)
+                }
+            },
+            _ => (None, None),
+        };
+
+        Self {
+            mic,
+            mic_spectrograms,
+            camera,
+            camera_frames,
+            osc_values: None,
+            midi_values: None,
+        }
+    }
+
+    fn node_inputs(&self) -> NodeInputs {
+        NodeInputs {
+            mic_spectrograms: &self.mic_spectrograms,
+            camera_frames: &self.camera_frames,
+            osc_values: &self.osc_values,
+            midi_values: &self.midi_values,
+        }
+    }
+
+    fn update(&mut self, current_t: usize) {
+        if let Some(mic) = self.mic.as_mut() {
+            mic.update(self.mic_spectrograms.as_mut().unwrap())
+                .unwrap_or_else(|e| warn!("Failed to update mic: {}", e));
+        }
+
+        if let Some(camera) = self.camera.as_mut() {
+            camera
+                .update(self.camera_frames.as_mut().unwrap(), current_t)
+                .unwrap_or_else(|e| warn!("Failed to update camera: {}", e));
+        }
+    }
+}
+
+impl HeadlessRenderer {
+    /// Generates a new tree from the current [RNG_SEED]
+    pub fn new(image_preloader: Rc<Preloader<Image>>) -> Self {
+        Self::generate_with_inputs(
+            &mut DeterministicRng::new(),
+            image_preloader,
+            CONSTS.cell_array_width,
+            CONSTS.cell_array_height,
+            FileInputs::new(),
+        )
+    }
+
+    /// Generates a new tree from `rng`, rendering at a different resolution to the constants
+    pub fn generate(
+        rng: &mut DeterministicRng,
+        image_preloader: Rc<Preloader<Image>>,
+        width: usize,
+        height: usize,
+    ) -> Self {
+        Self::generate_with_inputs(rng, image_preloader, width, height, FileInputs::default())
+    }
+
+    fn generate_with_inputs(
+        rng: &mut DeterministicRng,
+        image_preloader: Rc<Preloader<Image>>,
+        width: usize,
+        height: usize,
+        inputs: FileInputs,
+    ) -> Self {
+        let history = Self::new_history(rng, width, height);
+        let mut nodes: Vec<_> = (0..=node::max_node_depth())
+            .map(|_| NodeSet::new())
+            .collect();
+        let mut data = DataSet::new();
+        let mut profiler = None;
+        let mut gamepads = Gamepads::new();
+        let mut mouse_position = Point2 { x: 0.0, y: 0.0 };
+
+        let node_tree = NodeTree::generate_rng(
+            rng,
+            GenArg {
+                nodes: &mut nodes,
+                data: &mut data,
+                depth: 0,
+                current_t: 0,
+                history: &history,
+                coordinate_set: history.history_steps[0].update_coordinate,
+                image_preloader: &*image_preloader,
+                profiler: &mut profiler,
+                mic_spectrograms: &inputs.mic_spectrograms,
+                gamepads: &mut gamepads,
+                mouse_position: &mut mouse_position,
+                camera_frames: &inputs.camera_frames,
+                osc_values: &inputs.osc_values,
+                midi_values: &inputs.midi_values,
+            },
+        );
+
+        Self {
+            history,
+            next_history_step: Self::new_history_step(rng, width, height),
+            nodes,
+            data,
+            node_tree,
+            current_t: 0,
+            gamepads,
+            mouse_position,
+            image_preloader,
+            profiler,
+            inputs,
+        }
+    }
+
+    /// Picks up a saved tree where it left off, setting [RNG_SEED] to the one it was saved with
+    pub fn from_snapshot(snapshot: Snapshot, image_preloader: Rc<Preloader<Image>>) -> Self {
+        *RNG_SEED.lock().unwrap() = snapshot.seed;
+
+        Self {
+            inputs: FileInputs::new(),
+            ..Self::with_size(
+                snapshot,
+                image_preloader,
+                CONSTS.cell_array_width,
+                CONSTS.cell_array_height,
+            )
+        }
+    }
+
+    /// Picks up a saved tree at a different resolution to the constants, without touching
+    /// [RNG_SEED]
+    pub fn with_size(
+        snapshot: Snapshot,
+        image_preloader: Rc<Preloader<Image>>,
+        width: usize,
+        height: usize,
+    ) -> Self {
+        let Snapshot {
+            current_t,
+            node_tree,
+            nodes,
+            data,
+            ..
+        } = snapshot;
+
+        let mut rng = DeterministicRng::new();
+
+        Self {
+            history: Self::new_history(&mut rng, width, height),
+            next_history_step: Self::new_history_step(&mut rng, width, height),
+            nodes,
+            data,
+            node_tree,
+            current_t,
+            gamepads: Gamepads::new(),
+            mouse_position: Point2 { x: 0.0, y: 0.0 },
+            image_preloader,
+            profiler: None,
+            inputs: FileInputs::default(),
+        }
+    }
+
+    fn new_history(rng: &mut DeterministicRng, width: usize, height: usize) -> History {
+        History::new_headless(rng, width, height, CONSTS.cell_array_history_length)
+    }
+
+    fn new_history_step(rng: &mut DeterministicRng, width: usize, height: usize) -> HistoryStep {
+        HistoryStep::new_headless(rng, width, height)
+    }
+
+    /// Mutates one of the tree's branches, as the windowed game does when it decides to mutate.
+    /// Does nothing if every branch is locked.
+    pub fn mutate<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let inputs = std::mem::take(&mut self.inputs);
+        self.mutate_with(rng, inputs.node_inputs());
+        self.inputs = inputs;
+    }
+
+    /// Mutates like [HeadlessRenderer::mutate], with nodes reading `inputs` rather than this
+    /// renderer's own
+    pub fn mutate_with<R: Rng + ?Sized>(&mut self, rng: &mut R, inputs: NodeInputs) {
+        if !self.node_tree.can_mutate() {
+            return;
+        }
+
+        let coordinate_set = self.current_step().update_coordinate;
+
+        self.node_tree.mutate_rng(
+            rng,
+            MutArg {
+                nodes: &mut self.nodes,
+                data: &mut self.data,
+                depth: 0,
+                current_t: self.current_t,
+                coordinate_set,
+                history: &self.history,
+                image_preloader: &*self.image_preloader,
+                profiler: &mut self.profiler,
+                mic_spectrograms: inputs.mic_spectrograms,
+                gamepads: &mut self.gamepads,
+                mouse_position: &mut self.mouse_position,
+                camera_frames: inputs.camera_frames,
+                osc_values: inputs.osc_values,
+                midi_values: inputs.midi_values,
+            },
+        );
+    }
+
+    /// Computes one full frame and advances the tree, returning the frame's averaged stats
+    pub fn tick(&mut self) -> UpdateStat {
+        self.inputs.update(self.current_t);
+
+        let inputs = std::mem::take(&mut self.inputs);
+        let update_stat = self.tick_with(inputs.node_inputs());
+        self.inputs = inputs;
+
+        update_stat
+    }
+
+    /// Computes a frame like [HeadlessRenderer::tick], with nodes reading `inputs` rather than
+    /// this renderer's own
+    pub fn tick_with(&mut self, inputs: NodeInputs) -> UpdateStat {
+        let current_t = self.current_t;
+        let t_coord = current_t as f32 / CONSTS.target_fps as f32;
+        let (height, width, _) = self.next_history_step.cell_array.dim();
+        let total_cells = width * height;
+
+        let history_len = self.history.history_steps.len();
+        let last_update_coordinate =
+            self.history.history_steps[current_t.saturating_sub(1) % history_len].update_coordinate;
+
+        let compute_arg = ComArg {
+            nodes: &self.nodes,
+            data: &self.data,
+            current_t,
+            coordinate_set: CoordinateSet {
+                x: SNFloat::ZERO,
+                y: SNFloat::ZERO,
+                t: t_coord,
+            },
+            history: &self.history,
+            depth: 0,
+            mic_spectrograms: inputs.mic_spectrograms,
+            gamepads: &self.gamepads,
+            mouse_position: &self.mouse_position,
+            camera_frames: inputs.camera_frames,
+            osc_values: inputs.osc_values,
+            midi_values: inputs.midi_values,
+        };
+
+        let update_stat = self.node_tree.compute_cells(
+            compute_arg.clone(),
+            self.next_history_step.cell_array.view_mut(),
+            0,
+        ) / total_cells as f64;
+
+        self.node_tree.compute_history_step(
+            compute_arg.replace_coordinate_set(&last_update_coordinate),
+            t_coord,
+            &mut self.next_history_step,
+        );
+
+        self.node_tree.update_with_arenas(UpdArg {
+            coordinate_set: self.next_history_step.update_coordinate,
+            history: &self.history,
+            nodes: &mut self.nodes,
+            data: &mut self.data,
+            depth: 0,
+            image_preloader: &*self.image_preloader,
+            profiler: &mut self.profiler,
+            mic_spectrograms: inputs.mic_spectrograms,
+            gamepads: &mut self.gamepads,
+            current_t,
+            mouse_position: &mut self.mouse_position,
+            camera_frames: inputs.camera_frames,
+            osc_values: inputs.osc_values,
+            midi_values: inputs.midi_values,
+        });
+
+        std::mem::swap(
+            &mut self.history.history_steps[current_t % history_len],
+            &mut self.next_history_step,
+        );
+
+        self.current_t += 1;
+
+        update_stat
+    }
+
+    /// The most recently computed history step
+    pub fn current_step(&self) -> &HistoryStep {
+        self.history.latest_step(self.current_t)
+    }
+
+    /// How different this tree's structure is to `other`'s, from 0 to 1
+    pub fn tree_distance(&self, other: &HeadlessRenderer) -> f64 {
+        self.node_tree.tree_distance(
+            &other.node_tree,
+            DistArg {
+                nodes: &self.nodes,
+                other_nodes: &other.nodes,
+                depth: 0,
+                other_depth: 0,
+            },
+        )
+    }
+
+    /// Borrows the tree as a snapshot, to save or copy it
+    pub fn snapshot(&self) -> SnapshotRef {
+        SnapshotRef {
+            seed: *RNG_SEED.lock().unwrap(),
+            current_t: self.current_t,
+            node_tree: &self.node_tree,
+            nodes: &self.nodes,
+            data: &self.data,
+            constants: None,
+        }
+    }
+
+    /// The most recently computed cell array as an image
+    pub fn frame_image(&self) -> Fallible<RgbaImage> {
+        self.current_step().cell_image()
+    }
+
+    pub fn save_frame<P: AsRef<Path>>(&self, path: P) -> Fallible<()> {
+        self.frame_image()?.save(path)?;
+
+        Ok(())
+    }
+}
+
+fn try_run(opts: Opts, image_preloader: Rc<Preloader<Image>>) -> Fallible<()> {
+    let mut renderer = if let Some(path) = &opts.snapshot {
+        info!("Loading snapshot from {}", path.display());
+        HeadlessRenderer::from_snapshot(Snapshot::load(path)?, image_preloader)
+    } else if let Some(path) = &opts.tree {
+        info!("Loading tree from {}", path.display());
+        HeadlessRenderer::from_snapshot(Snapshot::load_tree(path)?, image_preloader)
+    } else {
+        HeadlessRenderer::new(image_preloader)
+    };
+
+    write_frames(&mut renderer, &opts)
+}
+
+/// Runs `renderer` for `opts.ticks` tics, writing frames to `opts.output` and recording if asked
+fn write_frames(renderer: &mut HeadlessRenderer, opts: &Opts) -> Fallible<()> {
+    fs::create_dir_all(&opts.output)?;
+
+    let mut recorder = if opts.recording.record {
+        Some(Recorder::new(&opts.recording)?)
+    } else {
+        None
+    };
+
+    for tic in 0..opts.ticks {
+        renderer.tick();
+
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.record_with(|| renderer.frame_image())?;
+        }
+
+        if !opts.last_frame_only || tic + 1 == opts.ticks {
+            let path = opts
+                .output
+                .join(format!("frame_{:06}.png", renderer.current_t - 1));
+            renderer.save_frame(&path)?;
+            info!("Wrote {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+pub fn run(opts: Opts, image_preloader: Rc<Preloader<Image>>) {
+    try_run(opts, image_preloader).unwrap_or_else(|e| error!("Headless render failed: {}", e));
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::env;
+
+    use structopt::StructOpt;
+
+    use super::*;
+    use crate::{node_tree::NodeTreeField, tree_path::TreePath};
+
+    struct FallbackImageLoader;
+
+    impl Generator for FallbackImageLoader {
+        type Output = Image;
+
+        fn generate(&mut self) -> Image {
+            Image::fallback()
+        }
+    }
+
+    /// Images for tests, which can't download any
+    pub(crate) fn fallback_images() -> Rc<Preloader<Image>> {
+        Rc::new(Preloader::new(1, || FallbackImageLoader))
+    }
+
+    /// FNV-1a, so frames can be compared without keeping every one of them
+    fn stable_hash(bytes: &[u8]) -> u64 {
+        bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    fn frame_hashes(seed: u128, ticks: usize) -> Vec<u64> {
+        with_rng_seed(seed, || {
+            let mut renderer = HeadlessRenderer::new(fallback_images());
+
+            (0..ticks)
+                .map(|_| {
+                    renderer.tick();
+                    stable_hash(renderer.current_step().cell_array.as_slice().unwrap())
+                })
+                .collect()
+        })
+    }
+
+    #[test]
+    fn same_seed_renders_identical_frames() {
+        assert_eq!(frame_hashes(1234, 20), frame_hashes(1234, 20));
+    }
+
+    /// A renderer where only [NodeTreeField::RootCoordinateNode] can mutate
+    fn coordinate_only_renderer() -> HeadlessRenderer {
+        let mut renderer = HeadlessRenderer::new(fallback_images());
+
+        for &field in NodeTreeField::ALL.iter() {
+            if field != NodeTreeField::RootCoordinateNode {
+                renderer.node_tree.locks.set_locked(field, true);
+            }
+        }
+
+        renderer
+    }
+
+    /// A generated tree rendered at a size small enough to tick quickly
+    fn small_renderer() -> HeadlessRenderer {
+        let generated = HeadlessRenderer::new(fallback_images());
+        let snapshot = Snapshot::from_bytes(&generated.snapshot().to_bytes().unwrap()).unwrap();
+
+        HeadlessRenderer::with_size(snapshot, fallback_images(), 16, 16)
+    }
+
+    /// Runs a small headless render with `args`, returning how many files it wrote. Each test gives
+    /// its own `name`, as they run at the same time.
+    fn written_frames(name: &str, args: &[&str]) -> usize {
+        let output = env::temp_dir().join(format!(
+            "cellular4_headless_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let opts = Opts::from_iter(
+            [
+                "cellular4",
+                "--headless",
+                "--output",
+                output.to_str().unwrap(),
+            ]
+            .iter()
+            .chain(args),
+        );
+
+        with_rng_seed(1234, || write_frames(&mut small_renderer(), &opts)).unwrap();
+
+        let frames = fs::read_dir(&output).unwrap().count();
+        fs::remove_dir_all(&output).unwrap();
+
+        frames
+    }
+
+    #[test]
+    fn writes_a_frame_per_tic() {
+        assert_eq!(written_frames("every_tic", &["--ticks", "5"]), 5);
+    }
+
+    #[test]
+    fn writes_only_the_last_frame() {
+        assert_eq!(
+            written_frames("last_tic", &["--ticks", "5", "--last-frame-only"]),
+            1
+        );
+    }
+
+    fn mutate_many(renderer: &mut HeadlessRenderer) {
+        let mut rng = DeterministicRng::from_key(1234);
+
+        for _ in 0..50 {
+            renderer.mutate(&mut rng);
+        }
+    }
+
+    /// The tree's fields without following their node boxes, so a field only differs if it was
+    /// mutated itself
+    fn locked_fields(node_tree: &NodeTree) -> serde_yaml::Value {
+        let mut fields = serde_yaml::to_value(node_tree).unwrap();
+        fields
+            .as_mapping_mut()
+            .unwrap()
+            .remove(&"root_coordinate_node".into());
+
+        fields
+    }
+
+    #[test]
+    fn locked_fields_survive_mutation() {
+        with_rng_seed(1234, || {
+            let mut renderer = coordinate_only_renderer();
+            let before = locked_fields(&renderer.node_tree);
+
+            mutate_many(&mut renderer);
+
+            assert_eq!(locked_fields(&renderer.node_tree), before);
+        });
+    }
+
+    #[test]
+    fn locked_nodes_survive_mutation() {
+        with_rng_seed(1234, || {
+            let mut renderer = coordinate_only_renderer();
+            let path = TreePath::new(vec!["root_coordinate_node"]);
+            renderer
+                .node_tree
+                .set_node_locked(&mut renderer.nodes, &path, true)
+                .unwrap();
+            assert!(renderer
+                .node_tree
+                .is_node_locked(&renderer.nodes, &path)
+                .unwrap());
+
+            let before = renderer.node_tree.to_text(&renderer.nodes).unwrap();
+
+            mutate_many(&mut renderer);
+
+            assert_eq!(renderer.node_tree.to_text(&renderer.nodes).unwrap(), before);
+        });
+    }
+}
@@ -1,4 +1,6 @@
+use failure::{format_err, Fallible};
 use ggez::{graphics::Image as GgImage, Context};
+use image::RgbaImage;
 use ndarray::{s, Array3, ArrayView1};
 use rand::prelude::*;
 
@@ -7,7 +9,8 @@ use crate::prelude::*;
 #[derive(Debug)]
 pub struct HistoryStep {
     pub cell_array: Array3<u8>,
-    pub computed_texture: GgImage,
+    /// Only present when rendering to a window, headless runs never upload the cell array
+    pub computed_texture: Option<GgImage>,
 
     pub update_coordinate: CoordinateSet,
     pub frame_renderer: FrameRenderers,
@@ -17,21 +20,32 @@ pub struct HistoryStep {
 }
 
 impl HistoryStep {
-    pub fn new(
+    pub fn new<R: Rng + ?Sized>(
         ctx: &mut Context,
+        rng: &mut R,
         array_width: usize,
         array_height: usize,
         use_nearest_neighbour_scaling: bool,
     ) -> Self {
-        let cell_array = init_cell_array(array_width, array_height);
+        let mut step = Self::new_headless(rng, array_width, array_height);
 
+        step.computed_texture = Some(compute_texture(
+            ctx,
+            step.cell_array.view(),
+            use_nearest_neighbour_scaling,
+        ));
+
+        step
+    }
+
+    pub fn new_headless<R: Rng + ?Sized>(
+        rng: &mut R,
+        array_width: usize,
+        array_height: usize,
+    ) -> Self {
         Self {
-            computed_texture: compute_texture(
-                ctx,
-                cell_array.view(),
-                use_nearest_neighbour_scaling,
-            ),
-            cell_array,
+            cell_array: init_cell_array(rng, array_width, array_height),
+            computed_texture: None,
             update_coordinate: CoordinateSet {
                 x: SNFloat::ZERO,
                 y: SNFloat::ZERO,
@@ -43,6 +57,24 @@ impl HistoryStep {
             alpha_multiplier: UNFloat::ZERO,
         }
     }
+
+    pub fn texture(&self) -> &GgImage {
+        self.computed_texture
+            .as_ref()
+            .expect("Tried to draw a history step that has no texture")
+    }
+
+    /// The cell array as a full resolution image
+    pub fn cell_image(&self) -> Fallible<RgbaImage> {
+        let (height, width, _) = self.cell_array.dim();
+
+        RgbaImage::from_raw(
+            width as u32,
+            height as u32,
+            self.cell_array.as_slice().unwrap().to_vec(),
+        )
+        .ok_or_else(|| format_err!("Cell array is not {}x{} RGBA", width, height))
+    }
 }
 
 #[derive(Debug)]
@@ -51,10 +83,34 @@ pub struct History {
 }
 
 impl History {
-    pub fn new(ctx: &mut Context, array_width: usize, array_height: usize, size: usize) -> Self {
+    /// The step most recently written by the update that advanced time to `current_t`
+    pub fn latest_step(&self, current_t: usize) -> &HistoryStep {
+        &self.history_steps[current_t.saturating_sub(1) % self.history_steps.len()]
+    }
+
+    pub fn new<R: Rng + ?Sized>(
+        ctx: &mut Context,
+        rng: &mut R,
+        array_width: usize,
+        array_height: usize,
+        size: usize,
+    ) -> Self {
+        Self {
+            history_steps: (0..size)
+                .map(|_| HistoryStep::new(ctx, rng, array_width, array_height, false))
+                .collect(),
+        }
+    }
+
+    pub fn new_headless<R: Rng + ?Sized>(
+        rng: &mut R,
+        array_width: usize,
+        array_height: usize,
+        size: usize,
+    ) -> Self {
         Self {
             history_steps: (0..size)
-                .map(|_| HistoryStep::new(ctx, array_width, array_height, false))
+                .map(|_| HistoryStep::new_headless(rng, array_width, array_height))
                 .collect(),
         }
     }
@@ -84,13 +140,13 @@ impl History {
     }
 }
 
-fn init_cell_array(width: usize, height: usize) -> Array3<u8> {
+fn init_cell_array<R: Rng + ?Sized>(rng: &mut R, width: usize, height: usize) -> Array3<u8> {
     Array3::from_shape_fn((height, width, 4), |(_y, _x, c)| {
         if c == 3 {
             255
         } else {
             // 0
-            thread_rng().gen::<u8>()
+            rng.gen::<u8>()
         }
     })
 }
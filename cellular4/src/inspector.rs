@@ -0,0 +1,133 @@
+//! Records what every node outputs while a tree is computed, to see where a cell's colour comes
+//! from, e.g. which node is turning everything black.
+//!
+//! Nodes in the arenas are recorded as their `NodeBox` computes them. Nodes kept inline in their
+//! parent are part of their parent's output, unless they're computed through [traced] by hand.
+
+use std::{
+    cell::RefCell,
+    fmt::{self, Debug},
+};
+
+use scoped_tls::scoped_thread_local;
+
+/// A node computed while tracing, and what it output
+#[derive(Debug, Clone, PartialEq)]
+pub struct TracedNode {
+    pub node_type: &'static str,
+    pub depth: usize,
+    /// How many traced nodes this was computed under
+    pub nesting: usize,
+    pub output: String,
+}
+
+/// The nodes computed by [trace] in the order they started, so each is followed by the ones it
+/// computed
+#[derive(Debug, Default)]
+pub struct Trace {
+    pub nodes: Vec<TracedNode>,
+    nesting: usize,
+}
+
+scoped_thread_local!(static TRACE: RefCell<Trace>);
+
+/// Runs `f`, recording every node that's computed through [traced] on this thread
+pub fn trace<F: FnOnce() -> R, R>(f: F) -> (R, Trace) {
+    let trace = RefCell::new(Trace::default());
+    let result = TRACE.set(&trace, f);
+
+    (result, trace.into_inner())
+}
+
+/// Computes a node with `compute`, recording its output if this is running under [trace]
+pub fn traced<O, F>(node_type: &'static str, depth: usize, compute: F) -> O
+where
+    O: Debug,
+    F: FnOnce() -> O,
+{
+    if !TRACE.is_set() {
+        return compute();
+    }
+
+    let (index, nesting) = TRACE.with(|trace| {
+        let mut trace = trace.borrow_mut();
+        let nesting = trace.nesting;
+
+        trace.nodes.push(TracedNode {
+            node_type,
+            depth,
+            nesting,
+            output: String::new(),
+        });
+        trace.nesting += 1;
+
+        (trace.nodes.len() - 1, nesting)
+    });
+
+    // The borrow can't be held while the node computes, its children need it too
+    let output = compute();
+
+    TRACE.with(|trace| {
+        let mut trace = trace.borrow_mut();
+        trace.nesting = nesting;
+        trace.nodes[index].output = format!("{:?}", output);
+    });
+
+    output
+}
+
+/// One node per line, indented under the node that computed it
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for node in &self.nodes {
+            writeln!(
+                f,
+                "{}{} (depth {}) = {}",
+                "  ".repeat(node.nesting),
+                node.node_type,
+                node.depth,
+                node.output
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(depth: usize, a: f32, b: f32) -> f32 {
+        traced("Add", depth, || {
+            traced("Constant", depth + 1, || a) + traced("Constant", depth + 1, || b)
+        })
+    }
+
+    #[test]
+    fn records_nested_nodes() {
+        let (output, trace) = trace(|| traced("Sum", 0, || add(1, 0.5, 0.25) + add(1, 1.0, 0.0)));
+
+        assert_eq!(output, 1.75);
+        assert_eq!(
+            trace.to_string(),
+            "\
+Sum (depth 0) = 1.75
+  Add (depth 1) = 0.75
+    Constant (depth 2) = 0.5
+    Constant (depth 2) = 0.25
+  Add (depth 1) = 1.0
+    Constant (depth 2) = 1.0
+    Constant (depth 2) = 0.0
+"
+        );
+    }
+
+    #[test]
+    fn records_nothing_outside_a_trace() {
+        assert_eq!(add(0, 2.0, 3.0), 5.0);
+
+        let ((), trace) = trace(|| ());
+        assert!(trace.nodes.is_empty());
+    }
+}
@@ -0,0 +1,231 @@
+//! A directory of subtrees kept by node type, so a branch of one tree can be put into another, e.g.
+//! the coordinate mapping from one save with the colours from another.
+//!
+//! Entries are written in the [tree_text] format to `library/<node type>/<name>.tree`, e.g.
+//! `library/CoordMapNodes/swirl.tree`, so they can also be written or tidied up by hand. An entry
+//! can only be put where a node of its type goes.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use failure::{format_err, Fallible};
+use serde::Serialize;
+
+use crate::{
+    node_tree::{NodeTree, NodeTreeField},
+    prelude::*,
+    tree_path::{locate, with_node_type, TreePath},
+    tree_text, util,
+};
+
+/// The [GenericColorNodes] fields of a [NodeTree], which are kept in the tree rather than in a slot
+fn color_field(tree: &mut NodeTree, field: NodeTreeField) -> Option<&mut GenericColorNodes> {
+    match field {
+        NodeTreeField::RootNode => Some(&mut tree.root_node),
+        NodeTreeField::FadeColorNode => Some(&mut tree.fade_color_node),
+        _ => None,
+    }
+}
+
+pub struct Library {
+    dir: PathBuf,
+}
+
+impl Library {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_owned(),
+        }
+    }
+
+    fn entry_path(&self, node_type: &str, name: &str) -> PathBuf {
+        self.dir.join(node_type).join(format!("{}.tree", name))
+    }
+
+    fn write_entry(&self, node_type: &str, name: &str, text: &str) -> Fallible<PathBuf> {
+        let entry_path = self.entry_path(node_type, name);
+        fs::create_dir_all(entry_path.parent().unwrap())?;
+        fs::write(&entry_path, text)?;
+
+        Ok(entry_path)
+    }
+
+    fn read_entry(&self, node_type: &str, name: &str) -> Fallible<String> {
+        let entry_path = self.entry_path(node_type, name);
+
+        fs::read_to_string(&entry_path).map_err(|e| {
+            format_err!(
+                "Couldn't read {} {} from {}: {}",
+                node_type,
+                name,
+                entry_path.display(),
+                e
+            )
+        })
+    }
+
+    /// Copies the subtree at `path` in `tree` into the library as `name`, returning the file it was
+    /// written to
+    #[allow(clippy::ptr_arg)]
+    pub fn extract(
+        &self,
+        name: &str,
+        tree: &NodeTree,
+        nodes: &Vec<NodeSet>,
+        path: &TreePath,
+    ) -> Fallible<PathBuf> {
+        match path.tree_field() {
+            Some(NodeTreeField::RootNode) => self.write_entry(
+                "GenericColorNodes",
+                name,
+                &tree_text::to_text(&tree.root_node, nodes)?,
+            ),
+            Some(NodeTreeField::FadeColorNode) => self.write_entry(
+                "GenericColorNodes",
+                name,
+                &tree_text::to_text(&tree.fade_color_node, nodes)?,
+            ),
+            _ => self.extract_slot(name, tree, nodes, path),
+        }
+    }
+
+    #[allow(clippy::ptr_arg)]
+    fn extract_slot<R: Serialize>(
+        &self,
+        name: &str,
+        root: &R,
+        nodes: &Vec<NodeSet>,
+        path: &TreePath,
+    ) -> Fallible<PathBuf> {
+        let slot = locate(root, nodes, path)?;
+
+        let text = with_node_type!(slot.node_type.as_str(), T => {
+            let arena = <NodeSet as Storage<T>>::arena(&nodes[slot.depth]);
+            tree_text::to_text(arena[slot.index].value(), nodes)
+        })?;
+
+        self.write_entry(&slot.node_type, name, &text)
+    }
+
+    /// Puts the library's copy of `name` into `tree` at `path`, in new slots in `nodes`. The entry
+    /// has to be of the type that goes at `path`, and fit in the arenas below it.
+    ///
+    /// A node with several parents is replaced under all of them.
+    pub fn insert(
+        &self,
+        name: &str,
+        tree: &mut NodeTree,
+        nodes: &mut Vec<NodeSet>,
+        path: &TreePath,
+        current_t: usize,
+    ) -> Fallible<()> {
+        if let Some(field) = path.tree_field().and_then(|field| color_field(tree, field)) {
+            let text = self.read_entry("GenericColorNodes", name)?;
+            *field = tree_text::from_text(&text, nodes, 0, current_t)?;
+
+            return Ok(());
+        }
+
+        self.insert_slot(name, &*tree, nodes, path, current_t)
+    }
+
+    fn insert_slot<R: Serialize>(
+        &self,
+        name: &str,
+        root: &R,
+        nodes: &mut Vec<NodeSet>,
+        path: &TreePath,
+        current_t: usize,
+    ) -> Fallible<()> {
+        let slot = locate(root, nodes, path)?;
+        let text = self.read_entry(&slot.node_type, name)?;
+
+        with_node_type!(slot.node_type.as_str(), T => {
+            // The node goes in the slot, so only its children need new ones
+            let value: T = tree_text::from_text(&text, nodes, slot.depth + 1, current_t)?;
+
+            <NodeSet as Storage<T>>::arena_mut(&mut nodes[slot.depth])[slot.index]
+                .replace(value, current_t);
+
+            Ok(())
+        })
+    }
+}
+
+impl Default for Library {
+    fn default() -> Self {
+        Self::new(util::local_path("library"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_nodes(depth: usize) -> Vec<NodeSet> {
+        (0..depth).map(|_| NodeSet::new()).collect()
+    }
+
+    fn read_root(text: &str, nodes: &mut Vec<NodeSet>) -> UNFloatNodes {
+        tree_text::from_text(text, nodes, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn moves_subtrees_between_trees() {
+        let library = Library::new(
+            std::env::temp_dir().join(format!("cellular4_library_test_{}", std::process::id())),
+        );
+
+        let mut nodes_a = new_nodes(3);
+        let a = read_root(
+            "(Multiply
+              :child_a (Constant (UNFloat 0.25))
+              :child_b (Multiply :child_a (Constant (UNFloat 1.0)) :child_b (Constant (UNFloat 0.5))))",
+            &mut nodes_a,
+        );
+
+        let path = library
+            .extract_slot("half", &a, &nodes_a, &"child_b".parse().unwrap())
+            .unwrap();
+        assert!(path.ends_with("UNFloatNodes/half.tree"));
+
+        let mut nodes_b = new_nodes(2);
+        let b = read_root(
+            "(Multiply :child_a (Constant (UNFloat 0.75)) :child_b (Constant (UNFloat 0.125)))",
+            &mut nodes_b,
+        );
+
+        library
+            .insert_slot("half", &b, &mut nodes_b, &"child_a".parse().unwrap(), 1)
+            .unwrap();
+
+        assert_eq!(
+            tree_text::to_text(&b, &nodes_b).unwrap(),
+            "(Multiply
+  :child_a (Multiply :child_a (Constant (UNFloat 1.0)) :child_b (Constant (UNFloat 0.5)))
+  :child_b (Constant (UNFloat 0.125)))
+"
+        );
+
+        // There's no room under the deepest slots for the entry's children
+        assert!(library
+            .insert_slot(
+                "half",
+                &b,
+                &mut nodes_b,
+                &"child_a/child_a".parse().unwrap(),
+                2
+            )
+            .is_err());
+        assert!(library
+            .insert_slot("missing", &b, &mut nodes_b, &"child_b".parse().unwrap(), 2)
+            .is_err());
+        assert!(library
+            .insert_slot("half", &b, &mut nodes_b, &"child_c".parse().unwrap(), 2)
+            .is_err());
+
+        fs::remove_dir_all(&library.dir).unwrap();
+    }
+}
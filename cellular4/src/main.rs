@@ -6,30 +6,48 @@ extern crate gfx;
 
 use std::{
     fs,
+    path::Path,
+    process::Command,
     rc::Rc,
     thread,
     time::{Duration, Instant},
 };
 
 use cpu_monitor::CpuInstant;
+use failure::{ensure, format_err, Fallible};
 use ggez::{
     conf::{FullscreenType, WindowMode, WindowSetup},
-    event::{self, EventHandler, KeyCode, KeyMods},
+    event::{self, EventHandler, KeyCode, KeyMods, MouseButton},
     graphics,
     graphics::Image as GgImage,
     input::keyboard,
     mint::Point2,
     timer, Context, ContextBuilder, GameResult,
 };
-use log::{info, warn};
-use mutagen::{Generatable, Mutatable, Reborrow, Updatable, UpdatableRecursively};
-use ndarray::{s, ArrayViewMut1, Axis as NdAxis};
+use image::RgbaImage;
+use log::{error, info, warn};
+use mutagen::{Generatable, Mutatable};
+use ndarray::s;
 use rand::prelude::*;
-use rayon::prelude::*;
 use structopt::StructOpt;
 
 use crate::{
-    arena_wrappers::*, data_set::*, history::*, node_set::*, opts::Opts, prelude::*, ui::*,
+    arena_wrappers::*,
+    breeder::Breeder,
+    data_set::*,
+    headless::NodeInputs,
+    history::*,
+    library::Library,
+    mutation_history::{self, MutationHistory},
+    node_set::*,
+    node_tree::{MutationTarget, NodeTree, NodeTreeField},
+    opts::{Opts, RecordingOpts},
+    prelude::*,
+    recorder::Recorder,
+    remote::{RemoteCommand, RemoteServer},
+    snapshot::{save_screenshot, save_slot_path, Snapshot, SnapshotRef},
+    tree_path::TreePath,
+    ui::*,
     update_stat::UpdateStat,
 };
 
@@ -60,35 +78,107 @@ macro_rules! ldbg {
 }
 
 pub mod arena_wrappers;
+pub mod breeder;
 pub mod camera;
 pub mod constants;
 pub mod coordinate_set;
+pub mod crossover;
 pub mod data_set;
 pub mod datatype;
+pub mod evolution;
 pub mod gamepad;
 pub mod gfx_renderer;
+pub mod headless;
 pub mod history;
+pub mod inspector;
+pub mod library;
 pub mod mic;
+pub mod midi;
 pub mod mutagen_args;
+pub mod mutation_history;
 pub mod node;
 pub mod node_set;
+pub mod node_tree;
 pub mod opts;
+pub mod osc;
 pub mod preloader;
 pub mod prelude;
 pub mod profiler;
+pub mod recorder;
+pub mod remote;
+pub mod snapshot;
+pub mod tree_path;
+pub mod tree_text;
 pub mod ui;
 pub mod update_stat;
 pub mod util;
 
+fn try_init_constants(opts: &Opts) -> Fallible<()> {
+    // Screenshot snapshots carry their constants, which are used unless --config says otherwise
+    let constants = LiveConstants::load(ConstantsSource {
+        path: opts.config.clone().or_else(|| opts.snapshot.clone()),
+        from_snapshot: opts.config.is_none() && opts.snapshot.is_some(),
+        overrides: opts.overrides.clone(),
+    })?;
+
+    if opts.print_config {
+        print!("{}", serde_yaml::to_string(&constants.effective_value())?);
+    }
+
+    constants::init_constants(constants);
+
+    Ok(())
+}
+
 fn main() {
     std::env::set_var("RUST_BACKTRACE", "full");
 
     let opts = Opts::from_args();
 
+    if let Err(e) = try_init_constants(&opts) {
+        eprintln!("Failed to load constants: {}", e);
+        std::process::exit(1);
+    }
+
+    if opts.print_config {
+        return;
+    }
+
+    if let Some(seed) = opts.seed {
+        *RNG_SEED.lock().unwrap() = seed;
+    }
+
+    if !opts.cross.is_empty() {
+        if let Err(e) = crossover::try_cross(&opts) {
+            eprintln!("Failed to cross snapshots: {}", e);
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    fs::write(
+        util::local_path("last_seed.txt"),
+        &RNG_SEED.lock().unwrap().to_string(),
+    )
+    .unwrap();
+
     // We initialize the preloader before the ggez context so it is destroyed after the context.
     // The preloader can take a while to destroy since it may be waiting on IO/network,and we want the window to close responsively
     let image_preloader = Rc::new(Preloader::new(32, RandomImageLoader::new));
 
+    if opts.evolution.evolve {
+        setup_logging(&Ui::new());
+        evolution::run(opts, image_preloader);
+        return;
+    }
+
+    if opts.headless {
+        setup_logging(&Ui::new());
+        headless::run(opts, image_preloader);
+        return;
+    }
+
     let (mut ctx, event_loop) = ContextBuilder::new("cellular4", "CodeBunny")
         .window_mode(
             WindowMode::default()
@@ -107,11 +197,29 @@ fn main() {
         .build()
         .expect("Could not create ggez context!");
 
-    let my_game = MyGame::new(&mut ctx, opts, Rc::clone(&image_preloader));
+    let mut my_game = MyGame::new(&mut ctx, Rc::clone(&image_preloader), opts.recording);
+
+    if let Some(path) = &opts.snapshot {
+        my_game.load_snapshot(path);
+    }
+
+    if let Some(path) = &opts.tree {
+        my_game.load_tree(path);
+    }
 
     event::run(ctx, event_loop, my_game);
 }
 
+/// Reads back what has been drawn to the screen so far this frame
+fn capture_frame(ctx: &mut Context) -> Fallible<RgbaImage> {
+    let screenshot = graphics::screenshot(ctx).map_err(|e| format_err!("{}", e))?;
+    let (width, height) = (screenshot.width() as u32, screenshot.height() as u32);
+    let pixels = screenshot.to_rgba8(ctx).map_err(|e| format_err!("{}", e))?;
+
+    RgbaImage::from_raw(width, height, pixels)
+        .ok_or_else(|| format_err!("Screenshot is not {}x{} RGBA", width, height))
+}
+
 fn setup_logging(ui: &Ui) {
     let image_error_dispatch = fern::Dispatch::new()
         .level(log::LevelFilter::Off)
@@ -136,92 +244,6 @@ fn setup_logging(ui: &Ui) {
         .unwrap();
 }
 
-#[derive(Debug, Generatable, Mutatable, UpdatableRecursively)]
-#[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
-struct NodeTree {
-    /// The root node for the tree that computes the next screen state
-    root_node: GenericColorNodes,
-    root_coordinate_node: NodeBox<CoordMapNodes>,
-    root_frame_renderer: NodeBox<FrameRendererNodes>,
-    compute_offset_node: NodeBox<CoordMapNodes>,
-    fade_color_node: GenericColorNodes,
-    fade_color_alpha_multiplier: NodeBox<UNFloatNodes>,
-    scaling_mode_node: NodeBox<BooleanNodes>,
-}
-
-// impl NodeTree {
-//     fn try_save(&self, slot: &str) -> Fallible<()> {
-//         info!("Saving tree to slot {}", slot);
-//         let path = save_slot_path(slot);
-
-//         fs::create_dir_all(path.parent().unwrap())?;
-//         fs::write(&path, serde_yaml::to_vec(&self)?)?;
-
-//         Ok(())
-//     }
-
-//     fn save(&self, slot: &str) {
-//         self.try_save(slot)
-//             .unwrap_or_else(|e| error!("Failed to save tree to slot '{}': {}", slot, e));
-//     }
-
-//     fn try_load(&mut self, slot: &str) -> Fallible<()> {
-//         info!("Loading tree from slot {}", slot);
-//         let loaded = serde_yaml::from_slice(&fs::read(&save_slot_path(slot))?)?;
-//         *self = loaded;
-
-//         Ok(())
-//     }
-
-//     fn load(&mut self, slot: &str) {
-//         self.try_load(slot)
-//             .unwrap_or_else(|e| error!("Failed to load tree from slot '{}': {}", slot, e));
-//     }
-
-//     fn try_graph(&self) -> Fallible<()> {
-//         let tmp_dir = env::temp_dir().join("cellular3");
-//         fs::create_dir_all(&tmp_dir)?;
-
-//         let dot_path = tmp_dir.join("tree_graph.dot");
-//         fs::write(&dot_path, &dot_serde::to_vec(&self.root_node)?)?;
-//         let png_path = dot_path.with_extension("png");
-
-//         ensure!(
-//             Command::new("dot")
-//                 .arg("-T")
-//                 .arg("png")
-//                 .arg("-o")
-//                 .arg(&png_path)
-//                 .arg(&dot_path)
-//                 .status()?
-//                 .success(),
-//             "Could not run dot"
-//         );
-
-//         opener::open(png_path)?;
-
-//         Ok(())
-//     }
-
-//     fn graph(&self) {
-//         self.try_graph()
-//             .unwrap_or_else(|e| error!("Failed to graph tree: {}", e));
-//     }
-// }
-
-//fn save_slot_path(slot: &str) -> PathBuf {
-//    std::env::current_dir()
-//        .unwrap()
-//        .join("saves")
-//        .join(&format!("{}.yml", slot))
-//}
-
-impl<'a> Updatable<'a> for NodeTree {
-    type UpdateArg = UpdArg<'a>;
-
-    fn update(&mut self, _arg: UpdArg<'a>) {}
-}
-
 struct MyGame {
     history: History,
     next_history_step: HistoryStep,
@@ -241,12 +263,19 @@ struct MyGame {
     data: DataSet,
 
     node_tree: NodeTree,
+    mutation_history: MutationHistory,
 
     //record_tree: bool,
     tree_dirty: bool,
+    /// The branch to mutate when the tree is dirty, a random one if not set
+    mutation_target: Option<MutationTarget>,
+    /// Set from the remote, overrides the auto_mutate constant
+    auto_mutate: Option<bool>,
     current_t: usize,
     time_elapsed: f32,
     last_mutation_t: usize,
+    /// How many downbeats the mic had heard at the last mutation
+    last_mutation_downbeat: u64,
     last_render_t: usize,
     cpu_t: CpuInstant,
     last_update_time: Instant,
@@ -257,25 +286,31 @@ struct MyGame {
     profiler: Option<MutagenProfiler>,
     camera: Option<Camera>,
     camera_frames: Option<CameraFrames>,
+    osc: Option<OscReader>,
+    osc_values: Option<OscValues>,
+    midi: Option<MidiReader>,
+    midi_values: Option<MidiValues>,
+    remote: Option<RemoteServer>,
+
+    recording_opts: RecordingOpts,
+    recorder: Option<Recorder>,
+    /// Present while the user is picking between mutated candidates, which pauses the main tree
+    breeder: Option<Breeder>,
+    /// Set with I, clicking a cell then logs what every node computed for it
+    inspecting: bool,
 }
 
 impl MyGame {
-    pub fn new(ctx: &mut Context, opts: Opts, image_preloader: Rc<Preloader<Image>>) -> MyGame {
-        if let Some(seed) = opts.seed {
-            info!("Manually setting RNG seed");
-            *RNG_SEED.lock().unwrap() = seed;
-        }
-
-        fs::write(
-            util::local_path("last_seed.txt"),
-            &RNG_SEED.lock().unwrap().to_string(),
-        )
-        .unwrap();
-
+    pub fn new(
+        ctx: &mut Context,
+        image_preloader: Rc<Preloader<Image>>,
+        recording_opts: RecordingOpts,
+    ) -> MyGame {
         let mut rng = DeterministicRng::new();
 
         let history = History::new(
             ctx,
+            &mut rng,
             CONSTS.cell_array_width,
             CONSTS.cell_array_height,
             CONSTS.cell_array_history_length,
@@ -324,6 +359,44 @@ impl MyGame {
             (None, None)
         };
 
+        let (osc, osc_values) = if let Some(config) = &CONSTS.osc {
+            match OscReader::new(config.clone()) {
+                Ok((osc, osc_values)) => (Some(osc), Some(osc_values)),
+                Err(e) => {
+                    warn!("Failed to initialize OSC: {}", e);
+                    (None, None)
+                }
+            }
+        } else {
+            (None, None)
+        };
+
+        let (midi, midi_values) = if let Some(config) = &CONSTS.midi {
+            match MidiReader::new(config.clone()) {
+                Ok((midi, midi_values)) => (Some(midi), Some(midi_values)),
+                Err(e) => {
+                    warn!("Failed to initialize MIDI: {}", e);
+                    (None, None)
+                }
+            }
+        } else {
+            (None, None)
+        };
+
+        let remote = CONSTS.remote.clone().and_then(|config| {
+            RemoteServer::new(config)
+                .map_err(|e| warn!("Failed to start remote control server: {}", e))
+                .ok()
+        });
+
+        let recorder = if recording_opts.record {
+            Recorder::new(&recording_opts)
+                .map_err(|e| error!("Failed to start recording: {}", e))
+                .ok()
+        } else {
+            None
+        };
+
         let mut gamepads = Gamepads::new();
         let mut mouse_position = ggez::input::mouse::position(ctx);
 
@@ -331,6 +404,7 @@ impl MyGame {
             blank_texture: compute_blank_texture(ctx),
             next_history_step: HistoryStep::new(
                 ctx,
+                &mut rng,
                 CONSTS.cell_array_width,
                 CONSTS.cell_array_height,
                 false,
@@ -340,6 +414,9 @@ impl MyGame {
                 alpha_value: 0.0,
                 local_similarity_value: 0.0,
                 global_similarity_value: 0.0,
+                sharpness_value: 0.0,
+                colourfulness_value: 0.0,
+                symmetry_value: 0.0,
                 graph_stability: 0.0,
                 cpu_usage: 0.0,
             },
@@ -348,6 +425,9 @@ impl MyGame {
                 alpha_value: 0.0,
                 local_similarity_value: 0.0,
                 global_similarity_value: 0.0,
+                sharpness_value: 0.0,
+                colourfulness_value: 0.0,
+                symmetry_value: 0.0,
                 graph_stability: 0.0,
                 cpu_usage: 0.0,
             },
@@ -367,17 +447,23 @@ impl MyGame {
                     gamepads: &mut gamepads,
                     mouse_position: &mut mouse_position,
                     camera_frames: &camera_frames,
+                    osc_values: &osc_values,
+                    midi_values: &midi_values,
                 },
             ),
 
             nodes,
             data,
+            mutation_history: MutationHistory::new(),
 
             //record_tree: false,
             tree_dirty: false,
+            mutation_target: None,
+            auto_mutate: None,
             current_t: 0,
             time_elapsed: 0.0,
             last_mutation_t: 0,
+            last_mutation_downbeat: 0,
             last_render_t: 0,
             cpu_t: CpuInstant::now().unwrap(),
             last_update_time: Instant::now(),
@@ -392,8 +478,459 @@ impl MyGame {
             mouse_position,
             camera,
             camera_frames,
+            osc,
+            osc_values,
+            midi,
+            midi_values,
+            remote,
+            recorder,
+            recording_opts,
+            breeder: None,
+            inspecting: false,
+        }
+    }
+
+    fn snapshot(&self) -> SnapshotRef {
+        SnapshotRef {
+            seed: *RNG_SEED.lock().unwrap(),
+            current_t: self.current_t,
+            node_tree: &self.node_tree,
+            nodes: &self.nodes,
+            data: &self.data,
+            constants: None,
+        }
+    }
+
+    /// Swaps in a tree and its arenas, leaving the seed, time and data alone. Fields and nodes
+    /// locked now stay locked, so undo and picking a candidate don't drop them. Nodes are matched
+    /// by slot, which holds as the tree came from this one.
+    fn restore_tree(&mut self, node_tree: NodeTree, nodes: Vec<NodeSet>) {
+        let locks = std::mem::take(&mut self.node_tree.locks);
+        let old_nodes = std::mem::replace(&mut self.nodes, nodes);
+
+        for (nodes, old_nodes) in self.nodes.iter_mut().zip(&old_nodes) {
+            nodes.copy_locks_from(old_nodes);
+        }
+
+        self.node_tree = node_tree;
+        self.node_tree.locks = locks;
+        self.tree_restored();
+    }
+
+    /// Swaps in the tree, arenas and data from a file along with the locks saved in it
+    fn restore_saved_tree(&mut self, snapshot: Snapshot) {
+        self.node_tree = snapshot.node_tree;
+        self.nodes = snapshot.nodes;
+        self.data = snapshot.data;
+        self.tree_restored();
+    }
+
+    fn tree_restored(&mut self) {
+        self.last_mutation_t = self.current_t;
+        self.tree_dirty = false;
+    }
+
+    fn try_save(&self, slot: &str) -> Fallible<()> {
+        info!("Saving tree to slot {}", slot);
+
+        self.snapshot().save(save_slot_path(slot))
+    }
+
+    fn save(&self, slot: &str) {
+        self.try_save(slot)
+            .unwrap_or_else(|e| error!("Failed to save tree to slot '{}': {}", slot, e));
+    }
+
+    fn try_load(&mut self, slot: &str) -> Fallible<()> {
+        info!("Loading tree from slot {}", slot);
+
+        self.try_load_snapshot(save_slot_path(slot))
+    }
+
+    fn load(&mut self, slot: &str) {
+        self.try_load(slot)
+            .unwrap_or_else(|e| error!("Failed to load tree from slot '{}': {}", slot, e));
+    }
+
+    /// Picks up the tree, seed and time from a save or screenshot, but not its constants
+    fn try_load_snapshot<P: AsRef<Path>>(&mut self, path: P) -> Fallible<()> {
+        let snapshot = Snapshot::load(path)?;
+
+        *RNG_SEED.lock().unwrap() = snapshot.seed;
+        self.rng = DeterministicRng::new();
+        self.current_t = snapshot.current_t;
+
+        self.restore_saved_tree(snapshot);
+
+        Ok(())
+    }
+
+    fn load_snapshot(&mut self, path: &Path) {
+        info!("Loading snapshot from {}", path.display());
+
+        self.try_load_snapshot(path)
+            .unwrap_or_else(|e| error!("Failed to load snapshot from {}: {}", path.display(), e));
+    }
+
+    fn load_tree(&mut self, path: &Path) {
+        info!("Loading tree from {}", path.display());
+
+        Snapshot::load_tree(path)
+            .map(|snapshot| self.restore_saved_tree(snapshot))
+            .unwrap_or_else(|e| error!("Failed to load tree from {}: {}", path.display(), e));
+    }
+
+    fn try_write_tree(&self) -> Fallible<()> {
+        let trees_dir = util::local_path("trees");
+        fs::create_dir_all(&trees_dir)?;
+
+        let tree_path = trees_dir.join(format!("tree_{}.tree", self.current_t));
+        fs::write(&tree_path, self.node_tree.to_text(&self.nodes)?)?;
+
+        info!("Wrote tree to {}", tree_path.display());
+
+        Ok(())
+    }
+
+    fn write_tree(&self) {
+        self.try_write_tree()
+            .unwrap_or_else(|e| error!("Failed to write tree: {}", e));
+    }
+
+    fn try_screenshot(&self) -> Fallible<()> {
+        let image_path = save_screenshot(
+            &util::local_path("screenshots"),
+            &self.history.latest_step(self.current_t).cell_image()?,
+            SnapshotRef {
+                constants: Some(CONSTS.effective_value()),
+                ..self.snapshot()
+            },
+        )?;
+
+        info!("Saved screenshot to {}", image_path.display());
+
+        Ok(())
+    }
+
+    fn screenshot(&self) {
+        self.try_screenshot()
+            .unwrap_or_else(|e| error!("Failed to save screenshot: {}", e));
+    }
+
+    fn try_graph(&self) -> Fallible<()> {
+        let graphs_dir = util::local_path("graphs");
+        fs::create_dir_all(&graphs_dir)?;
+
+        let dot_path = graphs_dir.join(format!("tree_{}.dot", self.current_t));
+        fs::write(&dot_path, self.node_tree.to_dot(&self.nodes)?)?;
+        let png_path = dot_path.with_extension("png");
+
+        ensure!(
+            Command::new("dot")
+                .arg("-T")
+                .arg("png")
+                .arg("-o")
+                .arg(&png_path)
+                .arg(&dot_path)
+                .status()?
+                .success(),
+            "Could not run dot"
+        );
+
+        info!("Wrote tree graph to {}", png_path.display());
+
+        opener::open(png_path)?;
+
+        Ok(())
+    }
+
+    fn graph(&self) {
+        self.try_graph()
+            .unwrap_or_else(|e| error!("Failed to graph tree: {}", e));
+    }
+
+    fn try_start_breeding(&mut self) -> Fallible<()> {
+        let parent = self.snapshot().to_bytes()?;
+        let breeder = Breeder::new(
+            &mut self.rng,
+            &parent,
+            &self.image_preloader,
+            NodeInputs {
+                mic_spectrograms: &self.mic_spectrograms,
+                camera_frames: &self.camera_frames,
+                osc_values: &self.osc_values,
+                midi_values: &self.midi_values,
+            },
+        )?;
+
+        info!("Breeding {} candidates", breeder.len());
+        self.breeder = Some(breeder);
+
+        Ok(())
+    }
+
+    fn toggle_breeding(&mut self) {
+        if self.breeder.take().is_none() {
+            self.try_start_breeding()
+                .unwrap_or_else(|e| error!("Failed to start breeding: {}", e));
         }
     }
+
+    /// Promotes a breeder candidate to be the main tree, ending breeding
+    fn pick_candidate(&mut self, index: usize) {
+        let breeder = match self.breeder.take() {
+            Some(breeder) if index < breeder.len() => breeder,
+            breeder => {
+                self.breeder = breeder;
+                return;
+            }
+        };
+
+        info!("Picked candidate {}", index + 1);
+        self.record_mutation();
+
+        let candidate = breeder.into_candidate(index);
+        self.data = candidate.data;
+        self.restore_tree(candidate.node_tree, candidate.nodes);
+    }
+
+    fn auto_mutate(&self) -> bool {
+        self.auto_mutate.unwrap_or(CONSTS.auto_mutate)
+    }
+
+    /// Whether enough downbeats have been heard since the last mutation, if the mic is set to
+    /// mutate on them
+    fn downbeat_mutation_due(&self) -> Option<bool> {
+        let every = CONSTS.mic.as_ref()?.mutate_every_downbeats?;
+        let beat = self.mic_spectrograms.as_ref()?.beat();
+
+        Some(beat.downbeats_since(self.last_mutation_downbeat, every))
+    }
+
+    /// Restarts the rng from `seed`, the tree carries on as it is
+    fn set_seed(&mut self, seed: u128) {
+        info!("Setting seed to {}", seed);
+
+        *RNG_SEED.lock().unwrap() = seed;
+        self.rng = DeterministicRng::new();
+    }
+
+    /// Reads the latest from the gamepads, mic, camera, OSC and MIDI
+    fn update_inputs(&mut self, ctx: &Context) {
+        self.gamepads.update(ctx);
+
+        if let Some(mic) = self.mic.as_mut() {
+            mic.update(self.mic_spectrograms.as_mut().unwrap())
+                .unwrap_or_else(|e| warn!("Failed to update mic: {}", e));
+        }
+
+        if let Some(camera) = self.camera.as_mut() {
+            camera
+                .update(self.camera_frames.as_mut().unwrap(), self.current_t)
+                .unwrap_or_else(|e| warn!("Failed to update camera: {}", e));
+        }
+
+        if let Some(osc) = self.osc.as_mut() {
+            osc.update(self.osc_values.as_mut().unwrap());
+        }
+
+        if let Some(midi) = self.midi.as_mut() {
+            midi.update(self.midi_values.as_mut().unwrap());
+        }
+    }
+
+    fn handle_remote_commands(&mut self) {
+        let commands: Vec<_> = match &self.remote {
+            Some(remote) => remote.commands().collect(),
+            None => return,
+        };
+
+        for command in commands {
+            match command {
+                RemoteCommand::Mutate(target) => {
+                    self.tree_dirty = true;
+                    self.mutation_target = target;
+                }
+                RemoteCommand::Save(slot) => self.save(&slot),
+                RemoteCommand::Load(slot) => self.load(&slot),
+                RemoteCommand::SetAutoMutate(enabled) => {
+                    let enabled = enabled.unwrap_or(!self.auto_mutate());
+                    info!(
+                        "Turning auto mutation {}",
+                        if enabled { "on" } else { "off" }
+                    );
+                    self.auto_mutate = Some(enabled);
+                }
+                RemoteCommand::SetSeed(seed) => self.set_seed(seed),
+                RemoteCommand::SetLocked(field, locked) => self.set_locked(field, locked),
+                RemoteCommand::SetNodeLocked(path, locked) => self.set_node_locked(&path, locked),
+                RemoteCommand::ExtractSubtree(name, path) => self.extract_subtree(&name, &path),
+                RemoteCommand::InsertSubtree(name, path) => self.insert_subtree(&name, &path),
+            }
+        }
+    }
+
+    fn set_locked(&mut self, field: NodeTreeField, locked: bool) {
+        info!(
+            "{} {}",
+            if locked { "Locking" } else { "Unlocking" },
+            field.name()
+        );
+
+        self.node_tree.locks.set_locked(field, locked);
+    }
+
+    fn try_set_node_locked(&mut self, path: &TreePath, locked: bool) -> Fallible<()> {
+        info!(
+            "{} the node at {}",
+            if locked { "Locking" } else { "Unlocking" },
+            path
+        );
+
+        self.node_tree
+            .set_node_locked(&mut self.nodes, path, locked)
+    }
+
+    fn set_node_locked(&mut self, path: &TreePath, locked: bool) {
+        self.try_set_node_locked(path, locked)
+            .unwrap_or_else(|e| error!("Failed to lock the node at {}: {}", path, e));
+    }
+
+    fn try_extract_subtree(&self, name: &str, path: &TreePath) -> Fallible<()> {
+        let entry_path = Library::default().extract(name, &self.node_tree, &self.nodes, path)?;
+
+        info!("Copied {} to {}", path, entry_path.display());
+
+        Ok(())
+    }
+
+    fn extract_subtree(&self, name: &str, path: &TreePath) {
+        self.try_extract_subtree(name, path)
+            .unwrap_or_else(|e| error!("Failed to copy {} to the library: {}", path, e));
+    }
+
+    fn try_insert_subtree(&mut self, name: &str, path: &TreePath) -> Fallible<()> {
+        info!("Putting {} from the library at {}", name, path);
+        self.record_mutation();
+
+        Library::default().insert(
+            name,
+            &mut self.node_tree,
+            &mut self.nodes,
+            path,
+            self.current_t,
+        )
+    }
+
+    fn insert_subtree(&mut self, name: &str, path: &TreePath) {
+        self.try_insert_subtree(name, path).unwrap_or_else(|e| {
+            error!("Failed to put {} from the library at {}: {}", name, path, e)
+        });
+    }
+
+    /// Logs what every node computes for the cell under `mouse_position`, which is also what the
+    /// mouse nodes see
+    fn inspect_cell(&self, ctx: &Context, mouse_position: Point2<f32>) {
+        let (window_width, window_height) = graphics::drawable_size(ctx);
+        let cell_x = (mouse_position.x / window_width * CONSTS.cell_array_width as f32) as usize;
+        let cell_y = (mouse_position.y / window_height * CONSTS.cell_array_height as f32) as usize;
+
+        if mouse_position.x < 0.0
+            || mouse_position.y < 0.0
+            || cell_x >= CONSTS.cell_array_width
+            || cell_y >= CONSTS.cell_array_height
+        {
+            return;
+        }
+
+        let compute_arg = ComArg {
+            nodes: &self.nodes,
+            data: &self.data,
+            current_t: self.current_t,
+            coordinate_set: CoordinateSet {
+                x: SNFloat::ZERO,
+                y: SNFloat::ZERO,
+                t: self.time_elapsed,
+            },
+            history: &self.history,
+            depth: 0,
+            mic_spectrograms: &self.mic_spectrograms,
+            gamepads: &self.gamepads,
+            mouse_position: &mouse_position,
+            camera_frames: &self.camera_frames,
+            osc_values: &self.osc_values,
+            midi_values: &self.midi_values,
+        };
+
+        let (color, trace) = self.node_tree.inspect_cell(compute_arg, cell_x, cell_y);
+
+        info!(
+            "Cell ({}, {}) on tic {} computes to {:?}:\n{}",
+            cell_x, cell_y, self.current_t, color, trace
+        );
+    }
+
+    fn toggle_recording(&mut self) {
+        if self.recorder.take().is_none() {
+            self.recorder = Recorder::new(&self.recording_opts)
+                .map_err(|e| error!("Failed to start recording: {}", e))
+                .ok();
+        }
+    }
+
+    fn try_record_mutation(&mut self) -> Fallible<()> {
+        let state = mutation_history::encode_tree(&self.node_tree, &self.nodes)?;
+        self.mutation_history.record(state);
+
+        Ok(())
+    }
+
+    fn record_mutation(&mut self) {
+        self.try_record_mutation()
+            .unwrap_or_else(|e| error!("Failed to record tree before mutating: {}", e));
+    }
+
+    fn try_undo_mutation(&mut self) -> Fallible<()> {
+        if !self.mutation_history.can_undo() {
+            info!("No mutations to undo");
+            return Ok(());
+        }
+
+        info!("Undoing mutation");
+
+        let current = mutation_history::encode_tree(&self.node_tree, &self.nodes)?;
+        let state = self.mutation_history.undo(current).unwrap();
+        let (node_tree, nodes) = mutation_history::decode_tree(&state)?;
+        self.restore_tree(node_tree, nodes);
+
+        Ok(())
+    }
+
+    fn undo_mutation(&mut self) {
+        self.try_undo_mutation()
+            .unwrap_or_else(|e| error!("Failed to undo mutation: {}", e));
+    }
+
+    fn try_redo_mutation(&mut self) -> Fallible<()> {
+        if !self.mutation_history.can_redo() {
+            info!("No mutations to redo");
+            return Ok(());
+        }
+
+        info!("Redoing mutation");
+
+        let current = mutation_history::encode_tree(&self.node_tree, &self.nodes)?;
+        let state = self.mutation_history.redo(current).unwrap();
+        let (node_tree, nodes) = mutation_history::decode_tree(&state)?;
+        self.restore_tree(node_tree, nodes);
+
+        Ok(())
+    }
+
+    fn redo_mutation(&mut self) {
+        self.try_redo_mutation()
+            .unwrap_or_else(|e| error!("Failed to redo mutation: {}", e));
+    }
 }
 
 impl EventHandler<ggez::GameError> for MyGame {
@@ -401,58 +938,128 @@ impl EventHandler<ggez::GameError> for MyGame {
         &mut self,
         ctx: &mut Context,
         keycode: KeyCode,
-        _keymods: KeyMods,
-        _repeat: bool,
+        keymods: KeyMods,
+        repeat: bool,
     ) {
         if keycode == KeyCode::Escape {
             event::quit(ctx);
             return;
         }
 
-        // if !repeat {
-        //     let save_slot = match keycode {
-        //         KeyCode::Key1 => Some("1"),
-        //         KeyCode::Key2 => Some("2"),
-        //         KeyCode::Key3 => Some("3"),
-        //         KeyCode::Key4 => Some("4"),
-        //         KeyCode::Key5 => Some("5"),
-        //         KeyCode::Key6 => Some("6"),
-        //         KeyCode::Key7 => Some("7"),
-        //         KeyCode::Key8 => Some("8"),
-        //         KeyCode::Key9 => Some("9"),
-        //         KeyCode::Key0 => Some("0"),
-
-        //         _ => None,
-        //     };
-
-        //     if let Some(save_slot) = save_slot {
-        //         if keymods.contains(KeyMods::CTRL) {
-        //             self.node_tree.save(save_slot);
-        //         } else {
-        //             self.node_tree.load(save_slot);
-        //         }
-        //     }
-
-        //     if keycode == KeyCode::D {
-        //         self.node_tree.graph();
-        //     }
-
-        //     if keycode == KeyCode::Tab {
-        //         self.record_tree = !self.record_tree;
-
-        //         if self.record_tree {
-        //             self.node_tree.save("latest");
-        //         }
-
-        //         let title = if self.record_tree {
-        //             "Cellular 3 (Recording)"
-        //         } else {
-        //             "Cellular 3"
-        //         };
-
-        //         graphics::set_window_title(ctx, title);
-        //     }
-        // }
+        if !repeat {
+            let save_slot = match keycode {
+                KeyCode::Key1 => Some("1"),
+                KeyCode::Key2 => Some("2"),
+                KeyCode::Key3 => Some("3"),
+                KeyCode::Key4 => Some("4"),
+                KeyCode::Key5 => Some("5"),
+                KeyCode::Key6 => Some("6"),
+                KeyCode::Key7 => Some("7"),
+                KeyCode::Key8 => Some("8"),
+                KeyCode::Key9 => Some("9"),
+                KeyCode::Key0 => Some("0"),
+
+                _ => None,
+            };
+
+            if let Some(save_slot) = save_slot {
+                if self.breeder.is_some() {
+                    let candidate: usize = save_slot.parse().unwrap();
+
+                    if candidate > 0 {
+                        self.pick_candidate(candidate - 1);
+                    }
+                } else if keymods.contains(KeyMods::ALT) {
+                    // Alt and a number toggles the lock on that field of the tree, counting from 1.
+                    // With shift it toggles the lock on the field's top node instead.
+                    let index = save_slot.parse::<usize>().unwrap().wrapping_sub(1);
+
+                    if let Some(&field) = NodeTreeField::ALL.get(index) {
+                        if keymods.contains(KeyMods::SHIFT) {
+                            let path = TreePath::new(vec![field.name()]);
+
+                            match self.node_tree.is_node_locked(&self.nodes, &path) {
+                                Ok(locked) => self.set_node_locked(&path, !locked),
+                                Err(e) => error!("Failed to lock the node at {}: {}", path, e),
+                            }
+                        } else {
+                            let locked = self.node_tree.locks.is_locked(field);
+                            self.set_locked(field, !locked);
+                        }
+                    }
+                } else if keymods.contains(KeyMods::CTRL) {
+                    self.save(save_slot);
+                } else {
+                    self.load(save_slot);
+                }
+            }
+
+            if keycode == KeyCode::Left {
+                self.undo_mutation();
+            }
+
+            if keycode == KeyCode::Right {
+                self.redo_mutation();
+            }
+
+            if keycode == KeyCode::P {
+                self.screenshot();
+            }
+
+            if keycode == KeyCode::B {
+                self.toggle_breeding();
+            }
+
+            if keycode == KeyCode::R {
+                self.toggle_recording();
+            }
+
+            if keycode == KeyCode::D {
+                self.graph();
+            }
+
+            if keycode == KeyCode::T {
+                self.write_tree();
+            }
+
+            if keycode == KeyCode::I {
+                self.inspecting = !self.inspecting;
+                info!(
+                    "Turning the inspector {}",
+                    if self.inspecting { "on" } else { "off" }
+                );
+            }
+
+            // if keycode == KeyCode::Tab {
+            //     self.record_tree = !self.record_tree;
+
+            //     if self.record_tree {
+            //         self.save("latest");
+            //     }
+
+            //     let title = if self.record_tree {
+            //         "Cellular 3 (Recording)"
+            //     } else {
+            //         "Cellular 3"
+            //     };
+
+            //     graphics::set_window_title(ctx, title);
+            // }
+        }
+    }
+
+    fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        if button == MouseButton::Left {
+            if let Some(index) = self
+                .breeder
+                .as_ref()
+                .and_then(|b| b.candidate_at(ctx, x, y))
+            {
+                self.pick_candidate(index);
+            } else if self.breeder.is_none() && self.inspecting {
+                self.inspect_cell(ctx, Point2 { x, y });
+            }
+        }
     }
 
     fn gamepad_button_down_event(&mut self, ctx: &mut Context, _btn: GgButton, id: GgGamepadId) {
@@ -488,6 +1095,24 @@ impl EventHandler<ggez::GameError> for MyGame {
     }
 
     fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
+        if let Some(breeder) = self.breeder.as_mut() {
+            // The candidates read the game's inputs, which carry on updating while it's paused
+            breeder.tick(NodeInputs {
+                mic_spectrograms: &self.mic_spectrograms,
+                camera_frames: &self.camera_frames,
+                osc_values: &self.osc_values,
+                midi_values: &self.midi_values,
+            });
+
+            if timer::ticks(ctx) % CONSTS.tics_per_update == 0 {
+                self.update_inputs(ctx);
+            }
+
+            return Ok(());
+        }
+
+        self.handle_remote_commands();
+
         if keyboard::is_key_pressed(ctx, KeyCode::Space) {
             self.tree_dirty = true;
         }
@@ -498,131 +1123,53 @@ impl EventHandler<ggez::GameError> for MyGame {
         let slice_y = (timer::ticks(ctx) % CONSTS.tics_per_update) * slice_height;
         let slice_y_range = slice_y..slice_y + slice_height;
 
-        let mut new_update_slice =
+        let new_update_slice =
             self.next_history_step
                 .cell_array
                 .slice_mut(s![slice_y_range, .., ..]);
-        let new_update_iter = new_update_slice.lanes_mut(NdAxis(2));
 
         let history = &self.history;
-        let mic_spectrograms = &self.mic_spectrograms;
         //TODO
-        let gamepads = &self.gamepads;
         let mut mouse_position = ggez::input::mouse::position(ctx);
-        let camera_frames = &self.camera_frames;
 
         //let rule_sets = self.rule_sets;
 
-        let root_node = &self.node_tree.root_node;
-        let root_coordinate_node = &self.node_tree.root_coordinate_node;
-        let nodes = &self.nodes;
-        let data = &self.data;
         let total_cells = CONSTS.cell_array_width * CONSTS.cell_array_height;
 
         let t_coord = self.time_elapsed;
 
-        let update_step = |y, x, mut new: ArrayViewMut1<u8>| {
-            let coordinate_set = CoordinateSet {
-                x: UNFloat::new(x as f32 / CONSTS.cell_array_width as f32).to_signed(),
-                y: UNFloat::new((y + slice_y as usize) as f32 / CONSTS.cell_array_height as f32)
-                    .to_signed(),
-                // t: current_t as f32,
+        let compute_arg = ComArg {
+            nodes: &self.nodes,
+            data: &self.data,
+            current_t,
+            coordinate_set: CoordinateSet {
+                x: SNFloat::ZERO,
+                y: SNFloat::ZERO,
                 t: t_coord,
-            };
-
-            let mut compute_arg = ComArg {
-                nodes,
-                data,
-                current_t,
-                coordinate_set,
-                history,
-                depth: 0,
-                mic_spectrograms,
-                gamepads,
-                mouse_position: &mouse_position,
-                camera_frames,
-            };
-
-            let transformed_coords = root_coordinate_node.compute(compute_arg.reborrow());
-
-            let new_color = ByteColor::from(
-                root_node.compute(compute_arg.replace_coordinate_set(&transformed_coords)),
-            );
-
-            new[0] = new_color.r.into_inner();
-            new[1] = new_color.g.into_inner();
-            new[2] = new_color.b.into_inner();
-            new[3] = new_color.a.into_inner();
-
-            let current_color = history.get(x, y, current_t);
-            let older_color = history.get(x, y, usize::max(current_t, 1) - 1);
-
-            let local_offset = (
-                thread_rng().gen_range(-1..=1),
-                thread_rng().gen_range(-1..=1),
-            );
-            let local_color = history.get(
-                (x as i32 + local_offset.0)
-                    .max(0)
-                    .min(CONSTS.cell_array_width as i32 - 1) as usize,
-                (y as i32 + local_offset.1).min(CONSTS.cell_array_height as i32 - 1) as usize,
-                current_t,
-            );
-            let global_color = history.get(
-                random::<usize>() % CONSTS.cell_array_width,
-                random::<usize>() % CONSTS.cell_array_height,
-                current_t,
-            );
-
-            let older_color: FloatColor = older_color.into();
-            let current_color: FloatColor = current_color.into();
-            let local_color: FloatColor = local_color.into();
-            let global_color: FloatColor = global_color.into();
-
-            UpdateStat {
-                activity_value: f64::from(older_color.get_average() - current_color.get_average())
-                    .abs(), // / total_cells as f64
-                alpha_value: f64::from(current_color.a.into_inner()), // / total_cells as f64
-                local_similarity_value: f64::from(
-                    1.0 - (local_color.get_average() - current_color.get_average()).abs(),
-                ), // / total_cells as f64
-                global_similarity_value: f64::from(
-                    1.0 - (global_color.get_average() - current_color.get_average()).abs(),
-                ), // / total_cells as f64
-                graph_stability: 0.0, //we don't accumulate this here because we set it below
-                cpu_usage: 0.0,       //we don't accumulate this here because we set it below
-            }
+            },
+            history,
+            depth: 0,
+            mic_spectrograms: &self.mic_spectrograms,
+            gamepads: &self.gamepads,
+            mouse_position: &mouse_position,
+            camera_frames: &self.camera_frames,
+            osc_values: &self.osc_values,
+            midi_values: &self.midi_values,
         };
 
-        let zip = ndarray::Zip::indexed(new_update_iter);
-
-        let slice_update_stat: UpdateStat = if CONSTS.parallelize {
-            zip.into_par_iter()
-                .map(|((y, x), new)| update_step(y, x, new))
-                .sum()
-        } else {
-            let mut stat = UpdateStat::default();
-            zip.for_each(|(y, x), new| stat += update_step(y, x, new));
-            stat
-        } / total_cells as f64;
+        let slice_update_stat =
+            self.node_tree
+                .compute_cells(compute_arg, new_update_slice, slice_y)
+                / total_cells as f64;
 
         self.rolling_update_stat_total += slice_update_stat;
 
         if timer::ticks(ctx) % CONSTS.tics_per_update == 0 {
-            self.time_elapsed = timer::time_since_start(ctx).as_secs_f32();
-
-            self.gamepads.update(ctx);
+            CONSTS.reload_if_changed();
 
-            if let Some(mic) = self.mic.as_mut() {
-                mic.update(self.mic_spectrograms.as_mut().unwrap())
-                    .unwrap_or_else(|e| warn!("Failed to update mic: {}", e));
-            }
+            self.time_elapsed = timer::time_since_start(ctx).as_secs_f32();
 
-            if let Some(camera) = self.camera.as_mut() {
-                camera
-                    .update(self.camera_frames.as_mut().unwrap(), current_t)
-                    .unwrap_or_else(|e| warn!("Failed to update camera: {}", e));
-            }
+            self.update_inputs(ctx);
 
             let next_cpu_t = CpuInstant::now().unwrap();
             let cpu_usage = (next_cpu_t - self.cpu_t).non_idle();
@@ -631,6 +1178,10 @@ impl EventHandler<ggez::GameError> for MyGame {
             self.average_update_stat =
                 ((self.average_update_stat + self.rolling_update_stat_total) / 2.0).clamp_values();
 
+            if let Some(remote) = &self.remote {
+                remote.publish(&self.average_update_stat);
+            }
+
             //dbg!(timer::fps(ctx));
             // let node_count: usize = self.nodes.iter().map(|node_set| node_set.count_all()).sum();
             // info!("Node amount: {}", node_count);
@@ -640,6 +1191,9 @@ impl EventHandler<ggez::GameError> for MyGame {
                 alpha_value: 0.0,
                 local_similarity_value: 0.0,
                 global_similarity_value: 0.0,
+                sharpness_value: 0.0,
+                colourfulness_value: 0.0,
+                symmetry_value: 0.0,
                 graph_stability,
                 cpu_usage,
             };
@@ -660,82 +1214,55 @@ impl EventHandler<ggez::GameError> for MyGame {
 
             let history_len = self.history.history_steps.len();
             let history_index = self.current_t.saturating_sub(1) % history_len;
-            let history_step = &self.history.history_steps[history_index];
-
-            if self.tree_dirty
-                || (CONSTS.auto_mutate
-                    && (
-                        cpu_usage >= CONSTS.auto_mutate_above_cpu_usage
-                            || self.average_update_stat.should_mutate()
-                        // || dbg!(thread_rng().gen::<usize>() % CONSTS.graph_mutation_divisor) == 0
-                    ))
-            {
+            let last_update_coordinate =
+                self.history.history_steps[history_index].update_coordinate;
+
+            let auto_mutation_due = match self.downbeat_mutation_due() {
+                Some(due) => due,
+                None => {
+                    cpu_usage >= CONSTS.auto_mutate_above_cpu_usage
+                        || self.average_update_stat.should_mutate(&mut self.rng)
+                    // || dbg!(thread_rng().gen::<usize>() % CONSTS.graph_mutation_divisor) == 0
+                }
+            };
+
+            if self.tree_dirty || (self.auto_mutate() && auto_mutation_due) {
                 info!("====TIC: {} MUTATING TREE====", self.current_t);
-                if thread_rng().gen_bool(0.5) {
-                    info!("MUTATING ROOT NODE");
-                    self.node_tree.root_node.mutate_rng(
-                        &mut self.rng,
-                        MutArg {
-                            nodes: &mut self.nodes,
-                            data: &mut self.data,
-                            depth: 0,
-                            current_t,
-                            coordinate_set: history_step.update_coordinate,
-                            history: &self.history,
-                            image_preloader: &mut self.image_preloader,
-                            profiler: &mut self.profiler,
-                            mic_spectrograms: &self.mic_spectrograms,
-                            gamepads: &mut self.gamepads,
-                            mouse_position: &mut self.mouse_position,
-                            camera_frames: &self.camera_frames,
-                        },
-                    );
-                } else {
-                    if thread_rng().gen_bool(0.5) {
-                        info!("MUTATING COORD NODE");
-                        self.node_tree.root_coordinate_node.mutate_rng(
-                            &mut self.rng,
-                            MutArg {
-                                nodes: &mut self.nodes,
-                                data: &mut self.data,
-                                depth: 0,
-                                current_t,
-                                coordinate_set: history_step.update_coordinate,
-                                history: &self.history,
-                                image_preloader: &mut self.image_preloader,
-                                profiler: &mut self.profiler,
-                                mic_spectrograms: &self.mic_spectrograms,
-                                gamepads: &mut self.gamepads,
-                                mouse_position: &mut self.mouse_position,
-                                camera_frames: &self.camera_frames,
-                            },
-                        );
-                    } else {
-                        info!("MUTATING RENDERER");
-                        self.node_tree.root_frame_renderer.mutate_rng(
-                            &mut self.rng,
-                            MutArg {
-                                nodes: &mut self.nodes,
-                                data: &mut self.data,
-                                depth: 0,
-                                current_t,
-                                coordinate_set: history_step.update_coordinate,
-                                history: &self.history,
-                                image_preloader: &mut self.image_preloader,
-                                profiler: &mut self.profiler,
-                                mic_spectrograms: &self.mic_spectrograms,
-                                gamepads: &mut self.gamepads,
-                                mouse_position: &mut self.mouse_position,
-                                camera_frames: &self.camera_frames,
-                            },
-                        );
+                self.record_mutation();
+
+                let mut_arg = MutArg {
+                    nodes: &mut self.nodes,
+                    data: &mut self.data,
+                    depth: 0,
+                    current_t,
+                    coordinate_set: last_update_coordinate,
+                    history: &self.history,
+                    image_preloader: &mut self.image_preloader,
+                    profiler: &mut self.profiler,
+                    mic_spectrograms: &self.mic_spectrograms,
+                    gamepads: &mut self.gamepads,
+                    mouse_position: &mut self.mouse_position,
+                    camera_frames: &self.camera_frames,
+                    osc_values: &self.osc_values,
+                    midi_values: &self.midi_values,
+                };
+
+                match self.mutation_target.take() {
+                    Some(target) => self.node_tree.mutate_target(&mut self.rng, target, mut_arg),
+                    None if self.node_tree.can_mutate() => {
+                        self.node_tree.mutate_rng(&mut self.rng, mut_arg)
                     }
+                    None => info!("Every branch of the tree is locked, not mutating"),
                 }
+
                 // // info!("{:#?}", &self.root_node);
                 // if self.record_tree {
-                //     self.node_tree.save("latest");
+                //     self.save("latest");
                 // }
                 self.last_mutation_t = self.current_t;
+                if let Some(mic_spectrograms) = &self.mic_spectrograms {
+                    self.last_mutation_downbeat = mic_spectrograms.beat().downbeats();
+                }
                 self.tree_dirty = false;
             }
 
@@ -746,37 +1273,36 @@ impl EventHandler<ggez::GameError> for MyGame {
 
             self.gamepads.clear_in_use();
 
-            let last_update_arg = UpdArg {
-                coordinate_set: history_step.update_coordinate,
+            let last_com_arg = ComArg {
+                coordinate_set: last_update_coordinate,
                 history: &self.history,
-                nodes: &mut self.nodes,
-                data: &mut self.data,
+                nodes: &self.nodes,
+                data: &self.data,
                 depth: 0,
-                image_preloader: &mut self.image_preloader,
-                profiler: &mut self.profiler,
                 mic_spectrograms: &self.mic_spectrograms,
-                gamepads: &mut self.gamepads,
+                gamepads: &self.gamepads,
                 current_t,
-                mouse_position: &mut mouse_position,
+                mouse_position: &mouse_position,
                 camera_frames: &self.camera_frames,
+                osc_values: &self.osc_values,
+                midi_values: &self.midi_values,
             };
 
-            // dbg!(last_update_arg.coordinate_set);
-
-            self.next_history_step.update_coordinate = self
-                .node_tree
-                .compute_offset_node
-                .compute(last_update_arg.into());
+            let use_nearest_neighbour_scaling = self.node_tree.compute_history_step(
+                last_com_arg,
+                t_coord,
+                &mut self.next_history_step,
+            );
 
-            // dbg!(self.next_history_step.update_coordinate);
+            self.next_history_step.root_scalar = UNFloat::new(mutation_likelihood.powf(2.0) as f32);
 
-            //Workaround, TODO:please fix
-            //double TODO: fix this please it could be breaking other stuff
-            //triple TODO: please it's important
-            // self.next_history_step.update_coordinate.t = current_t as f32;
-            self.next_history_step.update_coordinate.t = t_coord;
+            self.next_history_step.computed_texture = Some(compute_texture(
+                ctx,
+                self.next_history_step.cell_array.view(),
+                use_nearest_neighbour_scaling,
+            ));
 
-            let mut step_upd_arg = UpdArg {
+            self.node_tree.update_with_arenas(UpdArg {
                 coordinate_set: self.next_history_step.update_coordinate,
                 history: &self.history,
                 nodes: &mut self.nodes,
@@ -789,60 +1315,9 @@ impl EventHandler<ggez::GameError> for MyGame {
                 current_t,
                 mouse_position: &mut mouse_position,
                 camera_frames: &self.camera_frames,
-            };
-
-            let mut step_com_arg: ComArg = step_upd_arg.reborrow().into();
-
-            self.next_history_step.fade_color = self
-                .node_tree
-                .fade_color_node
-                .compute(step_com_arg.reborrow());
-            self.next_history_step.alpha_multiplier = self
-                .node_tree
-                .fade_color_alpha_multiplier
-                .compute(step_com_arg.reborrow());
-
-            self.next_history_step.root_scalar = UNFloat::new(mutation_likelihood.powf(2.0) as f32);
-
-            self.next_history_step.frame_renderer = self
-                .node_tree
-                .root_frame_renderer
-                .compute(step_com_arg.reborrow());
-
-            let use_nearest_neighbour_scaling = self
-                .node_tree
-                .scaling_mode_node
-                .compute(step_com_arg.reborrow())
-                .into_inner();
-
-            self.next_history_step.computed_texture = compute_texture(
-                ctx,
-                self.next_history_step.cell_array.view(),
-                use_nearest_neighbour_scaling,
-            );
-
-            self.node_tree.update_recursively(step_upd_arg.reborrow());
-
-            for depth in 0..self.nodes.len() {
-                let (current, children) = self.nodes[depth..].split_first_mut().unwrap();
-
-                let mut step_upd_arg = UpdArg {
-                    coordinate_set: self.next_history_step.update_coordinate,
-                    history: &self.history,
-                    nodes: children,
-                    data: &mut self.data,
-                    image_preloader: &mut self.image_preloader,
-                    profiler: &mut self.profiler,
-                    mic_spectrograms: &self.mic_spectrograms,
-                    gamepads: &mut self.gamepads,
-                    depth,
-                    current_t,
-                    mouse_position: &mut mouse_position,
-                    camera_frames: &self.camera_frames,
-                };
-
-                current.update_recursively(step_upd_arg.reborrow());
-            }
+                osc_values: &self.osc_values,
+                midi_values: &self.midi_values,
+            });
 
             // Rotate the buffers by swapping
             let h_len = self.history.history_steps.len();
@@ -887,6 +1362,12 @@ impl EventHandler<ggez::GameError> for MyGame {
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
         assert!(CONSTS.cell_array_history_length > CONSTS.cell_array_lerp_length);
 
+        if let Some(breeder) = &self.breeder {
+            breeder.draw(ctx)?;
+            graphics::present(ctx)?;
+            return Ok(());
+        }
+
         if self.last_render_t != timer::ticks(ctx) {
             let lerp_sub =
                 (timer::ticks(ctx) % CONSTS.tics_per_update) as f32 / CONSTS.tics_per_update as f32;
@@ -907,6 +1388,13 @@ impl EventHandler<ggez::GameError> for MyGame {
                 args.history_step().frame_renderer.draw(args)?;
             }
 
+            if let Some(recorder) = self.recorder.as_mut() {
+                if let Err(e) = recorder.record_with(|| capture_frame(ctx)) {
+                    error!("Failed to record frame, stopping recording: {}", e);
+                    self.recorder = None;
+                }
+            }
+
             self.last_render_t = timer::ticks(ctx);
             graphics::present(ctx)?;
         }
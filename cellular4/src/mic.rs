@@ -1,13 +1,19 @@
 use std::{
+    collections::VecDeque,
+    fs::File,
+    io::BufReader,
     mem,
+    path::Path,
     sync::{Arc, Mutex},
 };
 
+use claxon::FlacReader;
 use cpal::{
     traits::{DeviceTrait, HostTrait, StreamTrait},
-    Device, Sample, SampleFormat, Stream, StreamConfig,
+    BufferSize, Device, Sample, SampleFormat, SampleRate, Stream, StreamConfig,
 };
-use failure::{format_err, Fallible};
+use failure::{ensure, format_err, Fallible};
+use hound::WavReader;
 use itertools::{izip, Itertools};
 use lerp::Lerp;
 use odds::stride::Stride;
@@ -51,6 +57,7 @@ impl FrequencySpectrogram {
 pub struct FrequencySpectrograms {
     linear: FrequencySpectrogram,
     gamma: FrequencySpectrogram,
+    beat: BeatState,
 }
 
 impl FrequencySpectrograms {
@@ -58,6 +65,7 @@ impl FrequencySpectrograms {
         Self {
             linear: FrequencySpectrogram::new(n_bins),
             gamma: FrequencySpectrogram::new(n_bins),
+            beat: BeatState::default(),
         }
     }
 
@@ -68,6 +76,237 @@ impl FrequencySpectrograms {
             &self.linear
         }
     }
+
+    pub fn beat(&self) -> &BeatState {
+        &self.beat
+    }
+}
+
+/// Onsets and beats tracked from the mic, as of the last update
+#[derive(Clone, Debug)]
+pub struct BeatState {
+    onset_strength: f32,
+    beat_phase: f32,
+    on_beat: bool,
+    tempo: f32,
+    beats: u64,
+    downbeats: u64,
+}
+
+impl Default for BeatState {
+    fn default() -> Self {
+        Self {
+            onset_strength: 0.0,
+            beat_phase: 0.0,
+            on_beat: false,
+            tempo: 60.0 / DEFAULT_BEAT_PERIOD,
+            beats: 0,
+            downbeats: 0,
+        }
+    }
+}
+
+impl BeatState {
+    /// How sudden the latest change in the spectrum was, relative to recent ones
+    pub fn onset_strength(&self) -> UNFloat {
+        UNFloat::new_clamped(self.onset_strength)
+    }
+
+    /// How far through the current beat we are, going from 0 on the beat up to 1
+    pub fn beat_phase(&self) -> UNFloat {
+        UNFloat::new_clamped(self.beat_phase)
+    }
+
+    /// Whether a beat landed since the update before
+    pub fn on_beat(&self) -> bool {
+        self.on_beat
+    }
+
+    /// The estimated tempo in beats per minute
+    pub fn tempo(&self) -> f32 {
+        self.tempo
+    }
+
+    /// How many beats have landed on the first beat of a bar so far
+    pub fn downbeats(&self) -> u64 {
+        self.downbeats
+    }
+
+    /// Whether `every` downbeats have landed since there had been `last_downbeats`
+    pub fn downbeats_since(&self, last_downbeats: u64, every: u32) -> bool {
+        self.downbeats >= last_downbeats + u64::from(every)
+    }
+}
+
+const DEFAULT_BEAT_PERIOD: f32 = 0.5;
+const MIN_TEMPO: f32 = 70.0;
+const MAX_TEMPO: f32 = 180.0;
+/// How many seconds of onsets are kept to estimate the tempo from
+const ONSET_HISTORY_SECONDS: f32 = 4.0;
+/// How often the tempo is estimated, in seconds
+const TEMPO_INTERVAL_SECONDS: f32 = 0.5;
+/// Onsets have to be this many standard deviations above the recent mean
+const ONSET_THRESHOLD_DEVIATIONS: f32 = 1.5;
+const MIN_ONSET_INTERVAL_SECONDS: f32 = 0.1;
+/// How much of the gap between an onset and the nearest beat is closed each onset
+const PHASE_CORRECTION: f32 = 0.2;
+/// How many octaves from the default beat period a tempo can be before it's half as likely to
+/// be picked, so beats aren't heard at double or half the tempo as easily
+const TEMPO_PRIOR_OCTAVES: f32 = 1.0;
+/// How much of the loudest onset is kept after a second, for normalising onset strength
+const ONSET_MAX_DECAY: f32 = 0.5;
+
+/// Finds onsets from the spectral flux of each FFT chunk, and tracks beats against them.
+///
+/// The tempo is the strongest autocorrelation of recent flux in a plausible range, and the beat
+/// phase runs at that tempo, nudged towards each onset. Bars are counted from whichever beat
+/// came first, so downbeats won't always match the music's.
+struct BeatTracker {
+    /// Seconds per FFT chunk
+    chunk_duration: f32,
+    beats_per_bar: u64,
+
+    prev_power: Vec<f32>,
+    flux_history: VecDeque<f32>,
+    flux_history_len: usize,
+    flux_max: f32,
+    adj_flux_max_decay: f32,
+
+    chunks_since_onset: usize,
+    chunks_since_tempo: usize,
+    tempo_interval: usize,
+    min_onset_interval: usize,
+
+    beat_period: f32,
+}
+
+impl BeatTracker {
+    fn new(n_powers: usize, chunk_duration: f32, beats_per_bar: u32) -> Self {
+        let seconds_to_chunks = |seconds: f32| (seconds / chunk_duration).ceil() as usize;
+
+        Self {
+            chunk_duration,
+            beats_per_bar: u64::from(beats_per_bar.max(1)),
+            prev_power: vec![0.0; n_powers],
+            flux_history: VecDeque::new(),
+            flux_history_len: seconds_to_chunks(ONSET_HISTORY_SECONDS),
+            flux_max: 0.0,
+            adj_flux_max_decay: ONSET_MAX_DECAY.powf(chunk_duration),
+            chunks_since_onset: 0,
+            chunks_since_tempo: 0,
+            tempo_interval: seconds_to_chunks(TEMPO_INTERVAL_SECONDS),
+            min_onset_interval: seconds_to_chunks(MIN_ONSET_INTERVAL_SECONDS),
+            beat_period: DEFAULT_BEAT_PERIOD,
+        }
+    }
+
+    /// Takes in the log power of each frequency in the next chunk, summed over channels
+    fn process(&mut self, power: &[f32], beat: &mut BeatState) {
+        let flux: f32 = power
+            .iter()
+            .zip(self.prev_power.iter())
+            .map(|(power, prev_power)| (power - prev_power).max(0.0))
+            .sum();
+
+        self.prev_power.copy_from_slice(power);
+
+        let is_onset = self.is_onset(flux);
+
+        if self.flux_history.len() == self.flux_history_len {
+            self.flux_history.pop_front();
+        }
+        self.flux_history.push_back(flux);
+
+        self.flux_max = (self.flux_max * self.adj_flux_max_decay).max(flux);
+        beat.onset_strength = if self.flux_max > 0.0 {
+            flux / self.flux_max
+        } else {
+            0.0
+        };
+
+        self.chunks_since_tempo += 1;
+        if self.chunks_since_tempo >= self.tempo_interval {
+            self.chunks_since_tempo = 0;
+            self.estimate_beat_period();
+            beat.tempo = 60.0 / self.beat_period;
+        }
+
+        beat.beat_phase += self.chunk_duration / self.beat_period;
+
+        if is_onset {
+            let error = if beat.beat_phase > 0.5 {
+                beat.beat_phase - 1.0
+            } else {
+                beat.beat_phase
+            };
+
+            beat.beat_phase -= error * PHASE_CORRECTION;
+        }
+
+        if beat.beat_phase >= 1.0 {
+            beat.beat_phase = beat.beat_phase.fract();
+            beat.on_beat = true;
+
+            if beat.beats % self.beats_per_bar == 0 {
+                beat.downbeats += 1;
+            }
+
+            beat.beats += 1;
+        }
+    }
+
+    fn is_onset(&mut self, flux: f32) -> bool {
+        self.chunks_since_onset += 1;
+
+        if self.flux_history.is_empty() || self.chunks_since_onset < self.min_onset_interval {
+            return false;
+        }
+
+        let len = self.flux_history.len() as f32;
+        let mean = self.flux_history.iter().sum::<f32>() / len;
+        let variance = self
+            .flux_history
+            .iter()
+            .map(|flux| (flux - mean).powi(2))
+            .sum::<f32>()
+            / len;
+
+        let is_onset = flux > mean + ONSET_THRESHOLD_DEVIATIONS * variance.sqrt();
+
+        if is_onset {
+            self.chunks_since_onset = 0;
+        }
+
+        is_onset
+    }
+
+    fn estimate_beat_period(&mut self) {
+        let min_lag = (60.0 / MAX_TEMPO / self.chunk_duration).round() as usize;
+        let max_lag = (60.0 / MIN_TEMPO / self.chunk_duration).round() as usize;
+
+        if self.flux_history.len() <= max_lag * 2 {
+            return;
+        }
+
+        let mean = self.flux_history.iter().sum::<f32>() / self.flux_history.len() as f32;
+        let flux: Vec<f32> = self.flux_history.iter().map(|flux| flux - mean).collect();
+
+        let best = (min_lag.max(1)..=max_lag)
+            .map(|lag| {
+                let correlation: f32 = flux.iter().zip(&flux[lag..]).map(|(a, b)| a * b).sum();
+                let octaves = (lag as f32 * self.chunk_duration / DEFAULT_BEAT_PERIOD).log2();
+                let prior = 0.5_f32.powf((octaves / TEMPO_PRIOR_OCTAVES).powi(2));
+
+                (lag, correlation / (flux.len() - lag) as f32 * prior)
+            })
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        if let Some((lag, correlation)) = best {
+            if correlation > 0.0 {
+                self.beat_period = lag as f32 * self.chunk_duration;
+            }
+        }
+    }
 }
 
 pub struct FftMicReader {
@@ -87,14 +326,28 @@ pub struct FftMicReader {
     min_frequency_idx: usize,
     max_frequency_idx: usize,
     norm: f32,
+
+    beat_tracker: BeatTracker,
+    /// The log power of each selected frequency in the current chunk, summed over channels
+    chunk_power: Vec<f32>,
 }
 
 impl FftMicReader {
     pub fn new(config: MicConfig) -> Fallible<Self> {
-        let mic = MicReader::build(|stream_config| {
+        let chunk_size_fn = |stream_config: &StreamConfig| {
             usize::from(stream_config.channels)
                 * chunk_size_for_fps(stream_config.sample_rate.0, config.target_fps)
-        })?;
+        };
+
+        let mic = if config.files.is_empty() {
+            MicReader::build(chunk_size_fn)?
+        } else {
+            MicReader::File(FileReader::new(
+                &config.files,
+                config.play_files,
+                chunk_size_fn,
+            )?)
+        };
 
         let stream_config = mic.config();
         let sample_rate = stream_config.sample_rate.0;
@@ -123,6 +376,13 @@ impl FftMicReader {
         let max_frequency_idx =
             frequency_to_fft_idx(config.max_frequency, sample_rate, fft_out_buf.len());
 
+        let n_powers = max_frequency_idx - min_frequency_idx;
+        let beat_tracker = BeatTracker::new(
+            n_powers,
+            channel_chunk_size as f32 / sample_rate as f32,
+            config.beats_per_bar,
+        );
+
         Ok(Self {
             config,
             mic,
@@ -136,6 +396,8 @@ impl FftMicReader {
             min_frequency_idx,
             max_frequency_idx,
             norm,
+            beat_tracker,
+            chunk_power: vec![0.0; n_powers],
         })
     }
 
@@ -143,6 +405,10 @@ impl FftMicReader {
         let stream_config = self.mic.config();
         let num_channels = stream_config.channels as usize;
 
+        self.mic.advance_tick();
+
+        spectrograms.beat.on_beat = false;
+
         let mut first = true;
 
         while let Some(chunk) = self.mic.next_chunk() {
@@ -160,6 +426,10 @@ impl FftMicReader {
 
             assert_eq!(self.fft_in_buf.len(), chunk.len() / num_channels);
 
+            for power in self.chunk_power.iter_mut() {
+                *power = 0.0;
+            }
+
             for channel_i in 0..num_channels {
                 for (fft_sample, w, sample) in izip!(
                     self.fft_in_buf.iter_mut(),
@@ -187,6 +457,8 @@ impl FftMicReader {
                     let power = scaled.norm_sqr();
                     let power_log = (power + 1.0).ln();
 
+                    self.chunk_power[fft_idx] += power_log;
+
                     for (gamma, spectrogram, prev_bin_idx) in &mut [
                         (
                             self.config.gamma,
@@ -223,6 +495,9 @@ impl FftMicReader {
                     }
                 }
             }
+
+            self.beat_tracker
+                .process(&self.chunk_power, &mut spectrograms.beat);
         }
 
         Ok(())
@@ -273,6 +548,7 @@ pub enum MicReader {
     F32(StreamReader<f32>),
     I16(ConvertingStreamReader<i16, f32>),
     U16(ConvertingStreamReader<u16, f32>),
+    File(FileReader),
 }
 
 impl MicReader {
@@ -306,6 +582,7 @@ impl MicReader {
             MicReader::F32(r) => r.chunk_size(),
             MicReader::I16(r) => r.chunk_size(),
             MicReader::U16(r) => r.chunk_size(),
+            MicReader::File(r) => r.chunk_size(),
         }
     }
 
@@ -314,6 +591,7 @@ impl MicReader {
             MicReader::F32(r) => r.next_chunk(),
             MicReader::I16(r) => r.next_chunk(),
             MicReader::U16(r) => r.next_chunk(),
+            MicReader::File(r) => r.next_chunk(),
         }
     }
 
@@ -322,7 +600,236 @@ impl MicReader {
             MicReader::F32(r) => r.config(),
             MicReader::I16(r) => r.config(),
             MicReader::U16(r) => r.config(),
+            MicReader::File(r) => r.config(),
+        }
+    }
+
+    /// Lets files read another tic's worth of audio. Devices fill up in real time by themselves.
+    pub fn advance_tick(&mut self) {
+        if let MicReader::File(r) = self {
+            r.advance_tick();
+        }
+    }
+}
+
+/// Reads decoded audio files in step with the tics, rather than in real time, so the same
+/// tic always hears the same audio
+pub struct FileReader {
+    /// Interleaved samples of every file, one after the other
+    samples: Vec<f32>,
+    /// The next sample to read into a chunk
+    position: usize,
+    /// How many samples the tics so far have made available, but haven't been read yet
+    available: usize,
+    samples_per_tick: usize,
+    chunk_size: usize,
+    chunk_buf: Vec<f32>,
+    config: StreamConfig,
+    playback: Option<FilePlayback>,
+    /// The next sample to play back
+    playback_position: usize,
+}
+
+impl FileReader {
+    pub fn new<P: AsRef<Path>, F: FnMut(&StreamConfig) -> usize>(
+        paths: &[P],
+        play: bool,
+        mut chunk_size_fn: F,
+    ) -> Fallible<Self> {
+        let mut samples = Vec::new();
+        let mut config = None;
+
+        for path in paths {
+            let path = path.as_ref();
+            let (file_samples, file_config) = decode_audio_file(path)?;
+
+            let config = config.get_or_insert_with(|| file_config.clone());
+            ensure!(
+                config.channels == file_config.channels
+                    && config.sample_rate == file_config.sample_rate,
+                "{} has {} channels at {}Hz, but the first file has {} channels at {}Hz",
+                path.display(),
+                file_config.channels,
+                file_config.sample_rate.0,
+                config.channels,
+                config.sample_rate.0
+            );
+
+            samples.extend(file_samples);
+        }
+
+        let config = config.ok_or_else(|| format_err!("No audio files given"))?;
+        ensure!(!samples.is_empty(), "The audio files are empty");
+
+        let channels = usize::from(config.channels);
+        let samples_per_tick =
+            (config.sample_rate.0 as f32 / CONSTS.target_fps as f32).round() as usize * channels;
+
+        let playback = if play {
+            Some(FilePlayback::new(&config)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            samples,
+            position: 0,
+            available: 0,
+            samples_per_tick,
+            chunk_size: chunk_size_fn(&config),
+            chunk_buf: Vec::new(),
+            config,
+            playback,
+            playback_position: 0,
+        })
+    }
+
+    pub fn advance_tick(&mut self) {
+        self.available += self.samples_per_tick;
+
+        if let Some(playback) = &self.playback {
+            let mut queue = playback.queue.lock().unwrap();
+            let (samples, position) = (&self.samples, &mut self.playback_position);
+
+            read_looping(samples, position, self.samples_per_tick, |slice| {
+                queue.extend(slice)
+            });
+        }
+    }
+
+    pub fn next_chunk(&mut self) -> Option<&mut [f32]> {
+        if self.available < self.chunk_size {
+            return None;
+        }
+
+        self.available -= self.chunk_size;
+
+        let chunk_buf = &mut self.chunk_buf;
+        chunk_buf.clear();
+        read_looping(
+            &self.samples,
+            &mut self.position,
+            self.chunk_size,
+            |slice| chunk_buf.extend_from_slice(slice),
+        );
+
+        Some(&mut self.chunk_buf)
+    }
+
+    pub fn config(&self) -> &StreamConfig {
+        &self.config
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+}
+
+/// Passes the next `count` samples from `position` to `read`, going back to the start of
+/// `samples` whenever the end is reached
+fn read_looping<F: FnMut(&[f32])>(
+    samples: &[f32],
+    position: &mut usize,
+    mut count: usize,
+    mut read: F,
+) {
+    while count > 0 {
+        let end = (*position + count).min(samples.len());
+
+        read(&samples[*position..end]);
+
+        count -= end - *position;
+        *position = end % samples.len();
+    }
+}
+
+/// Decodes a WAV or FLAC file to interleaved samples from -1 to 1
+fn decode_audio_file(path: &Path) -> Fallible<(Vec<f32>, StreamConfig)> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase);
+
+    let (samples, channels, sample_rate) = match extension.as_deref() {
+        Some("wav") => {
+            let reader = WavReader::open(path)?;
+            let spec = reader.spec();
+
+            let samples = match spec.sample_format {
+                hound::SampleFormat::Float => {
+                    reader.into_samples::<f32>().collect::<Result<_, _>>()?
+                }
+                hound::SampleFormat::Int => {
+                    let scale = 1.0 / (1_i64 << (spec.bits_per_sample - 1)) as f32;
+
+                    reader
+                        .into_samples::<i32>()
+                        .map(|sample| sample.map(|sample| sample as f32 * scale))
+                        .collect::<Result<_, _>>()?
+                }
+            };
+
+            (samples, spec.channels, spec.sample_rate)
+        }
+        Some("flac") => {
+            let mut reader = FlacReader::new(BufReader::new(File::open(path)?))?;
+            let info = reader.streaminfo();
+            let scale = 1.0 / (1_i64 << (info.bits_per_sample - 1)) as f32;
+
+            let samples = reader
+                .samples()
+                .map(|sample| sample.map(|sample| sample as f32 * scale))
+                .collect::<Result<_, _>>()?;
+
+            (samples, info.channels as u16, info.sample_rate)
         }
+        _ => return Err(format_err!("{} isn't a WAV or FLAC file", path.display())),
+    };
+
+    Ok((
+        samples,
+        StreamConfig {
+            channels,
+            sample_rate: SampleRate(sample_rate),
+            buffer_size: BufferSize::Default,
+        },
+    ))
+}
+
+/// Plays back samples as a [FileReader] reads them, so what's heard matches the tics even if
+/// they run slow
+pub struct FilePlayback {
+    queue: Arc<Mutex<VecDeque<f32>>>,
+    _stream: Stream,
+}
+
+impl FilePlayback {
+    pub fn new(config: &StreamConfig) -> Fallible<Self> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or_else(|| format_err!("No output device available"))?;
+
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+        let queue_worker = Arc::clone(&queue);
+
+        let stream = device.build_output_stream(
+            config,
+            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                let mut queue = queue_worker.lock().unwrap();
+
+                for sample in data.iter_mut() {
+                    *sample = queue.pop_front().unwrap_or(0.0);
+                }
+            },
+            move |err| println!("Audio error: {}", err),
+        )?;
+
+        stream.play()?;
+
+        Ok(Self {
+            queue,
+            _stream: stream,
+        })
     }
 }
 
@@ -456,3 +963,71 @@ where
         self.reader.chunk_size()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn beat_tracker_finds_tempo_of_regular_onsets() {
+        let chunk_duration = 0.01;
+        let mut tracker = BeatTracker::new(1, chunk_duration, 4);
+        let mut beat = BeatState::default();
+
+        // A click every 0.4 seconds, 150bpm
+        for chunk in 0..1000 {
+            let power = if chunk % 40 == 0 { 1.0 } else { 0.0 };
+            tracker.process(&[power], &mut beat);
+        }
+
+        assert!((beat.tempo() - 150.0).abs() < 5.0, "{}", beat.tempo());
+        assert!(beat.downbeats() > 0);
+    }
+
+    /// Runs the tracker over steady clicks, mutating every `every` downbeats like the game does.
+    /// Returns the beat state and how many mutations there were.
+    fn mutate_on_downbeats(beats_per_bar: u32, every: u32) -> (BeatState, u64) {
+        let mut tracker = BeatTracker::new(1, 0.01, beats_per_bar);
+        let mut beat = BeatState::default();
+        let mut last_mutation_downbeats = 0;
+        let mut mutations = 0;
+
+        for chunk in 0..1000 {
+            let power = if chunk % 40 == 0 { 1.0 } else { 0.0 };
+            tracker.process(&[power], &mut beat);
+
+            if beat.downbeats_since(last_mutation_downbeats, every) {
+                last_mutation_downbeats = beat.downbeats();
+                mutations += 1;
+            }
+        }
+
+        (beat, mutations)
+    }
+
+    #[test]
+    fn downbeats_start_each_bar() {
+        for &beats_per_bar in &[1, 3, 4] {
+            let (beat, _) = mutate_on_downbeats(beats_per_bar, 1);
+            let beats_per_bar = u64::from(beats_per_bar);
+
+            assert!(beat.beats > beats_per_bar);
+            assert_eq!(
+                beat.downbeats(),
+                (beat.beats + beats_per_bar - 1) / beats_per_bar
+            );
+        }
+
+        // 0 is taken as a bar of 1
+        let (beat, _) = mutate_on_downbeats(0, 1);
+        assert_eq!(beat.downbeats(), beat.beats);
+    }
+
+    #[test]
+    fn mutates_every_few_downbeats() {
+        let (beat, mutations) = mutate_on_downbeats(2, 3);
+
+        assert!(beat.downbeats() >= 3);
+        assert_eq!(mutations, beat.downbeats() / 3);
+    }
+}
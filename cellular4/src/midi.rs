@@ -0,0 +1,404 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use failure::{ensure, format_err, Fallible};
+use log::info;
+use midir::{MidiInput, MidiInputConnection};
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+use mutagen::{Generatable, Mutatable, Updatable, UpdatableRecursively};
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// MIDI clock runs at 24 pulses per quarter note
+const CLOCK_TICKS_PER_BEAT: u64 = 24;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MidiEvent {
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    NoteOff { channel: u8, note: u8 },
+    ControlChange { channel: u8, control: u8, value: u8 },
+    Clock,
+    Start,
+}
+
+impl MidiEvent {
+    fn parse(message: &[u8]) -> Option<Self> {
+        let status = *message.first()?;
+        let channel = status & 0x0F;
+
+        match (status & 0xF0, status) {
+            (_, 0xF8) => Some(MidiEvent::Clock),
+            (_, 0xFA) => Some(MidiEvent::Start),
+            // Note on with no velocity is how a lot of devices send note off
+            (0x90, _) if *message.get(2)? > 0 => Some(MidiEvent::NoteOn {
+                channel,
+                note: *message.get(1)?,
+                velocity: *message.get(2)?,
+            }),
+            (0x80, _) | (0x90, _) => Some(MidiEvent::NoteOff {
+                channel,
+                note: *message.get(1)?,
+            }),
+            (0xB0, _) => Some(MidiEvent::ControlChange {
+                channel,
+                control: *message.get(1)?,
+                value: *message.get(2)?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn from_file(channel: u8, message: MidiMessage) -> Option<Self> {
+        match message {
+            MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => Some(MidiEvent::NoteOn {
+                channel,
+                note: key.as_int(),
+                velocity: vel.as_int(),
+            }),
+            MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                Some(MidiEvent::NoteOff {
+                    channel,
+                    note: key.as_int(),
+                })
+            }
+            MidiMessage::Controller { controller, value } => Some(MidiEvent::ControlChange {
+                channel,
+                control: controller.as_int(),
+                value: value.as_int(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// The state of every control change, note and the clock, as of the last update
+#[derive(Clone, Debug, Default)]
+pub struct MidiValues {
+    ccs: BTreeMap<MidiCc, u8>,
+    /// Held notes and their velocities. Released notes are kept at 0 so they can still be picked.
+    notes: BTreeMap<MidiNote, u8>,
+    clock_ticks: u64,
+}
+
+impl MidiValues {
+    fn receive(&mut self, event: MidiEvent) {
+        match event {
+            MidiEvent::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => {
+                self.notes.insert(MidiNote { channel, note }, velocity);
+            }
+            MidiEvent::NoteOff { channel, note } => {
+                self.notes.insert(MidiNote { channel, note }, 0);
+            }
+            MidiEvent::ControlChange {
+                channel,
+                control,
+                value,
+            } => {
+                self.ccs.insert(MidiCc { channel, control }, value);
+            }
+            MidiEvent::Clock => self.clock_ticks += 1,
+            MidiEvent::Start => self.clock_ticks = 0,
+        }
+    }
+
+    pub fn cc(&self, cc: MidiCc) -> UNFloat {
+        UNFloat::new(self.ccs.get(&cc).copied().unwrap_or(0) as f32 / 127.0)
+    }
+
+    /// The velocity the note is held at, 0 when it isn't held
+    pub fn note_velocity(&self, note: MidiNote) -> u8 {
+        self.notes.get(&note).copied().unwrap_or(0)
+    }
+
+    /// How far through the current beat the clock is
+    pub fn beat_phase(&self) -> UNFloat {
+        UNFloat::new((self.clock_ticks % CLOCK_TICKS_PER_BEAT) as f32 / CLOCK_TICKS_PER_BEAT as f32)
+    }
+}
+
+/// Reads MIDI from an input port, a virtual port, or a recorded file played back on a loop
+pub struct MidiReader {
+    received: Arc<Mutex<MidiValues>>,
+    _connection: Option<MidiInputConnection<()>>,
+}
+
+impl MidiReader {
+    pub fn new(config: MidiConfig) -> Fallible<(Self, MidiValues)> {
+        let received = Arc::new(Mutex::new(MidiValues::default()));
+
+        let connection = match config {
+            MidiConfig::Port { name } => Some(connect_port(name, Arc::clone(&received))?),
+            MidiConfig::Virtual { name } => Some(create_virtual(name, Arc::clone(&received))?),
+            MidiConfig::File { path } => {
+                play_file(fs::read(&path)?, Arc::clone(&received))?;
+                info!("Playing MIDI file {}", path);
+                None
+            }
+        };
+
+        Ok((
+            Self {
+                received,
+                _connection: connection,
+            },
+            MidiValues::default(),
+        ))
+    }
+
+    pub fn update(&mut self, values: &mut MidiValues) {
+        values.clone_from(&self.received.lock().unwrap());
+    }
+}
+
+fn receiver(received: Arc<Mutex<MidiValues>>) -> impl FnMut(u64, &[u8], &mut ()) + Send {
+    move |_timestamp, message, _| {
+        if let Some(event) = MidiEvent::parse(message) {
+            received.lock().unwrap().receive(event);
+        }
+    }
+}
+
+/// Connects to the first port with `name` in its name, or the first port at all
+fn connect_port(
+    name: Option<String>,
+    received: Arc<Mutex<MidiValues>>,
+) -> Fallible<MidiInputConnection<()>> {
+    let input = MidiInput::new("cellular4")?;
+
+    let port = input
+        .ports()
+        .into_iter()
+        .find(|port| match (&name, input.port_name(port)) {
+            (Some(name), Ok(port_name)) => port_name.contains(name),
+            (None, _) => true,
+            (_, Err(_)) => false,
+        })
+        .ok_or_else(|| format_err!("No MIDI input port matching {:?}", name))?;
+
+    info!("Connecting to MIDI port {}", input.port_name(&port)?);
+
+    input
+        .connect(&port, "cellular4", receiver(received), ())
+        .map_err(|e| format_err!("{}", e))
+}
+
+#[cfg(unix)]
+fn create_virtual(
+    name: String,
+    received: Arc<Mutex<MidiValues>>,
+) -> Fallible<MidiInputConnection<()>> {
+    use midir::os::unix::VirtualInput;
+
+    info!("Creating virtual MIDI port {}", name);
+
+    MidiInput::new("cellular4")?
+        .create_virtual(&name, receiver(received), ())
+        .map_err(|e| format_err!("{}", e))
+}
+
+#[cfg(windows)]
+fn create_virtual(
+    _name: String,
+    _received: Arc<Mutex<MidiValues>>,
+) -> Fallible<MidiInputConnection<()>> {
+    Err(format_err!(
+        "Virtual MIDI ports aren't supported on Windows"
+    ))
+}
+
+enum Playback {
+    Event(MidiEvent),
+    /// Microseconds per beat
+    Tempo(u64),
+}
+
+/// Plays back every track of a MIDI file on a loop, following its tempo changes
+fn play_file(bytes: Vec<u8>, received: Arc<Mutex<MidiValues>>) -> Fallible<()> {
+    let smf = Smf::parse(&bytes).map_err(|e| format_err!("Failed to parse MIDI file: {}", e))?;
+
+    let ticks_per_beat = match smf.header.timing {
+        Timing::Metrical(ticks) => ticks.as_int() as u64,
+        Timing::Timecode(..) => return Err(format_err!("Timecode MIDI files aren't supported")),
+    };
+
+    // Every track's events on one timeline, in ticks since the start
+    let mut events = Vec::new();
+
+    for track in &smf.tracks {
+        let mut tick = 0;
+
+        for event in track {
+            tick += event.delta.as_int() as u64;
+
+            let event = match event.kind {
+                TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                    Playback::Tempo(tempo.as_int() as u64)
+                }
+                TrackEventKind::Midi { channel, message } => {
+                    match MidiEvent::from_file(channel.as_int(), message) {
+                        Some(event) => Playback::Event(event),
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            };
+
+            events.push((tick, event));
+        }
+    }
+
+    ensure!(
+        events
+            .iter()
+            .any(|(_, event)| matches!(event, Playback::Event(_))),
+        "MIDI file has no notes or control changes to play"
+    );
+
+    events.sort_by_key(|(tick, _)| *tick);
+
+    thread::spawn(move || loop {
+        // Microseconds per beat, 120bpm until the file says otherwise
+        let mut tempo = 500_000;
+        let mut last_tick = 0;
+
+        for (tick, event) in &events {
+            thread::sleep(Duration::from_micros(
+                (tick - last_tick) * tempo / ticks_per_beat,
+            ));
+            last_tick = *tick;
+
+            match event {
+                Playback::Event(event) => received.lock().unwrap().receive(*event),
+                Playback::Tempo(new_tempo) => tempo = *new_tempo,
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// A control change, picked from the ones that have been sent when the node using it is generated
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct MidiCc {
+    pub channel: u8,
+    pub control: u8,
+}
+
+impl<'a> Generatable<'a> for MidiCc {
+    type GenArg = GenArg<'a>;
+
+    fn generate_rng<R: Rng + ?Sized>(rng: &mut R, arg: GenArg<'a>) -> Self {
+        arg.midi_values
+            .as_ref()
+            .and_then(|values| values.ccs.keys().choose(rng).copied())
+            .unwrap_or_else(|| MidiCc {
+                channel: 0,
+                control: rng.gen_range(0..128),
+            })
+    }
+}
+
+impl<'a> Mutatable<'a> for MidiCc {
+    type MutArg = MutArg<'a>;
+
+    fn mutate_rng<R: Rng + ?Sized>(&mut self, rng: &mut R, arg: Self::MutArg) {
+        *self = Self::generate_rng(rng, arg.into());
+    }
+}
+
+impl<'a> Updatable<'a> for MidiCc {
+    type UpdateArg = ();
+    fn update(&mut self, _arg: Self::UpdateArg) {}
+}
+
+impl<'a> UpdatableRecursively<'a> for MidiCc {
+    fn update_recursively(&mut self, _arg: Self::UpdateArg) {}
+}
+
+/// A note, picked from the ones that have been played when the node using it is generated
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct MidiNote {
+    pub channel: u8,
+    pub note: u8,
+}
+
+impl<'a> Generatable<'a> for MidiNote {
+    type GenArg = GenArg<'a>;
+
+    fn generate_rng<R: Rng + ?Sized>(rng: &mut R, arg: GenArg<'a>) -> Self {
+        arg.midi_values
+            .as_ref()
+            .and_then(|values| values.notes.keys().choose(rng).copied())
+            .unwrap_or_else(|| MidiNote {
+                channel: 0,
+                note: rng.gen_range(0..128),
+            })
+    }
+}
+
+impl<'a> Mutatable<'a> for MidiNote {
+    type MutArg = MutArg<'a>;
+
+    fn mutate_rng<R: Rng + ?Sized>(&mut self, rng: &mut R, arg: Self::MutArg) {
+        *self = Self::generate_rng(rng, arg.into());
+    }
+}
+
+impl<'a> Updatable<'a> for MidiNote {
+    type UpdateArg = ();
+    fn update(&mut self, _arg: Self::UpdateArg) {}
+}
+
+impl<'a> UpdatableRecursively<'a> for MidiNote {
+    fn update_recursively(&mut self, _arg: Self::UpdateArg) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_notes_ccs_and_clock() {
+        let mut values = MidiValues::default();
+        let note = MidiNote {
+            channel: 1,
+            note: 60,
+        };
+
+        for message in &[
+            &[0x91, 60, 100][..],
+            &[0xB0, 7, 127],
+            &[0xFA],
+            &[0xF8],
+            &[0xF8],
+            &[0xF8],
+        ] {
+            values.receive(MidiEvent::parse(message).unwrap());
+        }
+
+        assert_eq!(values.note_velocity(note), 100);
+        assert_eq!(
+            values.cc(MidiCc {
+                channel: 0,
+                control: 7
+            }),
+            UNFloat::ONE
+        );
+        assert_eq!(values.beat_phase(), UNFloat::new(3.0 / 24.0));
+
+        // Note on with no velocity releases the note
+        values.receive(MidiEvent::parse(&[0x91, 60, 0]).unwrap());
+        assert_eq!(values.note_velocity(note), 0);
+    }
+}
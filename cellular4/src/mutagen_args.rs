@@ -8,6 +8,8 @@ pub trait MutagenArg {
     fn gamepads(&self) -> &Gamepads;
     fn mic_spectrograms(&self) -> &Option<FrequencySpectrograms>;
     fn camera_frames(&self) -> &Option<CameraFrames>;
+    fn osc_values(&self) -> &Option<OscValues>;
+    fn midi_values(&self) -> &Option<MidiValues>;
 }
 
 pub struct GenArg<'a> {
@@ -23,6 +25,8 @@ pub struct GenArg<'a> {
     pub gamepads: &'a mut Gamepads,
     pub mouse_position: &'a mut Point2<f32>,
     pub camera_frames: &'a Option<CameraFrames>,
+    pub osc_values: &'a Option<OscValues>,
+    pub midi_values: &'a Option<MidiValues>,
 }
 
 impl<'a, 'b: 'a> Reborrow<'a, 'b, GenArg<'a>> for GenArg<'b> {
@@ -40,6 +44,8 @@ impl<'a, 'b: 'a> Reborrow<'a, 'b, GenArg<'a>> for GenArg<'b> {
             gamepads: &mut self.gamepads,
             mouse_position: &mut self.mouse_position,
             camera_frames: &self.camera_frames,
+            osc_values: &self.osc_values,
+            midi_values: &self.midi_values,
         }
     }
 }
@@ -68,6 +74,14 @@ impl<'a> MutagenArg for GenArg<'a> {
     fn camera_frames(&self) -> &Option<CameraFrames> {
         &self.camera_frames
     }
+
+    fn osc_values(&self) -> &Option<OscValues> {
+        &self.osc_values
+    }
+
+    fn midi_values(&self) -> &Option<MidiValues> {
+        &self.midi_values
+    }
 }
 
 pub struct MutArg<'a> {
@@ -83,6 +97,8 @@ pub struct MutArg<'a> {
     pub gamepads: &'a mut Gamepads,
     pub mouse_position: &'a mut Point2<f32>,
     pub camera_frames: &'a Option<CameraFrames>,
+    pub osc_values: &'a Option<OscValues>,
+    pub midi_values: &'a Option<MidiValues>,
 }
 
 impl<'a, 'b: 'a> Reborrow<'a, 'b, MutArg<'a>> for MutArg<'b> {
@@ -100,6 +116,8 @@ impl<'a, 'b: 'a> Reborrow<'a, 'b, MutArg<'a>> for MutArg<'b> {
             gamepads: &mut self.gamepads,
             mouse_position: &mut self.mouse_position,
             camera_frames: &self.camera_frames,
+            osc_values: &self.osc_values,
+            midi_values: &self.midi_values,
         }
     }
 }
@@ -119,6 +137,8 @@ impl<'a> From<MutArg<'a>> for GenArg<'a> {
             gamepads: arg.gamepads,
             mouse_position: arg.mouse_position,
             camera_frames: arg.camera_frames,
+            osc_values: arg.osc_values,
+            midi_values: arg.midi_values,
         }
     }
 }
@@ -147,6 +167,14 @@ impl<'a> MutagenArg for MutArg<'a> {
     fn camera_frames(&self) -> &Option<CameraFrames> {
         &self.camera_frames
     }
+
+    fn osc_values(&self) -> &Option<OscValues> {
+        &self.osc_values
+    }
+
+    fn midi_values(&self) -> &Option<MidiValues> {
+        &self.midi_values
+    }
 }
 
 #[derive(Clone)]
@@ -161,6 +189,8 @@ pub struct ComArg<'a> {
     pub gamepads: &'a Gamepads,
     pub mouse_position: &'a Point2<f32>,
     pub camera_frames: &'a Option<CameraFrames>,
+    pub osc_values: &'a Option<OscValues>,
+    pub midi_values: &'a Option<MidiValues>,
 }
 
 impl<'a> ComArg<'a> {
@@ -179,6 +209,16 @@ impl<'a> ComArg<'a> {
 
         new
     }
+
+    /// An rng for randomness during compute, the same coordinates on the same tic always get the same stream
+    pub fn rng(&self) -> DeterministicRng {
+        DeterministicRng::from_key(hash_key(&[
+            self.coordinate_set.x.into_inner().to_bits().into(),
+            self.coordinate_set.y.into_inner().to_bits().into(),
+            self.coordinate_set.t.to_bits().into(),
+            self.current_t as u64,
+        ]))
+    }
 }
 
 impl<'a, 'b: 'a> Reborrow<'a, 'b, ComArg<'a>> for ComArg<'b> {
@@ -194,6 +234,8 @@ impl<'a, 'b: 'a> Reborrow<'a, 'b, ComArg<'a>> for ComArg<'b> {
             gamepads: &self.gamepads,
             mouse_position: &self.mouse_position,
             camera_frames: &self.camera_frames,
+            osc_values: &self.osc_values,
+            midi_values: &self.midi_values,
         }
     }
 }
@@ -216,6 +258,14 @@ impl<'a> MutagenArg for ComArg<'a> {
     fn camera_frames(&self) -> &Option<CameraFrames> {
         &self.camera_frames
     }
+
+    fn osc_values(&self) -> &Option<OscValues> {
+        &self.osc_values
+    }
+
+    fn midi_values(&self) -> &Option<MidiValues> {
+        &self.midi_values
+    }
 }
 
 pub struct UpdArg<'a> {
@@ -231,6 +281,8 @@ pub struct UpdArg<'a> {
     pub gamepads: &'a mut Gamepads,
     pub mouse_position: &'a mut Point2<f32>,
     pub camera_frames: &'a Option<CameraFrames>,
+    pub osc_values: &'a Option<OscValues>,
+    pub midi_values: &'a Option<MidiValues>,
 }
 
 impl<'a, 'b: 'a> Reborrow<'a, 'b, UpdArg<'a>> for UpdArg<'b> {
@@ -248,6 +300,8 @@ impl<'a, 'b: 'a> Reborrow<'a, 'b, UpdArg<'a>> for UpdArg<'b> {
             gamepads: &mut self.gamepads,
             mouse_position: &mut self.mouse_position,
             camera_frames: &self.camera_frames,
+            osc_values: &self.osc_values,
+            midi_values: &self.midi_values,
         }
     }
 }
@@ -265,6 +319,8 @@ impl<'a> From<UpdArg<'a>> for ComArg<'a> {
             gamepads: arg.gamepads,
             mouse_position: arg.mouse_position,
             camera_frames: arg.camera_frames,
+            osc_values: arg.osc_values,
+            midi_values: arg.midi_values,
         }
     }
 }
@@ -284,6 +340,8 @@ impl<'a> From<GenArg<'a>> for UpdArg<'a> {
             gamepads: arg.gamepads,
             mouse_position: arg.mouse_position,
             camera_frames: arg.camera_frames,
+            osc_values: arg.osc_values,
+            midi_values: arg.midi_values,
         }
     }
 }
@@ -312,8 +370,60 @@ impl<'a> MutagenArg for UpdArg<'a> {
     fn camera_frames(&self) -> &Option<CameraFrames> {
         &self.camera_frames
     }
+
+    fn osc_values(&self) -> &Option<OscValues> {
+        &self.osc_values
+    }
+
+    fn midi_values(&self) -> &Option<MidiValues> {
+        &self.midi_values
+    }
 }
 
+/// Crossover always takes from the other tree at the same depth, so both node slices start at `depth`
+pub struct CrossArg<'a> {
+    pub nodes: &'a mut [NodeSet],
+    pub other_nodes: &'a [NodeSet],
+    pub depth: usize,
+    pub current_t: usize,
+}
+
+impl<'a, 'b: 'a> Reborrow<'a, 'b, CrossArg<'a>> for CrossArg<'b> {
+    fn reborrow(&'a mut self) -> CrossArg<'a> {
+        CrossArg {
+            nodes: &mut self.nodes,
+            other_nodes: &self.other_nodes,
+            depth: self.depth,
+            current_t: self.current_t,
+        }
+    }
+}
+
+impl<'a> mutagen::State for CrossArg<'a> {}
+
+/// The two trees being compared can place matching nodes at different depths, so each side keeps
+/// its own depth
+#[derive(Clone)]
+pub struct DistArg<'a> {
+    pub nodes: &'a [NodeSet],
+    pub other_nodes: &'a [NodeSet],
+    pub depth: usize,
+    pub other_depth: usize,
+}
+
+impl<'a, 'b: 'a> Reborrow<'a, 'b, DistArg<'a>> for DistArg<'b> {
+    fn reborrow(&'a mut self) -> DistArg<'a> {
+        DistArg {
+            nodes: &self.nodes,
+            other_nodes: &self.other_nodes,
+            depth: self.depth,
+            other_depth: self.other_depth,
+        }
+    }
+}
+
+impl<'a> mutagen::State for DistArg<'a> {}
+
 #[derive(Clone, Copy, Debug)]
 pub struct UpdateState<'a> {
     //the set of coordinates for the update
@@ -0,0 +1,128 @@
+use std::collections::VecDeque;
+
+use failure::Fallible;
+use serde::{Deserialize, Serialize};
+
+use crate::{node_tree::NodeTree, prelude::*};
+
+/// A bounded log of trees encoded by [encode_tree] before each mutation, so mutations can be undone
+/// and redone while running.
+///
+/// The whole tree and its arenas are kept rather than just the mutated field, since a `NodeBox` is
+/// only an index into the arenas and the nodes it points at get culled once nothing reaches them.
+#[derive(Debug, Default)]
+pub struct MutationHistory {
+    undo_states: VecDeque<Vec<u8>>,
+    redo_states: Vec<Vec<u8>>,
+}
+
+impl MutationHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the state from before a mutation. Anything that was undone can no longer be redone.
+    pub fn record(&mut self, state: Vec<u8>) {
+        self.push_undo(state);
+        self.redo_states.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_states.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_states.is_empty()
+    }
+
+    /// Swaps `current` for the state from before the latest mutation
+    pub fn undo(&mut self, current: Vec<u8>) -> Option<Vec<u8>> {
+        let state = self.undo_states.pop_back()?;
+        self.redo_states.push(current);
+
+        Some(state)
+    }
+
+    /// Swaps `current` for the state that was most recently undone
+    pub fn redo(&mut self, current: Vec<u8>) -> Option<Vec<u8>> {
+        let state = self.redo_states.pop()?;
+        self.push_undo(current);
+
+        Some(state)
+    }
+
+    fn push_undo(&mut self, state: Vec<u8>) {
+        self.undo_states.push_back(state);
+
+        while self.undo_states.len() > CONSTS.mutation_history_length {
+            self.undo_states.pop_front();
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TreeStateRef<'a> {
+    node_tree: &'a NodeTree,
+    nodes: &'a [NodeSet],
+}
+
+#[derive(Deserialize)]
+struct TreeState {
+    node_tree: NodeTree,
+    nodes: Vec<NodeSet>,
+}
+
+/// Encodes a tree and its arenas for the history. This runs on every mutation, so it's JSON rather
+/// than the YAML saves use, which is several times smaller and quicker. It can't be a binary format
+/// as those can't read the flattened fields in noise functions.
+///
+/// The [DataSet] isn't kept, no node refers into it.
+pub fn encode_tree(node_tree: &NodeTree, nodes: &[NodeSet]) -> Fallible<Vec<u8>> {
+    Ok(serde_json::to_vec(&TreeStateRef { node_tree, nodes })?)
+}
+
+pub fn decode_tree(state: &[u8]) -> Fallible<(NodeTree, Vec<NodeSet>)> {
+    let state: TreeState = serde_json::from_slice(state)?;
+
+    Ok((state.node_tree, state.nodes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::{tests::fallback_images, HeadlessRenderer};
+
+    #[test]
+    fn undo_and_redo_walk_the_log() {
+        let mut history = MutationHistory::new();
+
+        history.record(vec![0]);
+        history.record(vec![1]);
+
+        assert_eq!(history.undo(vec![2]), Some(vec![1]));
+        assert_eq!(history.undo(vec![1]), Some(vec![0]));
+        assert_eq!(history.undo(vec![0]), None);
+
+        assert_eq!(history.redo(vec![0]), Some(vec![1]));
+        assert_eq!(history.redo(vec![1]), Some(vec![2]));
+        assert!(!history.can_redo());
+
+        history.undo(vec![2]);
+        history.record(vec![3]);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn encoded_trees_decode_unchanged() {
+        with_rng_seed(1234, || {
+            let renderer = HeadlessRenderer::new(fallback_images());
+            let state = encode_tree(&renderer.node_tree, &renderer.nodes).unwrap();
+            let (node_tree, nodes) = decode_tree(&state).unwrap();
+
+            assert_eq!(
+                node_tree.to_text(&nodes).unwrap(),
+                renderer.node_tree.to_text(&renderer.nodes).unwrap()
+            );
+        });
+    }
+}
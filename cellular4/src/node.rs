@@ -98,6 +98,21 @@ pub mod mutagen_functions {
         }
     }
 
+    pub fn osc_node_weight<T: MutagenArg>(arg: T) -> f64 {
+        match arg.osc_values() {
+            Some(osc_values) if !osc_values.is_empty() => 1.0,
+            _ => 0.0,
+        }
+    }
+
+    pub fn midi_node_weight<T: MutagenArg>(arg: T) -> f64 {
+        if arg.midi_values().is_none() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
     #[cfg(test)]
     mod tests {
         use super::*;
@@ -108,6 +123,8 @@ pub mod mutagen_functions {
             gamepads: &'a Gamepads,
             mic_spectrograms: &'a Option<FrequencySpectrograms>,
             camera_frames: &'a Option<CameraFrames>,
+            osc_values: &'a Option<OscValues>,
+            midi_values: &'a Option<MidiValues>,
         }
 
         impl<'a> MutagenArg for TestArg<'a> {
@@ -126,6 +143,14 @@ pub mod mutagen_functions {
             fn camera_frames(&self) -> &Option<CameraFrames> {
                 &self.camera_frames
             }
+
+            fn osc_values(&self) -> &Option<OscValues> {
+                &self.osc_values
+            }
+
+            fn midi_values(&self) -> &Option<MidiValues> {
+                &self.midi_values
+            }
         }
 
         #[test]
@@ -136,6 +161,8 @@ pub mod mutagen_functions {
                     gamepads: &Gamepads::new(),
                     mic_spectrograms: &Some(FrequencySpectrograms::new(256)),
                     camera_frames: &None,
+                    osc_values: &None,
+                    midi_values: &None,
                 };
 
                 assert!(
@@ -153,6 +180,8 @@ pub mod mutagen_functions {
                 gamepads: &Gamepads::new(),
                 mic_spectrograms: &Some(FrequencySpectrograms::new(256)),
                 camera_frames: &None,
+                osc_values: &None,
+                midi_values: &None,
             };
 
             assert!(leaf_node_weight(arg) > 0.0);
@@ -1,10 +1,22 @@
-use mutagen::{Generatable, Mutatable, Reborrow, Updatable, UpdatableRecursively};
+use mutagen::{
+    Crossover, Generatable, Mutatable, Reborrow, TreeDistance, Updatable, UpdatableRecursively,
+};
 use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Deserialize, Serialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Deserialize,
+    Serialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum BinaryAutomataNodes {
     Majority {
         child: NodeBox<BooleanNodes>,
@@ -1,10 +1,22 @@
-use mutagen::{Generatable, Mutatable, Reborrow, Updatable, UpdatableRecursively};
+use mutagen::{
+    Crossover, Generatable, Mutatable, Reborrow, TreeDistance, Updatable, UpdatableRecursively,
+};
 use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Deserialize, Serialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Deserialize,
+    Serialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum ColorBlendNodes {
     #[mutagen(gen_weight = leaf_node_weight)]
     Gray,
@@ -139,7 +151,7 @@ impl Node for ColorBlendNodes {
                 color_b,
                 value,
             } => {
-                if UNFloat::random(&mut rand::thread_rng()).into_inner()
+                if UNFloat::random(&mut compute_arg.rng()).into_inner()
                     < value.compute(compute_arg.reborrow()).into_inner()
                 {
                     color_a.compute(compute_arg.reborrow())
@@ -3,16 +3,27 @@
 use std::{collections::VecDeque, f32::consts::PI, iter};
 
 use itertools::izip;
-use mutagen::{Generatable, Mutatable, Reborrow, Updatable, UpdatableRecursively};
-use rand::prelude::*;
+use mutagen::{
+    Crossover, Generatable, Mutatable, Reborrow, TreeDistance, Updatable, UpdatableRecursively,
+};
 use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
 
 use nalgebra::*;
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Debug, Serialize, Deserialize)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Debug,
+    Serialize,
+    Deserialize,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum FloatColorNodes {
     #[mutagen(gen_weight = leaf_node_weight)]
     Constant { value: FloatColor },
@@ -353,6 +364,7 @@ impl Node for FloatColorNodes {
             } => blend_function.blend(
                 child_a.compute(compute_arg.reborrow()),
                 child_b.compute(compute_arg.reborrow()),
+                &mut compute_arg.rng(),
             ),
             FromBitColor { child } => FloatColor::from(child.compute(compute_arg.reborrow())),
             ModifyState { child, child_state } => child.compute(ComArg {
@@ -611,7 +623,7 @@ impl Node for FloatColorNodes {
                 if (reseed_stable.into_inner() || true)
                     && (result.get_average() == 1.0 || result.get_average() == 0.0)
                 {
-                    FloatColor::random(&mut thread_rng())
+                    FloatColor::random(&mut compute_arg.rng())
                 } else {
                     result
                 }
@@ -754,7 +766,7 @@ impl Node for FloatColorNodes {
                 if (reseed_stable.into_inner() || true)
                     && (result.get_average() == 1.0 || result.get_average() == 0.0)
                 {
-                    FloatColor::random(&mut thread_rng())
+                    FloatColor::random(&mut compute_arg.rng())
                 } else {
                     result
                 }
@@ -858,7 +870,7 @@ impl Node for FloatColorNodes {
                 if (reseed_stable.into_inner() || true)
                     && (result.get_average() == 1.0 || result.get_average() == 0.0)
                 {
-                    FloatColor::random(&mut thread_rng())
+                    FloatColor::random(&mut compute_arg.rng())
                 } else {
                     result
                 }
@@ -1120,8 +1132,18 @@ impl<'a> Updatable<'a> for FloatColorNodes {
     }
 }
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Serialize, Deserialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Serialize,
+    Deserialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum BitColorNodes {
     #[mutagen(gen_weight = leaf_node_weight)]
     Constant { value: BitColor },
@@ -1508,8 +1530,18 @@ impl<'a> Updatable<'a> for BitColorNodes {
     fn update(&mut self, _arg: UpdArg<'a>) {}
 }
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Serialize, Deserialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Serialize,
+    Deserialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum ByteColorNodes {
     #[mutagen(gen_weight = leaf_node_weight)]
     Constant { value: ByteColor },
@@ -1766,8 +1798,18 @@ impl<'a> Updatable<'a> for ByteColorNodes {
     fn update(&mut self, _arg: UpdArg<'a>) {}
 }
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Serialize, Deserialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Serialize,
+    Deserialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum HSVColorNodes {
     #[mutagen(gen_weight = leaf_node_weight)]
     Constant { value: HSVColor },
@@ -2033,8 +2075,18 @@ impl<'a> Updatable<'a> for HSVColorNodes {
     fn update(&mut self, _arg: UpdArg<'a>) {}
 }
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Serialize, Deserialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Serialize,
+    Deserialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum CMYKColorNodes {
     #[mutagen(gen_weight = leaf_node_weight)]
     Constant { value: CMYKColor },
@@ -2110,8 +2162,18 @@ impl<'a> Updatable<'a> for CMYKColorNodes {
     fn update(&mut self, _arg: UpdArg<'a>) {}
 }
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Serialize, Deserialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Serialize,
+    Deserialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum LABColorNodes {
     #[mutagen(gen_weight = leaf_node_weight)]
     Constant { value: LABColor },
@@ -2298,8 +2360,18 @@ impl<'a> Updatable<'a> for LABColorNodes {
     fn update(&mut self, _arg: UpdArg<'a>) {}
 }
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Serialize, Deserialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Serialize,
+    Deserialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum GenericColorNodes {
     //Necessary for using a generic color node as a child
     #[mutagen(mut_reroll = 0.9)]
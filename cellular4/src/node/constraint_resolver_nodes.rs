@@ -1,10 +1,22 @@
-use mutagen::{Generatable, Mutatable, Reborrow, Updatable, UpdatableRecursively};
+use mutagen::{
+    Crossover, Generatable, Mutatable, Reborrow, TreeDistance, Updatable, UpdatableRecursively,
+};
 use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Serialize, Deserialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Serialize,
+    Deserialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum SFloatNormaliserNodes {
     Random,
     Constant {
@@ -47,8 +59,18 @@ impl<'a> Updatable<'a> for SFloatNormaliserNodes {
     fn update(&mut self, mut _arg: UpdArg<'a>) {}
 }
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Serialize, Deserialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Serialize,
+    Deserialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum UFloatNormaliserNodes {
     Random,
     Constant {
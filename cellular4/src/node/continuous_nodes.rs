@@ -2,15 +2,27 @@ use std::f64::consts::PI;
 
 use average::WeightedMean;
 use float_ord::FloatOrd;
-use mutagen::{Generatable, Mutatable, Reborrow, Updatable, UpdatableRecursively};
+use mutagen::{
+    Crossover, Generatable, Mutatable, Reborrow, TreeDistance, Updatable, UpdatableRecursively,
+};
 use nalgebra::*;
 use num::signum;
 use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Deserialize, Serialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Deserialize,
+    Serialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum AngleNodes {
     #[mutagen(gen_weight = leaf_node_weight)]
     FromGametic,
@@ -136,8 +148,18 @@ impl<'a> Updatable<'a> for AngleNodes {
     fn update(&mut self, _arg: UpdArg<'a>) {}
 }
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Deserialize, Serialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Deserialize,
+    Serialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum SNFloatNodes {
     #[mutagen(gen_weight = pipe_node_weight)]
     Sin { child: NodeBox<AngleNodes> },
@@ -550,8 +572,18 @@ impl<'a> Updatable<'a> for SNFloatNodes {
     }
 }
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Deserialize, Serialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Deserialize,
+    Serialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum UNFloatNodes {
     // #[mutagen(gen_weight = leaf_node_weight)]
     // Random,
@@ -640,6 +672,18 @@ pub enum UNFloatNodes {
     PeakMicFrequency { use_gamma: Boolean },
     #[mutagen(gen_weight = [leaf_node_weight, mic_node_weight])]
     AverageMicFrequency { use_gamma: Boolean },
+    #[mutagen(gen_weight = [leaf_node_weight, mic_node_weight])]
+    BeatPhase,
+    #[mutagen(gen_weight = [leaf_node_weight, mic_node_weight])]
+    OnsetStrength,
+
+    #[mutagen(gen_weight = [leaf_node_weight, osc_node_weight])]
+    FromOscValue { address: OscAddress },
+
+    #[mutagen(gen_weight = [leaf_node_weight, midi_node_weight])]
+    FromMidiCc { cc: MidiCc },
+    #[mutagen(gen_weight = [leaf_node_weight, midi_node_weight])]
+    FromMidiClock,
 
     // #[mutagen(gen_weight = leaf_node_weight)]
     // LastRotation,
@@ -998,6 +1042,29 @@ impl Node for UNFloatNodes {
 
                 UNFloat::new(v)
             }
+            BeatPhase => compute_arg
+                .mic_spectrograms
+                .as_ref()
+                .map_or(UNFloat::ZERO, |spectrograms| {
+                    spectrograms.beat().beat_phase()
+                }),
+            OnsetStrength => compute_arg
+                .mic_spectrograms
+                .as_ref()
+                .map_or(UNFloat::ZERO, |spectrograms| {
+                    spectrograms.beat().onset_strength()
+                }),
+            FromOscValue { address } => address
+                .value(compute_arg.osc_values)
+                .map_or(UNFloat::ZERO, OscValue::to_unfloat),
+            FromMidiCc { cc } => compute_arg
+                .midi_values
+                .as_ref()
+                .map_or(UNFloat::ZERO, |midi_values| midi_values.cc(*cc)),
+            FromMidiClock => compute_arg
+                .midi_values
+                .as_ref()
+                .map_or(UNFloat::ZERO, MidiValues::beat_phase),
             SubDivideSawtooth { child_a, child_b } => child_a
                 .compute(compute_arg.reborrow())
                 .subdivide_sawtooth(child_b.compute(compute_arg.reborrow())),
@@ -1,13 +1,25 @@
 use average::WeightedMean;
 use float_ord::FloatOrd;
-use mutagen::{Generatable, Mutatable, Reborrow, Updatable, UpdatableRecursively};
+use mutagen::{
+    Crossover, Generatable, Mutatable, Reborrow, TreeDistance, Updatable, UpdatableRecursively,
+};
 use nalgebra::*;
 use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Deserialize, Serialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Deserialize,
+    Serialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum BooleanNodes {
     #[mutagen(gen_weight = branch_node_weight)]
     UNFloatLess {
@@ -113,6 +125,16 @@ pub enum BooleanNodes {
         button: GamepadButton,
         id: GamepadId,
     },
+    /// True on the updates where a beat landed
+    #[mutagen(gen_weight = [leaf_node_weight, mic_node_weight])]
+    OnBeat,
+    #[mutagen(gen_weight = [leaf_node_weight, osc_node_weight])]
+    FromOscValue { address: OscAddress },
+    /// True on the updates where a message was sent to the address
+    #[mutagen(gen_weight = [leaf_node_weight, osc_node_weight])]
+    FromOscTrigger { address: OscAddress },
+    #[mutagen(gen_weight = [leaf_node_weight, midi_node_weight])]
+    FromMidiNote { note: MidiNote },
 }
 
 impl Node for BooleanNodes {
@@ -273,6 +295,25 @@ impl Node for BooleanNodes {
                     .get(*button)
                     .is_pressed,
             },
+            OnBeat => Boolean {
+                value: compute_arg
+                    .mic_spectrograms
+                    .as_ref()
+                    .map_or(false, |spectrograms| spectrograms.beat().on_beat()),
+            },
+            FromOscValue { address } => address
+                .value(compute_arg.osc_values)
+                .map_or(Boolean { value: false }, OscValue::to_boolean),
+            FromOscTrigger { address } => Boolean {
+                value: address.triggered(compute_arg.osc_values),
+            },
+            FromMidiNote { note } => Boolean {
+                value: compute_arg
+                    .midi_values
+                    .as_ref()
+                    .map_or(0, |midi_values| midi_values.note_velocity(*note))
+                    > 0,
+            },
         }
     }
 }
@@ -317,8 +358,18 @@ impl<'a> Updatable<'a> for BooleanNodes {
     }
 }
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Deserialize, Serialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Deserialize,
+    Serialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum NibbleNodes {
     #[mutagen(gen_weight = leaf_node_weight)]
     Constant { value: Nibble },
@@ -467,8 +518,18 @@ impl<'a> Updatable<'a> for NibbleNodes {
     }
 }
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Deserialize, Serialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Deserialize,
+    Serialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum ByteNodes {
     #[mutagen(gen_weight = leaf_node_weight)]
     Constant { value: Byte },
@@ -510,6 +571,13 @@ pub enum ByteNodes {
     #[mutagen(gen_weight = [leaf_node_weight, mic_node_weight])]
     AverageMicFrequency { use_gamma: Boolean },
 
+    #[mutagen(gen_weight = [leaf_node_weight, osc_node_weight])]
+    FromOscValue { address: OscAddress },
+
+    /// The note's velocity, scaled up from MIDI's 0 to 127
+    #[mutagen(gen_weight = [leaf_node_weight, midi_node_weight])]
+    FromMidiNote { note: MidiNote },
+
     #[mutagen(gen_weight = leaf_node_weight)]
     FromGametic,
     #[mutagen(gen_weight = branch_node_weight)]
@@ -584,6 +652,16 @@ impl Node for ByteNodes {
 
                 Byte::new(v.round() as u8)
             }
+            FromOscValue { address } => address
+                .value(compute_arg.osc_values)
+                .map_or(Byte::new(0), OscValue::to_byte),
+            FromMidiNote { note } => Byte::new(
+                compute_arg
+                    .midi_values
+                    .as_ref()
+                    .map_or(0, |midi_values| midi_values.note_velocity(*note))
+                    * 2,
+            ),
             FromGametic => compute_arg.coordinate_set.get_byte_t(),
             IfElse {
                 predicate,
@@ -606,8 +684,18 @@ impl<'a> Updatable<'a> for ByteNodes {
     fn update(&mut self, _arg: UpdArg<'a>) {}
 }
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Deserialize, Serialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Deserialize,
+    Serialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum UIntNodes {
     #[mutagen(gen_weight = leaf_node_weight)]
     Constant { value: UInt },
@@ -692,8 +780,18 @@ impl<'a> Updatable<'a> for UIntNodes {
     fn update(&mut self, _arg: UpdArg<'a>) {}
 }
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Deserialize, Serialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Deserialize,
+    Serialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum SIntNodes {
     #[mutagen(gen_weight = leaf_node_weight)]
     Constant { value: SInt },
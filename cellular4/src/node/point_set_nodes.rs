@@ -1,14 +1,26 @@
 use std::sync::Arc;
 
-use mutagen::{Generatable, Mutatable, Reborrow, Updatable, UpdatableRecursively};
+use mutagen::{
+    Crossover, Generatable, Mutatable, Reborrow, TreeDistance, Updatable, UpdatableRecursively,
+};
 use nalgebra::*;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
 
-#[derive(Generatable, UpdatableRecursively, Mutatable, Deserialize, Serialize, Debug)]
+#[derive(
+    Generatable,
+    UpdatableRecursively,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    Deserialize,
+    Serialize,
+    Debug,
+)]
 #[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
 pub enum PointSetNodes {
     #[mutagen(gen_weight = leaf_node_weight)]
     Constant { value: PointSet },
@@ -266,14 +278,14 @@ impl<'a> Updatable<'a> for PointSetNodes {
                         .points()
                         .par_iter()
                         .map(|p| {
+                            let point_arg = compute_arg.clone().replace_coords(p);
+
                             p.normalised_add(
-                                p.subtract_normalised(value.get_random_point())
+                                p.subtract_normalised(value.get_random_point(&mut point_arg.rng()))
                                     .scale_unfloat(
-                                        child
-                                            .compute(compute_arg.clone().replace_coords(p))
-                                            .multiply(UNFloat::new(
-                                                0.25 / CONSTS.target_fps as f32,
-                                            )),
+                                        child.compute(point_arg).multiply(UNFloat::new(
+                                            0.25 / CONSTS.target_fps as f32,
+                                        )),
                                     ),
                                 normaliser,
                             )
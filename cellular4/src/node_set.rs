@@ -50,9 +50,10 @@ impl NodeSet {
         Self::default()
     }
 
-    //ensure this is updated for accurate counts. Don't rely on this for anything
+    /// Counts the nodes in every arena, this has to be kept up to date with the fields
     pub fn count_all(&self) -> usize {
-        self.color_blend_nodes.len()
+        self.generic_color_nodes.len()
+            + self.color_blend_nodes.len()
             + self.bit_color_nodes.len()
             + self.byte_color_nodes.len()
             + self.float_color_nodes.len()
@@ -77,6 +78,46 @@ impl NodeSet {
             + self.ufloat_normaliser_nodes.len()
             + self.frame_renderer_nodes.len()
     }
+
+    /// Locks every slot that's locked in `other`, which this was restored over. This has to be kept
+    /// up to date with the fields.
+    pub fn copy_locks_from(&mut self, other: &NodeSet) {
+        self.color_blend_nodes
+            .copy_locks_from(&other.color_blend_nodes);
+        self.generic_color_nodes
+            .copy_locks_from(&other.generic_color_nodes);
+        self.bit_color_nodes.copy_locks_from(&other.bit_color_nodes);
+        self.byte_color_nodes
+            .copy_locks_from(&other.byte_color_nodes);
+        self.float_color_nodes
+            .copy_locks_from(&other.float_color_nodes);
+        self.hsv_color_nodes.copy_locks_from(&other.hsv_color_nodes);
+        self.cmyk_color_nodes
+            .copy_locks_from(&other.cmyk_color_nodes);
+        self.lab_color_nodes.copy_locks_from(&other.lab_color_nodes);
+        self.angle_nodes.copy_locks_from(&other.angle_nodes);
+        self.unfloat_nodes.copy_locks_from(&other.unfloat_nodes);
+        self.snfloat_nodes.copy_locks_from(&other.snfloat_nodes);
+        self.coord_map_nodes.copy_locks_from(&other.coord_map_nodes);
+        self.boolean_nodes.copy_locks_from(&other.boolean_nodes);
+        self.nibble_nodes.copy_locks_from(&other.nibble_nodes);
+        self.byte_nodes.copy_locks_from(&other.byte_nodes);
+        self.uint_nodes.copy_locks_from(&other.uint_nodes);
+        self.sint_nodes.copy_locks_from(&other.sint_nodes);
+        self.snfloat_matrix3_nodes
+            .copy_locks_from(&other.snfloat_matrix3_nodes);
+        self.snpoint_nodes.copy_locks_from(&other.snpoint_nodes);
+        self.point_set_nodes.copy_locks_from(&other.point_set_nodes);
+        self.iterative_function_nodes
+            .copy_locks_from(&other.iterative_function_nodes);
+        self.sncomplex_nodes.copy_locks_from(&other.sncomplex_nodes);
+        self.sfloat_normaliser_nodes
+            .copy_locks_from(&other.sfloat_normaliser_nodes);
+        self.ufloat_normaliser_nodes
+            .copy_locks_from(&other.ufloat_normaliser_nodes);
+        self.frame_renderer_nodes
+            .copy_locks_from(&other.frame_renderer_nodes);
+    }
 }
 
 impl<'a> Updatable<'a> for NodeSet {
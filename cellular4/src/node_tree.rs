@@ -0,0 +1,590 @@
+use std::collections::BTreeSet;
+
+use failure::Fallible;
+use log::info;
+use mutagen::{
+    Crossover, Generatable, Mutatable, Reborrow, TreeDistance, Updatable, UpdatableRecursively,
+};
+use ndarray::{ArrayViewMut1, ArrayViewMut2, ArrayViewMut3, Axis};
+use rand::prelude::*;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    inspector::{self, Trace},
+    prelude::*,
+    tree_path::{locate, with_node_type, TreePath},
+    tree_text,
+    update_stat::UpdateStat,
+};
+
+#[derive(
+    Debug,
+    Generatable,
+    Mutatable,
+    Crossover,
+    TreeDistance,
+    UpdatableRecursively,
+    Serialize,
+    Deserialize,
+)]
+#[mutagen(gen_arg = type GenArg<'a>, mut_arg = type MutArg<'a>)]
+#[mutagen(cross_arg = type CrossArg<'a>, dist_arg = type DistArg<'a>)]
+pub struct NodeTree {
+    /// The root node for the tree that computes the next screen state
+    #[mutagen(mut_weight = |arg: MutArg| field_mut_weight(
+        root_node,
+        NodeTreeField::RootNode,
+        locks,
+        arg,
+    ))]
+    pub root_node: GenericColorNodes,
+    #[mutagen(mut_weight = |arg: MutArg| field_mut_weight(
+        root_coordinate_node,
+        NodeTreeField::RootCoordinateNode,
+        locks,
+        arg,
+    ))]
+    pub root_coordinate_node: NodeBox<CoordMapNodes>,
+    #[mutagen(mut_weight = |arg: MutArg| field_mut_weight(
+        root_frame_renderer,
+        NodeTreeField::RootFrameRenderer,
+        locks,
+        arg,
+    ))]
+    pub root_frame_renderer: NodeBox<FrameRendererNodes>,
+    #[mutagen(mut_weight = |arg: MutArg| field_mut_weight(
+        compute_offset_node,
+        NodeTreeField::ComputeOffsetNode,
+        locks,
+        arg,
+    ))]
+    pub compute_offset_node: NodeBox<CoordMapNodes>,
+    #[mutagen(mut_weight = |arg: MutArg| field_mut_weight(
+        fade_color_node,
+        NodeTreeField::FadeColorNode,
+        locks,
+        arg,
+    ))]
+    pub fade_color_node: GenericColorNodes,
+    #[mutagen(mut_weight = |arg: MutArg| field_mut_weight(
+        fade_color_alpha_multiplier,
+        NodeTreeField::FadeColorAlphaMultiplier,
+        locks,
+        arg,
+    ))]
+    pub fade_color_alpha_multiplier: NodeBox<UNFloatNodes>,
+    #[mutagen(mut_weight = |arg: MutArg| field_mut_weight(
+        scaling_mode_node,
+        NodeTreeField::ScalingModeNode,
+        locks,
+        arg,
+    ))]
+    pub scaling_mode_node: NodeBox<BooleanNodes>,
+    /// Fields that are kept from mutating
+    #[mutagen(mut_weight = 0.0, cross_weight = 0.0)]
+    #[serde(default)]
+    pub locks: NodeTreeLocks,
+}
+
+/// Looks up a field's mutation weight, scaled by its size if [Constants::mutation_weights_by_size]
+/// is set. Locked fields never mutate.
+fn field_mut_weight<T>(node: &T, field: NodeTreeField, locks: &NodeTreeLocks, arg: MutArg) -> f64
+where
+    T: for<'a> TreeDistance<'a, DistArg = DistArg<'a>>,
+{
+    let weight = field.mutation_weight();
+
+    if locks.is_locked(field) {
+        0.0
+    } else if weight > 0.0 && CONSTS.mutation_weights_by_size {
+        weight * count_nodes(node, arg) as f64
+    } else {
+        weight
+    }
+}
+
+/// Counts the nodes under `node`. Nodes with several parents are counted once per parent.
+fn count_nodes<T>(node: &T, arg: MutArg) -> usize
+where
+    T: for<'a> TreeDistance<'a, DistArg = DistArg<'a>>,
+{
+    let nodes: &[NodeSet] = arg.nodes;
+
+    node.tree_size(DistArg {
+        nodes,
+        other_nodes: nodes,
+        depth: arg.depth,
+        other_depth: arg.depth,
+    })
+    .max(1)
+}
+
+/// Where a cell sits in a cell array `array_width` by `array_height`, before the coordinate node
+/// moves it
+fn cell_coordinate_set(
+    x: usize,
+    y: usize,
+    array_width: usize,
+    array_height: usize,
+    t: f32,
+) -> CoordinateSet {
+    CoordinateSet {
+        x: UNFloat::new(x as f32 / array_width as f32).to_signed(),
+        y: UNFloat::new(y as f32 / array_height as f32).to_signed(),
+        t,
+    }
+}
+
+/// The fields of a [NodeTree] that mutation picks between
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeTreeField {
+    RootNode,
+    RootCoordinateNode,
+    RootFrameRenderer,
+    ComputeOffsetNode,
+    FadeColorNode,
+    FadeColorAlphaMultiplier,
+    ScalingModeNode,
+}
+
+impl NodeTreeField {
+    pub const ALL: [NodeTreeField; 7] = [
+        NodeTreeField::RootNode,
+        NodeTreeField::RootCoordinateNode,
+        NodeTreeField::RootFrameRenderer,
+        NodeTreeField::ComputeOffsetNode,
+        NodeTreeField::FadeColorNode,
+        NodeTreeField::FadeColorAlphaMultiplier,
+        NodeTreeField::ScalingModeNode,
+    ];
+
+    /// The field's name in [NodeTree] and [Constants::mutation_weights]
+    pub fn name(self) -> &'static str {
+        match self {
+            NodeTreeField::RootNode => "root_node",
+            NodeTreeField::RootCoordinateNode => "root_coordinate_node",
+            NodeTreeField::RootFrameRenderer => "root_frame_renderer",
+            NodeTreeField::ComputeOffsetNode => "compute_offset_node",
+            NodeTreeField::FadeColorNode => "fade_color_node",
+            NodeTreeField::FadeColorAlphaMultiplier => "fade_color_alpha_multiplier",
+            NodeTreeField::ScalingModeNode => "scaling_mode_node",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|field| field.name() == name)
+    }
+
+    fn mutation_weight(self) -> f64 {
+        let weights = &CONSTS.mutation_weights;
+
+        match self {
+            NodeTreeField::RootNode => weights.root_node,
+            NodeTreeField::RootCoordinateNode => weights.root_coordinate_node,
+            NodeTreeField::RootFrameRenderer => weights.root_frame_renderer,
+            NodeTreeField::ComputeOffsetNode => weights.compute_offset_node,
+            NodeTreeField::FadeColorNode => weights.fade_color_node,
+            NodeTreeField::FadeColorAlphaMultiplier => weights.fade_color_alpha_multiplier,
+            NodeTreeField::ScalingModeNode => weights.scaling_mode_node,
+        }
+    }
+}
+
+/// The fields of a [NodeTree] that are kept from mutating. Saved with the tree.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NodeTreeLocks(BTreeSet<NodeTreeField>);
+
+impl NodeTreeLocks {
+    pub fn is_locked(&self, field: NodeTreeField) -> bool {
+        self.0.contains(&field)
+    }
+
+    pub fn set_locked(&mut self, field: NodeTreeField, locked: bool) {
+        if locked {
+            self.0.insert(field);
+        } else {
+            self.0.remove(&field);
+        }
+    }
+}
+
+impl<'a> Generatable<'a> for NodeTreeLocks {
+    type GenArg = GenArg<'a>;
+
+    fn generate_rng<R: Rng + ?Sized>(_rng: &mut R, _arg: Self::GenArg) -> Self {
+        Self::default()
+    }
+}
+
+impl<'a> Mutatable<'a> for NodeTreeLocks {
+    type MutArg = MutArg<'a>;
+
+    fn mutate_rng<R: Rng + ?Sized>(&mut self, _rng: &mut R, _arg: Self::MutArg) {}
+}
+
+impl<'a> Updatable<'a> for NodeTreeLocks {
+    type UpdateArg = ();
+
+    fn update(&mut self, _arg: Self::UpdateArg) {}
+}
+
+impl<'a> UpdatableRecursively<'a> for NodeTreeLocks {
+    fn update_recursively(&mut self, _arg: Self::UpdateArg) {}
+}
+
+/// Branches of a [NodeTree] that can be mutated on request, rather than picked by its weights
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MutationTarget {
+    Root,
+    Coord,
+    Renderer,
+}
+
+impl MutationTarget {
+    fn field(self) -> NodeTreeField {
+        match self {
+            MutationTarget::Root => NodeTreeField::RootNode,
+            MutationTarget::Coord => NodeTreeField::RootCoordinateNode,
+            MutationTarget::Renderer => NodeTreeField::RootFrameRenderer,
+        }
+    }
+}
+
+impl NodeTree {
+    /// Computes every cell of `cells`, a horizontal slice of the cell array starting at row `slice_y`.
+    /// The `t` of `compute_arg.coordinate_set` is used for every cell, its x and y are replaced per cell.
+    /// Returns the stats summed over the slice, the caller is expected to normalise them.
+    pub fn compute_cells(
+        &self,
+        compute_arg: ComArg,
+        mut cells: ArrayViewMut3<u8>,
+        slice_y: usize,
+    ) -> UpdateStat {
+        let history = compute_arg.history;
+        let current_t = compute_arg.current_t;
+        let t_coord = compute_arg.coordinate_set.t;
+        // Breeder candidates render smaller than the constants say, so go by the history's size
+        let (array_height, array_width, _) = history.history_steps[0].cell_array.dim();
+
+        let update_step = |y, x, mut new: ArrayViewMut1<u8>| {
+            // `y` counts from the top of the slice, the history is read by row in the whole array
+            let cell_y = y + slice_y;
+            let coordinate_set = cell_coordinate_set(x, cell_y, array_width, array_height, t_coord);
+
+            let mut compute_arg = compute_arg.clone().replace_coordinate_set(&coordinate_set);
+
+            let transformed_coords = self.root_coordinate_node.compute(compute_arg.reborrow());
+
+            let new_color = ByteColor::from(
+                self.root_node
+                    .compute(compute_arg.replace_coordinate_set(&transformed_coords)),
+            );
+
+            new[0] = new_color.r.into_inner();
+            new[1] = new_color.g.into_inner();
+            new[2] = new_color.b.into_inner();
+            new[3] = new_color.a.into_inner();
+
+            let current_color = history.get(x, cell_y, current_t);
+            let older_color = history.get(x, cell_y, usize::max(current_t, 1) - 1);
+
+            let mut rng =
+                DeterministicRng::from_key(hash_key(&[x as u64, cell_y as u64, current_t as u64]));
+
+            let local_offset = (rng.gen_range(-1..=1), rng.gen_range(-1..=1));
+            let local_color = history.get(
+                (x as i32 + local_offset.0)
+                    .max(0)
+                    .min(array_width as i32 - 1) as usize,
+                (cell_y as i32 + local_offset.1)
+                    .max(0)
+                    .min(array_height as i32 - 1) as usize,
+                current_t,
+            );
+            let global_color = history.get(
+                rng.gen::<usize>() % array_width,
+                rng.gen::<usize>() % array_height,
+                current_t,
+            );
+
+            let right_color = history.get(x + 1, cell_y, current_t);
+            let below_color = history.get(x, cell_y + 1, current_t);
+            let mirrored_x_color = history.get(array_width - 1 - x, cell_y, current_t);
+            let mirrored_y_color = history.get(x, array_height - 1 - cell_y, current_t);
+
+            let older_color: FloatColor = older_color.into();
+            let current_color: FloatColor = current_color.into();
+            let local_color: FloatColor = local_color.into();
+            let global_color: FloatColor = global_color.into();
+            let right_color: FloatColor = right_color.into();
+            let below_color: FloatColor = below_color.into();
+            let mirrored_x_color: FloatColor = mirrored_x_color.into();
+            let mirrored_y_color: FloatColor = mirrored_y_color.into();
+
+            let brightness_difference =
+                |other: FloatColor| (other.get_average() - current_color.get_average()).abs();
+
+            let channels = [
+                current_color.r.into_inner(),
+                current_color.g.into_inner(),
+                current_color.b.into_inner(),
+            ];
+            let max_channel = channels.iter().cloned().fold(0.0, f32::max);
+            let min_channel = channels.iter().cloned().fold(1.0, f32::min);
+
+            UpdateStat {
+                activity_value: f64::from(older_color.get_average() - current_color.get_average())
+                    .abs(), // / total_cells as f64
+                alpha_value: f64::from(current_color.a.into_inner()), // / total_cells as f64
+                local_similarity_value: f64::from(
+                    1.0 - (local_color.get_average() - current_color.get_average()).abs(),
+                ), // / total_cells as f64
+                global_similarity_value: f64::from(
+                    1.0 - (global_color.get_average() - current_color.get_average()).abs(),
+                ), // / total_cells as f64
+                sharpness_value: f64::from(
+                    (brightness_difference(right_color) + brightness_difference(below_color)) / 2.0,
+                ),
+                colourfulness_value: f64::from(max_channel - min_channel),
+                symmetry_value: f64::from(
+                    1.0 - (brightness_difference(mirrored_x_color)
+                        + brightness_difference(mirrored_y_color))
+                        / 2.0,
+                ),
+                graph_stability: 0.0, //we don't accumulate this here because the caller sets it
+                cpu_usage: 0.0,       //we don't accumulate this here because the caller sets it
+            }
+        };
+
+        let compute_row = |(y, mut row): (usize, ArrayViewMut2<u8>)| {
+            let mut stat = UpdateStat::default();
+
+            for (x, new) in row.outer_iter_mut().enumerate() {
+                stat += update_step(y, x, new);
+            }
+
+            stat
+        };
+
+        if CONSTS.parallelize {
+            // Rows are collected before summing so the result doesn't depend on how rayon splits the work
+            cells
+                .axis_iter_mut(Axis(0))
+                .into_par_iter()
+                .enumerate()
+                .map(compute_row)
+                .collect::<Vec<_>>()
+                .into_iter()
+                .sum()
+        } else {
+            cells
+                .axis_iter_mut(Axis(0))
+                .enumerate()
+                .map(compute_row)
+                .sum()
+        }
+    }
+
+    /// Computes the cell at `x`, `y` the way [NodeTree::compute_cells] does, recording what every
+    /// node outputs along the way
+    pub fn inspect_cell(&self, compute_arg: ComArg, x: usize, y: usize) -> (ByteColor, Trace) {
+        let (array_height, array_width, _) = compute_arg.history.history_steps[0].cell_array.dim();
+        let coordinate_set = cell_coordinate_set(
+            x,
+            y,
+            array_width,
+            array_height,
+            compute_arg.coordinate_set.t,
+        );
+
+        inspector::trace(|| {
+            let mut compute_arg = compute_arg.replace_coordinate_set(&coordinate_set);
+
+            let transformed_coords = self.root_coordinate_node.compute(compute_arg.reborrow());
+
+            // The root node isn't in an arena, so its NodeBox doesn't record it
+            let color = inspector::traced("GenericColorNodes", 0, || {
+                self.root_node
+                    .compute(compute_arg.replace_coordinate_set(&transformed_coords))
+            });
+
+            ByteColor::from(color)
+        })
+    }
+
+    /// Whether any field is unlocked with a mutation weight above 0. [Mutatable::mutate_rng] panics
+    /// if not.
+    pub fn can_mutate(&self) -> bool {
+        NodeTreeField::ALL
+            .iter()
+            .any(|&field| !self.locks.is_locked(field) && field.mutation_weight() > 0.0)
+    }
+
+    /// Mutates the branch of the tree picked by `target`, unless it's locked.
+    /// [Mutatable::mutate_rng] picks one by [Constants::mutation_weights] instead.
+    pub fn mutate_target<R: Rng + ?Sized>(
+        &mut self,
+        rng: &mut R,
+        target: MutationTarget,
+        arg: MutArg,
+    ) {
+        let field = target.field();
+
+        if self.locks.is_locked(field) {
+            info!("Not mutating {}, it's locked", field.name());
+            return;
+        }
+
+        match target {
+            MutationTarget::Root => {
+                info!("MUTATING ROOT NODE");
+                self.root_node.mutate_rng(rng, arg);
+            }
+            MutationTarget::Coord => {
+                info!("MUTATING COORD NODE");
+                self.root_coordinate_node.mutate_rng(rng, arg);
+            }
+            MutationTarget::Renderer => {
+                info!("MUTATING RENDERER");
+                self.root_frame_renderer.mutate_rng(rng, arg);
+            }
+        }
+    }
+
+    /// Fills in everything about `step` except its cells, texture and root scalar, returning whether
+    /// it should be drawn with nearest neighbour scaling.
+    /// `arg.coordinate_set` should be the update coordinate of the previous step.
+    pub fn compute_history_step(
+        &self,
+        mut arg: ComArg,
+        t_coord: f32,
+        step: &mut HistoryStep,
+    ) -> bool {
+        step.update_coordinate = self.compute_offset_node.compute(arg.reborrow());
+
+        //Workaround, TODO:please fix
+        //double TODO: fix this please it could be breaking other stuff
+        //triple TODO: please it's important
+        // step.update_coordinate.t = current_t as f32;
+        step.update_coordinate.t = t_coord;
+
+        let mut step_com_arg = arg.replace_coordinate_set(&step.update_coordinate);
+
+        step.fade_color = self.fade_color_node.compute(step_com_arg.reborrow());
+        step.alpha_multiplier = self
+            .fade_color_alpha_multiplier
+            .compute(step_com_arg.reborrow());
+        step.frame_renderer = self.root_frame_renderer.compute(step_com_arg.reborrow());
+
+        self.scaling_mode_node
+            .compute(step_com_arg.reborrow())
+            .into_inner()
+    }
+
+    /// Updates the tree itself, followed by every node arena in `arg.nodes` from the top down
+    pub fn update_with_arenas(&mut self, mut arg: UpdArg) {
+        self.update_recursively(arg.reborrow());
+
+        let UpdArg {
+            nodes,
+            data,
+            coordinate_set,
+            history,
+            current_t,
+            image_preloader,
+            mic_spectrograms,
+            profiler,
+            gamepads,
+            mouse_position,
+            camera_frames,
+            osc_values,
+            midi_values,
+            ..
+        } = arg;
+
+        for depth in 0..nodes.len() {
+            let (current, children) = nodes[depth..].split_first_mut().unwrap();
+
+            current.update_recursively(UpdArg {
+                nodes: children,
+                data: &mut *data,
+                coordinate_set,
+                history,
+                depth,
+                current_t,
+                image_preloader,
+                mic_spectrograms,
+                profiler: &mut *profiler,
+                gamepads: &mut *gamepads,
+                mouse_position: &mut *mouse_position,
+                camera_frames,
+                osc_values,
+                midi_values,
+            });
+        }
+    }
+}
+
+impl NodeTree {
+    /// Renders the tree as a graphviz graph, following each [NodeBox] into its arena in `nodes`.
+    /// Nodes with more than one parent are drawn once with an edge from each parent.
+    #[allow(clippy::ptr_arg)]
+    pub fn to_dot(&self, nodes: &Vec<NodeSet>) -> Fallible<Vec<u8>> {
+        Ok(with_resolved_node_boxes(nodes, || dot_serde::to_vec(self))?)
+    }
+
+    /// Writes the tree out as text, following each [NodeBox] into its arena in `nodes`
+    #[allow(clippy::ptr_arg)]
+    pub fn to_text(&self, nodes: &Vec<NodeSet>) -> Fallible<String> {
+        tree_text::to_text(self, nodes)
+    }
+
+    /// Whether the node at `path` is locked, see [NodeTree::set_node_locked]
+    #[allow(clippy::ptr_arg)]
+    pub fn is_node_locked(&self, nodes: &Vec<NodeSet>, path: &TreePath) -> Fallible<bool> {
+        let slot = locate(self, nodes, path)?;
+
+        with_node_type!(slot.node_type.as_str(), T => {
+            Ok(<NodeSet as Storage<T>>::arena(&nodes[slot.depth])[slot.index].is_locked())
+        })
+    }
+
+    /// Locks or unlocks the node at `path`, so it keeps its value and its children while the rest
+    /// of the tree mutates. Only nodes in an arena slot can be locked, not the colour fields kept
+    /// in the tree itself.
+    #[allow(clippy::ptr_arg)]
+    pub fn set_node_locked(
+        &self,
+        nodes: &mut Vec<NodeSet>,
+        path: &TreePath,
+        locked: bool,
+    ) -> Fallible<()> {
+        let slot = locate(self, nodes, path)?;
+
+        with_node_type!(slot.node_type.as_str(), T => {
+            <NodeSet as Storage<T>>::arena_mut(&mut nodes[slot.depth])[slot.index]
+                .set_locked(locked);
+
+            Ok(())
+        })
+    }
+
+    /// Reads a tree written by [NodeTree::to_text] or by hand, along with new arenas holding its
+    /// nodes
+    pub fn from_text(text: &str, current_t: usize) -> Fallible<(Self, Vec<NodeSet>)> {
+        let mut nodes = (0..=crate::node::max_node_depth())
+            .map(|_| NodeSet::new())
+            .collect();
+        let node_tree = tree_text::from_text(text, &mut nodes, 0, current_t)?;
+
+        Ok((node_tree, nodes))
+    }
+}
+
+impl<'a> Updatable<'a> for NodeTree {
+    type UpdateArg = UpdArg<'a>;
+
+    fn update(&mut self, _arg: UpdArg<'a>) {}
+}
@@ -1,8 +1,129 @@
+use std::path::PathBuf;
+
 use structopt::StructOpt;
 
+use crate::constants::ConstantOverride;
+
 #[derive(StructOpt)]
 pub struct Opts {
     /// A number to seed the rng with
     #[structopt(long)]
     pub seed: Option<u128>,
+
+    /// A constants file to use instead of constants.yml
+    #[structopt(long, parse(from_os_str))]
+    pub config: Option<PathBuf>,
+
+    /// Overrides a single constant, e.g. `--set cell_array_width=256` or `--set mic.gamma=2.0`.
+    /// Can be given more than once
+    #[structopt(long = "set", number_of_values = 1)]
+    pub overrides: Vec<ConstantOverride>,
+
+    /// Print the constants after applying --config and --set, then exit
+    #[structopt(long)]
+    pub print_config: bool,
+
+    /// Render without opening a window, writing frames to disk instead
+    #[structopt(long)]
+    pub headless: bool,
+
+    /// How many tics to render in headless mode
+    #[structopt(long, default_value = "100")]
+    pub ticks: usize,
+
+    /// Directory to write headless frames to
+    #[structopt(long, parse(from_os_str), default_value = "frames")]
+    pub output: PathBuf,
+
+    /// Only write the frame from the last tic in headless mode
+    #[structopt(long)]
+    pub last_frame_only: bool,
+
+    /// A save or screenshot snapshot to start from, instead of generating a new tree. Constants
+    /// saved with a screenshot are used unless --config is given
+    #[structopt(long, parse(from_os_str))]
+    pub snapshot: Option<PathBuf>,
+
+    /// A tree written as text, by hand or with T, to start from instead of generating a new tree.
+    /// The seed and constants are used as they are
+    #[structopt(long, parse(from_os_str), conflicts_with = "snapshot")]
+    pub tree: Option<PathBuf>,
+
+    /// Cross the trees saved in two snapshots, save the child and exit
+    #[structopt(long, parse(from_os_str), number_of_values = 2)]
+    pub cross: Vec<PathBuf>,
+
+    /// Where to save the child of --cross. Defaults to a new slot in the saves directory
+    #[structopt(long, parse(from_os_str))]
+    pub cross_output: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    pub recording: RecordingOpts,
+
+    #[structopt(flatten)]
+    pub evolution: EvolutionOpts,
+}
+
+#[derive(StructOpt, Clone)]
+pub struct RecordingOpts {
+    /// Start recording straight away. Recording can also be toggled with R
+    #[structopt(long)]
+    pub record: bool,
+
+    /// Directory to write recordings to, each recording gets its own subdirectory
+    #[structopt(long, parse(from_os_str), default_value = "recordings")]
+    pub record_dir: PathBuf,
+
+    /// Also encode recordings as an animated GIF alongside the PNG sequence
+    #[structopt(long)]
+    pub record_gif: bool,
+
+    /// How many frames to skip between each recorded frame
+    #[structopt(long, default_value = "0")]
+    pub record_frame_skip: usize,
+
+    /// How long each recorded frame is shown in the GIF, in milliseconds. Defaults to the time the
+    /// recorded frames actually covered at the target fps
+    #[structopt(long)]
+    pub record_frame_duration: Option<u32>,
+}
+
+#[derive(StructOpt, Clone)]
+pub struct EvolutionOpts {
+    /// Evolve a population of trees without opening a window, saving the best of each generation
+    #[structopt(long)]
+    pub evolve: bool,
+
+    /// How many trees are in each generation
+    #[structopt(long, default_value = "16")]
+    pub population: usize,
+
+    /// How many generations to evolve for
+    #[structopt(long, default_value = "10")]
+    pub generations: usize,
+
+    /// How many tics each tree is rendered for before it's scored
+    #[structopt(long, default_value = "60")]
+    pub evolve_ticks: usize,
+
+    /// How many of the best trees survive into the next generation and are saved
+    #[structopt(long, default_value = "4")]
+    pub survivors: usize,
+
+    /// The chance each offspring is crossed with a second survivor before it's mutated
+    #[structopt(long, default_value = "0.5")]
+    pub crossover_rate: f64,
+
+    /// Survivors closer than this to a better survivor are passed over while there are other
+    /// candidates left, so one tree can't take over the population
+    #[structopt(long, default_value = "0.1")]
+    pub min_tree_distance: f64,
+
+    /// Width of the cell array trees are scored at
+    #[structopt(long, default_value = "64")]
+    pub evolve_width: usize,
+
+    /// Height of the cell array trees are scored at
+    #[structopt(long, default_value = "36")]
+    pub evolve_height: usize,
 }
@@ -0,0 +1,260 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    mem,
+    net::{Ipv4Addr, UdpSocket},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use failure::Fallible;
+use log::{info, warn};
+use mutagen::{Generatable, Mutatable, Updatable, UpdatableRecursively};
+use rand::prelude::*;
+use rosc::{OscPacket, OscType};
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// The latest value sent to an OSC address. Only the first argument of each message is kept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OscValue {
+    Float(f32),
+    Int(i32),
+    Bool(bool),
+}
+
+impl OscValue {
+    fn from_arg(arg: &OscType) -> Option<Self> {
+        match *arg {
+            OscType::Float(value) => Some(OscValue::Float(value)),
+            OscType::Double(value) => Some(OscValue::Float(value as f32)),
+            OscType::Int(value) => Some(OscValue::Int(value)),
+            OscType::Long(value) => Some(OscValue::Int(value as i32)),
+            OscType::Bool(value) => Some(OscValue::Bool(value)),
+            _ => None,
+        }
+    }
+
+    /// Floats are expected to be from 0 to 1, as most controllers send them, and ints from 0 to 255
+    pub fn to_unfloat(self) -> UNFloat {
+        match self {
+            OscValue::Float(value) => UNFloat::new_clamped(value),
+            OscValue::Int(value) => UNFloat::new(value.max(0).min(255) as f32 / 255.0),
+            OscValue::Bool(value) => UNFloat::new(if value { 1.0 } else { 0.0 }),
+        }
+    }
+
+    pub fn to_boolean(self) -> Boolean {
+        Boolean {
+            value: match self {
+                OscValue::Float(value) => value >= 0.5,
+                OscValue::Int(value) => value != 0,
+                OscValue::Bool(value) => value,
+            },
+        }
+    }
+
+    pub fn to_byte(self) -> Byte {
+        match self {
+            OscValue::Int(value) => Byte::new(value.max(0).min(255) as u8),
+            value => Byte::new((value.to_unfloat().into_inner() * 255.0).round() as u8),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Received {
+    values: BTreeMap<String, OscValue>,
+    triggered: BTreeSet<String>,
+}
+
+impl Received {
+    fn receive(&mut self, packet: OscPacket) {
+        match packet {
+            OscPacket::Message(message) => {
+                if let Some(value) = message.args.first().and_then(OscValue::from_arg) {
+                    self.values.insert(message.addr.clone(), value);
+                }
+
+                self.triggered.insert(message.addr);
+            }
+            OscPacket::Bundle(bundle) => {
+                for packet in bundle.content {
+                    self.receive(packet);
+                }
+            }
+        }
+    }
+
+    fn drain_into(&mut self, values: &mut OscValues) {
+        values.values.append(&mut self.values);
+        values.triggered = mem::take(&mut self.triggered);
+    }
+}
+
+/// Listens for OSC messages over UDP, keeping the latest value sent to each address
+pub struct OscReader {
+    received: Arc<Mutex<Received>>,
+}
+
+impl OscReader {
+    pub fn new(config: OscConfig) -> Fallible<(Self, OscValues)> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, config.port))?;
+        let received = Arc::new(Mutex::new(Received::default()));
+
+        info!("Listening for OSC messages on {}", socket.local_addr()?);
+
+        let thread_received = Arc::clone(&received);
+        thread::spawn(move || {
+            let mut buf = [0; rosc::decoder::MTU];
+
+            loop {
+                let size = match socket.recv(&mut buf) {
+                    Ok(size) => size,
+                    Err(e) => {
+                        warn!("Failed to receive OSC packet: {}", e);
+                        continue;
+                    }
+                };
+
+                match rosc::decoder::decode(&buf[..size]) {
+                    Ok(packet) => thread_received.lock().unwrap().receive(packet),
+                    Err(e) => warn!("Failed to decode OSC packet: {:?}", e),
+                }
+            }
+        });
+
+        Ok((Self { received }, OscValues::new(&config.addresses)))
+    }
+
+    /// Takes in everything received since the last update
+    pub fn update(&mut self, values: &mut OscValues) {
+        self.received.lock().unwrap().drain_into(values);
+    }
+}
+
+/// The OSC values nodes can read, as of the last update
+#[derive(Debug)]
+pub struct OscValues {
+    values: BTreeMap<String, OscValue>,
+    /// Addresses that were sent a message since the update before
+    triggered: BTreeSet<String>,
+}
+
+impl OscValues {
+    /// Starts with `addresses` known, so nodes can use them before anything is sent to them
+    pub fn new(addresses: &[String]) -> Self {
+        Self {
+            values: addresses
+                .iter()
+                .map(|address| (address.clone(), OscValue::Float(0.0)))
+                .collect(),
+            triggered: BTreeSet::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn get(&self, address: &OscAddress) -> Option<OscValue> {
+        self.values.get(&address.0).copied()
+    }
+
+    pub fn triggered(&self, address: &OscAddress) -> bool {
+        self.triggered.contains(&address.0)
+    }
+}
+
+/// An address that has been sent a value, picked when the node using it is generated.
+/// Trees loaded without the same OSC source read the addresses as zero.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OscAddress(pub String);
+
+impl OscAddress {
+    pub fn value(&self, osc_values: &Option<OscValues>) -> Option<OscValue> {
+        osc_values.as_ref().and_then(|values| values.get(self))
+    }
+
+    pub fn triggered(&self, osc_values: &Option<OscValues>) -> bool {
+        osc_values
+            .as_ref()
+            .map_or(false, |values| values.triggered(self))
+    }
+
+    /// One of the addresses that has been sent a value, if there are any
+    fn choose<R: Rng + ?Sized>(rng: &mut R, osc_values: &Option<OscValues>) -> Option<Self> {
+        osc_values
+            .as_ref()
+            .and_then(|values| values.values.keys().choose(rng))
+            .cloned()
+            .map(OscAddress)
+    }
+}
+
+impl<'a> Generatable<'a> for OscAddress {
+    type GenArg = GenArg<'a>;
+
+    /// Without any OSC addresses this is the root address, which is never sent a value and so
+    /// reads as zero
+    fn generate_rng<R: Rng + ?Sized>(rng: &mut R, arg: GenArg<'a>) -> Self {
+        Self::choose(rng, arg.osc_values).unwrap_or_else(|| OscAddress("/".to_owned()))
+    }
+}
+
+impl<'a> Mutatable<'a> for OscAddress {
+    type MutArg = MutArg<'a>;
+
+    /// Keeps the current address if there aren't any others, e.g. when OSC isn't set up
+    fn mutate_rng<R: Rng + ?Sized>(&mut self, rng: &mut R, arg: Self::MutArg) {
+        if let Some(address) = Self::choose(rng, arg.osc_values) {
+            *self = address;
+        }
+    }
+}
+
+impl<'a> Updatable<'a> for OscAddress {
+    type UpdateArg = ();
+    fn update(&mut self, _arg: Self::UpdateArg) {}
+}
+
+impl<'a> UpdatableRecursively<'a> for OscAddress {
+    fn update_recursively(&mut self, _arg: Self::UpdateArg) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use rosc::OscMessage;
+
+    use super::*;
+
+    #[test]
+    fn keeps_latest_value_per_address() {
+        let mut received = Received::default();
+        let message = |addr: &str, value| {
+            OscPacket::Message(OscMessage {
+                addr: addr.to_owned(),
+                args: vec![value],
+            })
+        };
+
+        received.receive(message("/fader/1", OscType::Float(0.25)));
+        received.receive(message("/fader/1", OscType::Float(0.75)));
+        received.receive(message("/button", OscType::Int(1)));
+
+        let mut values = OscValues::new(&["/unused".to_owned()]);
+        received.drain_into(&mut values);
+
+        let fader = OscAddress("/fader/1".to_owned());
+        assert_eq!(values.get(&fader), Some(OscValue::Float(0.75)));
+        assert!(values.triggered(&fader));
+        assert!(!values.triggered(&OscAddress("/unused".to_owned())));
+        assert!(
+            values
+                .get(&OscAddress("/button".to_owned()))
+                .unwrap()
+                .to_boolean()
+                .value
+        );
+    }
+}
@@ -14,6 +14,7 @@ pub use crate::{
     gamepad::*,
     history::*,
     mic::*,
+    midi::*,
     mutagen_args::*,
     node::{
         automata_nodes::*, color_blend_nodes::*, color_nodes::*, complex_nodes::*,
@@ -22,6 +23,7 @@ pub use crate::{
         mutagen_functions::*, point_nodes::*, point_set_nodes::*, Node,
     },
     node_set::*,
+    osc::*,
     preloader::*,
     profiler::*,
     util::*,
@@ -0,0 +1,145 @@
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use failure::Fallible;
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame, RgbaImage,
+};
+use log::info;
+
+use crate::{opts::RecordingOpts, prelude::*};
+
+/// Higher is faster but uglier, 1 is far too slow to keep up with the window
+const GIF_ENCODE_SPEED: i32 = 10;
+
+/// Writes the frames it's given out as a numbered PNG sequence, and optionally an animated GIF,
+/// in a new directory under [RecordingOpts::record_dir]. The GIF is finished when this is dropped.
+pub struct Recorder {
+    dir: PathBuf,
+    frame_skip: usize,
+    frame_delay: Delay,
+    gif_encoder: Option<GifEncoder<BufWriter<File>>>,
+    frames_seen: usize,
+    frames_written: usize,
+}
+
+impl Recorder {
+    pub fn new(opts: &RecordingOpts) -> Fallible<Self> {
+        let started = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let dir = opts.record_dir.join(format!("recording_{}", started));
+        fs::create_dir_all(&dir)?;
+
+        let frame_delay = match opts.record_frame_duration {
+            Some(ms) => Delay::from_numer_denom_ms(ms, 1),
+            None => Delay::from_numer_denom_ms(
+                (opts.record_frame_skip as u32 + 1) * 1000,
+                CONSTS.target_fps,
+            ),
+        };
+
+        let gif_encoder = if opts.record_gif {
+            let file = BufWriter::new(File::create(dir.join("recording.gif"))?);
+            let mut encoder = GifEncoder::new_with_speed(file, GIF_ENCODE_SPEED);
+            encoder.set_repeat(Repeat::Infinite)?;
+
+            Some(encoder)
+        } else {
+            None
+        };
+
+        info!("Recording to {}", dir.display());
+
+        Ok(Self {
+            dir,
+            frame_skip: opts.record_frame_skip,
+            frame_delay,
+            gif_encoder,
+            frames_seen: 0,
+            frames_written: 0,
+        })
+    }
+
+    /// Records the next frame. `capture` is only called for frames that aren't skipped, so it can
+    /// be expensive.
+    pub fn record_with<F>(&mut self, capture: F) -> Fallible<()>
+    where
+        F: FnOnce() -> Fallible<RgbaImage>,
+    {
+        let frame_index = self.frames_seen;
+        self.frames_seen += 1;
+
+        if frame_index % (self.frame_skip + 1) != 0 {
+            return Ok(());
+        }
+
+        let frame = capture()?;
+        frame.save(
+            self.dir
+                .join(format!("frame_{:06}.png", self.frames_written)),
+        )?;
+
+        if let Some(encoder) = self.gif_encoder.as_mut() {
+            encoder.encode_frame(Frame::from_parts(frame, 0, 0, self.frame_delay))?;
+        }
+
+        self.frames_written += 1;
+
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        info!(
+            "Finished recording {} frames to {}",
+            self.frames_written,
+            self.dir.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_frames_between_recorded_ones() {
+        let record_dir =
+            std::env::temp_dir().join(format!("cellular4_recorder_test_{}", std::process::id()));
+        let mut recorder = Recorder::new(&RecordingOpts {
+            record: true,
+            record_dir: record_dir.clone(),
+            record_gif: false,
+            record_frame_skip: 2,
+            record_frame_duration: None,
+        })
+        .unwrap();
+
+        let mut captures = 0;
+
+        for _ in 0..10 {
+            recorder
+                .record_with(|| {
+                    captures += 1;
+                    Ok(RgbaImage::new(4, 4))
+                })
+                .unwrap();
+        }
+
+        // Every third frame of 10, starting from the first
+        let written = fs::read_dir(&recorder.dir)
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().extension() == Some("png".as_ref()))
+            .count();
+        assert_eq!(written, 4);
+        assert_eq!(captures, 4);
+
+        drop(recorder);
+        fs::remove_dir_all(&record_dir).unwrap();
+    }
+}
@@ -0,0 +1,382 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use failure::Fallible;
+use log::{info, warn};
+use serde_json::json;
+
+use crate::{
+    node_tree::{MutationTarget, NodeTreeField},
+    prelude::*,
+    tree_path::TreePath,
+    update_stat::UpdateStat,
+};
+
+/// Something the control server has been asked to do, handled by the game on its next update
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemoteCommand {
+    /// Mutates the given branch of the tree, or a random one like pressing space does
+    Mutate(Option<MutationTarget>),
+    Save(String),
+    Load(String),
+    /// Turns auto mutation on or off, or toggles it if there's no value
+    SetAutoMutate(Option<bool>),
+    SetSeed(u128),
+    /// Locks a field of the tree against mutation, or unlocks it
+    SetLocked(NodeTreeField, bool),
+    /// Locks the node at a path against mutation, or unlocks it
+    SetNodeLocked(TreePath, bool),
+    /// Copies the subtree at a path into the library under a name
+    ExtractSubtree(String, TreePath),
+    /// Puts the subtree with a name from the library into the tree at a path
+    InsertSubtree(String, TreePath),
+}
+
+#[derive(Debug, PartialEq)]
+enum Request {
+    Command(RemoteCommand),
+    Stats,
+    StatsStream,
+}
+
+/// The latest stats, and the connections they are streamed to as they come in
+#[derive(Default)]
+struct Stats {
+    latest: Mutex<UpdateStat>,
+    subscribers: Mutex<Vec<TcpStream>>,
+}
+
+/// A small HTTP server on localhost that lets other programs drive the game.
+///
+/// Commands are sent with `POST`:
+/// - `/mutate`, `/mutate/root`, `/mutate/coord` or `/mutate/renderer`
+/// - `/save/<slot>` and `/load/<slot>`
+/// - `/auto_mutate`, `/auto_mutate/on` or `/auto_mutate/off`
+/// - `/seed/<seed>`
+/// - `/lock/<field>` and `/unlock/<field>`, where the field is named as in [NodeTree]
+/// - `/lock_node/<path>` and `/unlock_node/<path>`, where the path is a [TreePath]
+/// - `/library/extract/<name>/<path>` and `/library/insert/<name>/<path>`, where the path is a
+///   [TreePath]
+///
+/// `GET /stats` returns the current [UpdateStat] as JSON, and `GET /stats/stream` keeps the
+/// connection open and sends a line of JSON every update.
+///
+/// Requests sent by web pages from anywhere but localhost are refused with a 403.
+pub struct RemoteServer {
+    commands: Receiver<RemoteCommand>,
+    stats: Arc<Stats>,
+}
+
+impl RemoteServer {
+    pub fn new(config: RemoteConfig) -> Fallible<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, config.port))?;
+        let (sender, commands) = mpsc::channel();
+        let stats = Arc::new(Stats::default());
+
+        info!(
+            "Listening for remote commands on {}",
+            listener.local_addr()?
+        );
+
+        let thread_stats = Arc::clone(&stats);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream
+                    .map_err(Into::into)
+                    .and_then(|stream| handle_connection(stream, &sender, &thread_stats));
+
+                if let Err(e) = result {
+                    warn!("Failed to handle remote request: {}", e);
+                }
+            }
+        });
+
+        Ok(Self { commands, stats })
+    }
+
+    /// Commands that have come in since this was last called
+    pub fn commands(&self) -> impl Iterator<Item = RemoteCommand> + '_ {
+        self.commands.try_iter()
+    }
+
+    /// Sets the stats served to clients, and sends them to any that are streaming
+    pub fn publish(&self, update_stat: &UpdateStat) {
+        *self.stats.latest.lock().unwrap() = *update_stat;
+
+        let line = json!(update_stat).to_string();
+
+        self.stats
+            .subscribers
+            .lock()
+            .unwrap()
+            .retain(|mut stream| writeln!(stream, "{}", line).is_ok());
+    }
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    sender: &Sender<RemoteCommand>,
+    stats: &Stats,
+) -> Fallible<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // Only the origin is needed, but the other headers have to be read before replying
+    let mut origin = None;
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("origin") {
+                origin = Some(value.trim().to_owned());
+            }
+        }
+
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+
+    match check_origin(origin.as_deref()).and_then(|()| parse_request(method, path)) {
+        Ok(Request::Command(command)) => {
+            info!("Received remote command {:?}", command);
+            sender.send(command)?;
+            respond(&mut stream, "202 Accepted", &json!({ "ok": true }))
+        }
+        Ok(Request::Stats) => {
+            let update_stat = *stats.latest.lock().unwrap();
+            respond(&mut stream, "200 OK", &json!(update_stat))
+        }
+        Ok(Request::StatsStream) => {
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nConnection: close\r\n\r\n"
+            )?;
+
+            // Streams are written to from the game's update, so a slow client can't hold it up
+            stream.set_write_timeout(Some(Duration::from_millis(10)))?;
+            stats.subscribers.lock().unwrap().push(stream);
+
+            Ok(())
+        }
+        Err((status, message)) => respond(&mut stream, status, &json!({ "error": message })),
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &serde_json::Value) -> Fallible<()> {
+    let body = body.to_string();
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+
+    Ok(())
+}
+
+/// Browsers send an `Origin` with requests made by web pages. Pages from anywhere but localhost are
+/// refused, so a site open in a browser can't drive the game.
+fn check_origin(origin: Option<&str>) -> Result<(), (&'static str, String)> {
+    match origin {
+        Some(origin) if !is_local_origin(origin) => Err((
+            "403 Forbidden",
+            format!("Requests from {} aren't allowed", origin),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn is_local_origin(origin: &str) -> bool {
+    let authority = match origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"))
+    {
+        Some(authority) => authority,
+        None => return false,
+    };
+
+    ["localhost", "127.0.0.1", "[::1]"].iter().any(|host| {
+        authority.strip_prefix(host).map_or(false, |port| {
+            port.is_empty()
+                || port
+                    .strip_prefix(':')
+                    .map_or(false, |port| port.parse::<u16>().is_ok())
+        })
+    })
+}
+
+fn parse_request(method: &str, path: &str) -> Result<Request, (&'static str, String)> {
+    let not_found = || ("404 Not Found", format!("Nothing at {} {}", method, path));
+
+    let segments: Vec<_> = path
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+
+    let command = match (method, segments.as_slice()) {
+        ("GET", ["stats"]) => return Ok(Request::Stats),
+        ("GET", ["stats", "stream"]) => return Ok(Request::StatsStream),
+
+        ("POST", ["mutate"]) => RemoteCommand::Mutate(None),
+        ("POST", ["mutate", target]) => RemoteCommand::Mutate(Some(match *target {
+            "root" => MutationTarget::Root,
+            "coord" => MutationTarget::Coord,
+            "renderer" => MutationTarget::Renderer,
+            _ => return Err(not_found()),
+        })),
+
+        ("POST", ["save", slot]) => RemoteCommand::Save(parse_slot(slot)?),
+        ("POST", ["load", slot]) => RemoteCommand::Load(parse_slot(slot)?),
+
+        ("POST", ["auto_mutate"]) => RemoteCommand::SetAutoMutate(None),
+        ("POST", ["auto_mutate", "on"]) => RemoteCommand::SetAutoMutate(Some(true)),
+        ("POST", ["auto_mutate", "off"]) => RemoteCommand::SetAutoMutate(Some(false)),
+
+        ("POST", ["seed", seed]) => RemoteCommand::SetSeed(
+            seed.parse()
+                .map_err(|e| ("400 Bad Request", format!("Invalid seed '{}': {}", seed, e)))?,
+        ),
+
+        ("POST", ["lock", field]) => RemoteCommand::SetLocked(parse_field(field)?, true),
+        ("POST", ["unlock", field]) => RemoteCommand::SetLocked(parse_field(field)?, false),
+        ("POST", ["lock_node", path @ ..]) if !path.is_empty() => {
+            RemoteCommand::SetNodeLocked(parse_tree_path(path)?, true)
+        }
+        ("POST", ["unlock_node", path @ ..]) if !path.is_empty() => {
+            RemoteCommand::SetNodeLocked(parse_tree_path(path)?, false)
+        }
+
+        ("POST", ["library", "extract", name, path @ ..]) if !path.is_empty() => {
+            RemoteCommand::ExtractSubtree(parse_slot(name)?, parse_tree_path(path)?)
+        }
+        ("POST", ["library", "insert", name, path @ ..]) if !path.is_empty() => {
+            RemoteCommand::InsertSubtree(parse_slot(name)?, parse_tree_path(path)?)
+        }
+
+        _ => return Err(not_found()),
+    };
+
+    Ok(Request::Command(command))
+}
+
+/// Slots become file names in the saves directory, so they're kept to plain names
+fn parse_slot(slot: &str) -> Result<String, (&'static str, String)> {
+    if slot
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        Ok(slot.to_owned())
+    } else {
+        Err((
+            "400 Bad Request",
+            format!(
+                "Invalid slot '{}', slots can only contain letters, numbers, _ and -",
+                slot
+            ),
+        ))
+    }
+}
+
+fn parse_field(field: &str) -> Result<NodeTreeField, (&'static str, String)> {
+    NodeTreeField::from_name(field)
+        .ok_or_else(|| ("400 Bad Request", format!("Unknown tree field '{}'", field)))
+}
+
+fn parse_tree_path(segments: &[&str]) -> Result<TreePath, (&'static str, String)> {
+    segments
+        .join("/")
+        .parse()
+        .map_err(|e| ("400 Bad Request", e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(
+            parse_request("POST", "/mutate/coord"),
+            Ok(Request::Command(RemoteCommand::Mutate(Some(
+                MutationTarget::Coord
+            ))))
+        );
+        assert_eq!(
+            parse_request("POST", "/save/set_1"),
+            Ok(Request::Command(RemoteCommand::Save("set_1".to_owned())))
+        );
+        assert_eq!(
+            parse_request("POST", "/auto_mutate/off"),
+            Ok(Request::Command(RemoteCommand::SetAutoMutate(Some(false))))
+        );
+        assert_eq!(
+            parse_request("POST", "/seed/1234"),
+            Ok(Request::Command(RemoteCommand::SetSeed(1234)))
+        );
+        assert_eq!(
+            parse_request("POST", "/lock/root_coordinate_node"),
+            Ok(Request::Command(RemoteCommand::SetLocked(
+                NodeTreeField::RootCoordinateNode,
+                true
+            )))
+        );
+        assert_eq!(
+            parse_request("POST", "/unlock_node/root_frame_renderer/child"),
+            Ok(Request::Command(RemoteCommand::SetNodeLocked(
+                TreePath::new(vec!["root_frame_renderer", "child"]),
+                false
+            )))
+        );
+        assert_eq!(
+            parse_request("POST", "/library/insert/swirl/root_coordinate_node/child"),
+            Ok(Request::Command(RemoteCommand::InsertSubtree(
+                "swirl".to_owned(),
+                TreePath::new(vec!["root_coordinate_node", "child"])
+            )))
+        );
+        assert_eq!(parse_request("GET", "/stats/"), Ok(Request::Stats));
+    }
+
+    #[test]
+    fn reject_bad_requests() {
+        assert!(parse_request("GET", "/mutate").is_err());
+        assert!(parse_request("POST", "/mutate/everything").is_err());
+        assert!(parse_request("POST", "/seed/lots").is_err());
+        assert!(parse_request("POST", "/unlock/locks").is_err());
+        assert!(parse_request("POST", "/lock_node").is_err());
+        assert!(parse_request("POST", "/load/..%2Fconstants").is_err());
+        assert!(parse_request("POST", "/library/extract/swirl").is_err());
+        assert!(parse_request("POST", "/library/extract/swirl/root_node%2F").is_err());
+    }
+
+    #[test]
+    fn reject_other_origins() {
+        assert!(check_origin(None).is_ok());
+        assert!(check_origin(Some("http://localhost:8080")).is_ok());
+        assert!(check_origin(Some("http://127.0.0.1")).is_ok());
+        assert!(check_origin(Some("https://[::1]:3000")).is_ok());
+
+        assert!(check_origin(Some("null")).is_err());
+        assert!(check_origin(Some("https://example.com")).is_err());
+        assert!(check_origin(Some("http://localhost.example.com")).is_err());
+        assert!(check_origin(Some("http://127.0.0.1:80@example.com")).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use failure::{ensure, Fallible};
+use image::RgbaImage;
+use mutagen::Crossover;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+use crate::{node_tree::NodeTree, prelude::*, util};
+
+/// Everything needed to pick a running tree back up where it left off.
+///
+/// `current_t` is stored alongside the arenas because every slot remembers the tic it was last
+/// accessed on, and the arenas cull anything that looks stale relative to the current tic.
+#[derive(Debug, Deserialize)]
+pub struct Snapshot {
+    pub seed: u128,
+    pub current_t: usize,
+    pub node_tree: NodeTree,
+    pub nodes: Vec<NodeSet>,
+    pub data: DataSet,
+    /// The constants the tree was running with, only saved alongside screenshots
+    #[serde(default)]
+    pub constants: Option<Value>,
+}
+
+impl Snapshot {
+    pub fn load<P: AsRef<Path>>(path: P) -> Fallible<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Fallible<Self> {
+        let snapshot: Self = serde_yaml::from_slice(bytes)?;
+
+        ensure!(
+            snapshot.nodes.len() == crate::node::max_node_depth() + 1,
+            "Snapshot has {} node sets, expected {}. Was it saved with different depth constants?",
+            snapshot.nodes.len(),
+            crate::node::max_node_depth() + 1
+        );
+
+        Ok(snapshot)
+    }
+
+    /// Reads a tree written with [NodeTree::to_text] as a snapshot at tic 0, with the current
+    /// [RNG_SEED] and empty data
+    pub fn load_tree<P: AsRef<Path>>(path: P) -> Fallible<Self> {
+        let (node_tree, nodes) = NodeTree::from_text(&fs::read_to_string(path)?, 0)?;
+
+        Ok(Self {
+            seed: *RNG_SEED.lock().unwrap(),
+            current_t: 0,
+            node_tree,
+            nodes,
+            data: DataSet::new(),
+            constants: None,
+        })
+    }
+
+    /// Replaces part of this snapshot's tree with the matching part of `other`'s. Anything taken
+    /// from `other` is copied into this snapshot's node sets, everything else stays as it was.
+    pub fn crossover<R: Rng + ?Sized>(&mut self, rng: &mut R, other: &Snapshot) {
+        self.node_tree.crossover_rng(
+            rng,
+            &other.node_tree,
+            CrossArg {
+                nodes: &mut self.nodes,
+                other_nodes: &other.nodes,
+                depth: 0,
+                current_t: self.current_t,
+            },
+        );
+    }
+}
+
+/// Borrowed counterpart of [Snapshot], so saving doesn't need to clone the arenas
+#[derive(Debug, Serialize)]
+pub struct SnapshotRef<'a> {
+    pub seed: u128,
+    pub current_t: usize,
+    pub node_tree: &'a NodeTree,
+    pub nodes: &'a [NodeSet],
+    pub data: &'a DataSet,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub constants: Option<Value>,
+}
+
+impl<'a> SnapshotRef<'a> {
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Fallible<()> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, self.to_bytes()?)?;
+
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Fallible<Vec<u8>> {
+        Ok(serde_yaml::to_vec(self)?)
+    }
+}
+
+pub fn save_slot_path(slot: &str) -> PathBuf {
+    util::local_path("saves").join(&format!("{}.yml", slot))
+}
+
+/// Saves `image` to `dir` as a screenshot, with `snapshot` next to it so it can be loaded back to
+/// where it was taken. Returns the image's path.
+pub fn save_screenshot(dir: &Path, image: &RgbaImage, snapshot: SnapshotRef) -> Fallible<PathBuf> {
+    let taken = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let image_path = dir.join(format!("screenshot_{}_{}.png", taken, snapshot.current_t));
+    fs::create_dir_all(dir)?;
+
+    image.save(&image_path)?;
+    snapshot.save(image_path.with_extension("yml"))?;
+
+    Ok(image_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::{tests::fallback_images, HeadlessRenderer};
+
+    #[test]
+    fn round_trips_through_saves() {
+        with_rng_seed(1234, || {
+            let renderer = HeadlessRenderer::new(fallback_images());
+            let path = std::env::temp_dir().join(format!(
+                "cellular4_snapshot_test_{}.yml",
+                std::process::id()
+            ));
+
+            renderer.snapshot().save(&path).unwrap();
+            let snapshot = Snapshot::load(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+
+            assert_eq!(snapshot.seed, 1234);
+            assert_eq!(snapshot.current_t, renderer.current_t);
+            assert_eq!(
+                snapshot.node_tree.to_text(&snapshot.nodes).unwrap(),
+                renderer.node_tree.to_text(&renderer.nodes).unwrap()
+            );
+            assert_eq!(
+                serde_yaml::to_string(&snapshot.nodes).unwrap(),
+                serde_yaml::to_string(&renderer.nodes).unwrap()
+            );
+        });
+    }
+
+    #[test]
+    fn screenshots_load_where_they_were_taken() {
+        with_rng_seed(1234, || {
+            let renderer = HeadlessRenderer::new(fallback_images());
+            let dir = std::env::temp_dir()
+                .join(format!("cellular4_screenshot_test_{}", std::process::id()));
+
+            let image_path = save_screenshot(
+                &dir,
+                &renderer.frame_image().unwrap(),
+                SnapshotRef {
+                    constants: Some(CONSTS.effective_value()),
+                    ..renderer.snapshot()
+                },
+            )
+            .unwrap();
+            assert!(image_path.exists());
+
+            let snapshot = Snapshot::load(image_path.with_extension("yml")).unwrap();
+            fs::remove_dir_all(&dir).unwrap();
+
+            assert_eq!(snapshot.seed, 1234);
+            assert_eq!(snapshot.current_t, renderer.current_t);
+            assert_eq!(snapshot.constants, Some(CONSTS.effective_value()));
+            assert_eq!(
+                snapshot.node_tree.to_text(&snapshot.nodes).unwrap(),
+                renderer.node_tree.to_text(&renderer.nodes).unwrap()
+            );
+        });
+    }
+
+    #[test]
+    fn rejects_snapshots_with_other_depths() {
+        with_rng_seed(1234, || {
+            let renderer = HeadlessRenderer::new(fallback_images());
+            let snapshot = renderer.snapshot();
+            let bytes = SnapshotRef {
+                nodes: &renderer.nodes[1..],
+                ..snapshot
+            }
+            .to_bytes()
+            .unwrap();
+
+            let error = Snapshot::from_bytes(&bytes).unwrap_err();
+            assert!(error.to_string().contains("node sets"), "{}", error);
+        });
+    }
+}
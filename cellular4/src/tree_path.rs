@@ -0,0 +1,160 @@
+//! Paths to nodes in a tree by field name, and finding the arena slots they lead to
+
+use std::{fmt, str::FromStr};
+
+use failure::{bail, format_err, Error, Fallible};
+use generational_arena::Index;
+use serde::{Deserialize, Serialize};
+
+use crate::{node_tree::NodeTreeField, prelude::*};
+
+/// Runs `$body` with `$T` standing for the node type called `$node_type`
+macro_rules! with_node_type {
+    ($node_type:expr, $T:ident => $body:block) => {
+        with_node_type!(@match $node_type, $T => $body, [
+            ColorBlendNodes,
+            GenericColorNodes,
+            BitColorNodes,
+            ByteColorNodes,
+            FloatColorNodes,
+            HSVColorNodes,
+            CMYKColorNodes,
+            LABColorNodes,
+            AngleNodes,
+            UNFloatNodes,
+            SNFloatNodes,
+            CoordMapNodes,
+            BooleanNodes,
+            NibbleNodes,
+            ByteNodes,
+            UIntNodes,
+            SIntNodes,
+            SNFloatMatrix3Nodes,
+            SNPointNodes,
+            PointSetNodes,
+            IterativeFunctionNodes,
+            SNComplexNodes,
+            SFloatNormaliserNodes,
+            UFloatNormaliserNodes,
+            FrameRendererNodes
+        ])
+    };
+    (@match $node_type:expr, $T:ident => $body:block, [$($name:ident),*]) => {
+        match $node_type {
+            $(stringify!($name) => {
+                type $T = $name;
+                $body
+            })*
+            other => Err(failure::format_err!("{} isn't a node type", other)),
+        }
+    };
+}
+
+pub(crate) use with_node_type;
+
+/// Field names separated by `/`, leading from a [NodeTree] down to one of its nodes, e.g.
+/// `root_coordinate_node/child`. Only fields are named, not the enum variants they're in. Items in
+/// lists are picked by number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreePath(Vec<String>);
+
+impl TreePath {
+    pub fn new<I, S>(segments: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self(segments.into_iter().map(Into::into).collect())
+    }
+
+    /// The tree field this path starts from, if that's all there is to it
+    pub fn tree_field(&self) -> Option<NodeTreeField> {
+        match self.0.as_slice() {
+            [field] => NodeTreeField::from_name(field),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for TreePath {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Fallible<Self> {
+        let segments: Vec<_> = s.split('/').filter(|segment| !segment.is_empty()).collect();
+
+        if segments.is_empty() {
+            bail!("Empty tree path");
+        }
+
+        if let Some(segment) = segments.iter().find(|segment| {
+            !segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }) {
+            bail!("Invalid field '{}' in tree path {}", segment, s);
+        }
+
+        Ok(Self::new(segments))
+    }
+}
+
+impl fmt::Display for TreePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.join("/"))
+    }
+}
+
+/// The arena slot a [TreePath] leads to, read from a `NodeBox` serialised by
+/// [with_located_node_boxes]
+#[derive(Debug, Deserialize)]
+#[serde(rename = "NodeBox")]
+pub(crate) struct Slot {
+    pub node_type: String,
+    pub depth: usize,
+    pub index: Index,
+}
+
+/// Follows `path` from `root` to the slot it ends at.
+///
+/// This serialises the whole tree on every call, which is fine since it's only used by hotkeys and
+/// remote commands acting on one node at a time. Don't call it per frame.
+#[allow(clippy::ptr_arg)]
+pub(crate) fn locate<R: Serialize>(
+    root: &R,
+    nodes: &Vec<NodeSet>,
+    path: &TreePath,
+) -> Fallible<Slot> {
+    let root = with_located_node_boxes(nodes, || sexp_serde::to_value(root))?;
+    let mut value = &root;
+
+    for segment in &path.0 {
+        if value.name() == Some("NodeBox") {
+            value = value.get("value").unwrap();
+        }
+
+        value = value
+            .get(segment)
+            .ok_or_else(|| format_err!("There's no {} in the tree at {}", segment, path))?;
+    }
+
+    if value.name() != Some("NodeBox") {
+        bail!("{} isn't a node with its own slot", path);
+    }
+
+    Ok(sexp_serde::from_value(value.clone())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_paths() {
+        let path: TreePath = "root_coordinate_node/child/".parse().unwrap();
+        assert_eq!(path, TreePath::new(vec!["root_coordinate_node", "child"]));
+        assert_eq!(path.to_string(), "root_coordinate_node/child");
+
+        assert!("".parse::<TreePath>().is_err());
+        assert!("root_node/../constants".parse::<TreePath>().is_err());
+    }
+}
@@ -0,0 +1,83 @@
+//! Trees written out as S-expressions, so they can be read, written by hand, diffed and kept in
+//! version control. See [sexp_serde] for the syntax.
+//!
+//! Every [NodeBox] is written as the node it points at, so the text has no arena indices in it.
+//! Node types are left out, so the same text reads as whichever node type it's loaded as.
+
+use failure::Fallible;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::prelude::*;
+
+/// Writes `node` out, following each [NodeBox] under it into `nodes`. Nodes with more than one
+/// parent are written out once per parent.
+// scoped-tls can only hold sized values, so this can't take a slice
+#[allow(clippy::ptr_arg)]
+pub fn to_text<T: Serialize>(node: &T, nodes: &Vec<NodeSet>) -> Fallible<String> {
+    Ok(with_inlined_node_boxes(nodes, || {
+        sexp_serde::to_string(node)
+    })?)
+}
+
+/// Reads a node written by [to_text] or by hand. Everything under it is put in new slots in
+/// `nodes`, as if it had been generated at `depth` on tic `current_t`.
+pub fn from_text<T: DeserializeOwned>(
+    text: &str,
+    nodes: &mut Vec<NodeSet>,
+    depth: usize,
+    current_t: usize,
+) -> Fallible<T> {
+    Ok(with_allocated_node_boxes(nodes, depth, current_t, || {
+        sexp_serde::from_str(text)
+    })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_nodes(depth: usize) -> Vec<NodeSet> {
+        (0..depth).map(|_| NodeSet::new()).collect()
+    }
+
+    #[test]
+    fn reads_and_writes_nodes() {
+        let text = "\
+(Multiply
+  :child_a (Constant (UNFloat 0.25))
+  :child_b (Multiply :child_a (Constant (UNFloat 1.0)) :child_b (Constant (UNFloat 0.5))))
+";
+
+        let mut nodes = new_nodes(3);
+        let node: UNFloatNodes = from_text(
+            &format!(
+                "; Each child goes one level deeper than its parent\n{}",
+                text
+            ),
+            &mut nodes,
+            0,
+            7,
+        )
+        .unwrap();
+
+        let counts: Vec<_> = nodes.iter().map(NodeSet::count_all).collect();
+        assert_eq!(counts, vec![2, 2, 0]);
+
+        let written = to_text(&node, &nodes).unwrap();
+        assert_eq!(written, text);
+
+        let mut reread_nodes = new_nodes(3);
+        let reread: UNFloatNodes = from_text(&written, &mut reread_nodes, 0, 7).unwrap();
+        assert_eq!(to_text(&reread, &reread_nodes).unwrap(), written);
+    }
+
+    #[test]
+    fn rejects_trees_deeper_than_the_arenas() {
+        let text = "(Multiply
+          :child_a (Constant (UNFloat 0.25))
+          :child_b (Multiply :child_a (Constant (UNFloat 1.0)) :child_b (Constant (UNFloat 0.5))))";
+
+        assert!(from_text::<UNFloatNodes>(text, &mut new_nodes(1), 0, 0).is_err());
+        assert!(from_text::<UNFloatNodes>("(Multiply)", &mut new_nodes(3), 0, 0).is_err());
+    }
+}
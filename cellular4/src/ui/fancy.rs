@@ -224,6 +224,9 @@ impl UiBase for Ui {
             ("Alpha", update_stat.alpha_value),
             ("Local Similarity", update_stat.local_similarity_value),
             ("Global Similarity", update_stat.global_similarity_value),
+            ("Sharpness", update_stat.sharpness_value),
+            ("Colourfulness", update_stat.colourfulness_value),
+            ("Symmetry", update_stat.symmetry_value),
             ("Graph Stability", update_stat.graph_stability),
         ];
 
@@ -4,10 +4,11 @@ use std::{
 };
 
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::constants::*;
 
-#[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize)]
 pub struct UpdateStat {
     //Update stats are used to determine an approximation of the entropy of the current state
     //Update stats contain many values:
@@ -22,56 +23,98 @@ pub struct UpdateStat {
     pub alpha_value: f64,
     pub local_similarity_value: f64,
     pub global_similarity_value: f64,
+    pub sharpness_value: f64,
+    pub colourfulness_value: f64,
+    pub symmetry_value: f64,
     pub graph_stability: f64,
     pub cpu_usage: f64,
 }
 
-//TODO: add more heuristics of undesirability to inform mutation probability
-//Sharpness
-//Colourfulness
-//How symmetrical it is over the x and y axes
-impl UpdateStat {
-    pub fn should_mutate(&self) -> bool {
-        (thread_rng().gen::<f64>() * self.mutation_likelihood()
-                * CONSTS.cell_array_lerp_length as f64
-                / CONSTS.target_fps as f64).powf(5.0)
-         > thread_rng().gen::<f64>() * (1.0 - self.graph_stability)
-        //TODO: The following are placeholders until we can get something better going
-        || self.activity_value <= 0.1e-5
-        || self.alpha_value <= 0.1e-5
-        || self.local_similarity_value > 0.999
-        || self.global_similarity_value > 0.99
-    }
+/// The per-cell values in [UpdateStat] that feed into the mutation likelihood, each configured
+/// through [Constants::heuristics]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Heuristic {
+    Activity,
+    Alpha,
+    LocalSimilarity,
+    GlobalSimilarity,
+    Sharpness,
+    Colourfulness,
+    Symmetry,
+}
 
-    // pub fn should_mutate(&self) -> bool {
-    //     self.activity_value < CONSTS.activity_value_lower_bound
-    //         || self.alpha_value < CONSTS.alpha_value_lower_bound
-    //         || self.local_similarity_value > CONSTS.local_similarity_upper_bound
-    //         || self.global_similarity_value >= CONSTS.global_similarity_upper_bound
-    // }
+impl Heuristic {
+    pub fn value(self, update_stat: &UpdateStat) -> f64 {
+        match self {
+            Heuristic::Activity => update_stat.activity_value,
+            Heuristic::Alpha => update_stat.alpha_value,
+            Heuristic::LocalSimilarity => update_stat.local_similarity_value,
+            Heuristic::GlobalSimilarity => update_stat.global_similarity_value,
+            Heuristic::Sharpness => update_stat.sharpness_value,
+            Heuristic::Colourfulness => update_stat.colourfulness_value,
+            Heuristic::Symmetry => update_stat.symmetry_value,
+        }
+    }
+}
 
-    pub fn mutation_likelihood(&self) -> f64 {
-        (self.flatness() + self.noise() + self.stagnation() + self.transparency()) / 4.0
+impl HeuristicConfig {
+    pub fn is_out_of_bounds(&self, value: f64) -> bool {
+        value < self.lower_bound || value > self.upper_bound
     }
 
-    pub fn flatness(&self) -> f64 {
-        ((1.0 - self.activity_value).powf(4.0)
-            // + self.local_similarity_value.powf(4.0)
-            + self.global_similarity_value.powf(2.0))
-            // / 3.0
-            / 2.0
+    /// How far `value` is from the target, from 0 on target to 1 at either bound
+    pub fn undesirability(&self, value: f64) -> f64 {
+        let (distance, range) = if value < self.target {
+            (self.target - value, self.target - self.lower_bound)
+        } else {
+            (value - self.target, self.upper_bound - self.target)
+        };
+
+        if range > 0.0 {
+            (distance / range).min(1.0)
+        } else if distance > 0.0 {
+            1.0
+        } else {
+            0.0
+        }
     }
+}
 
-    pub fn noise(&self) -> f64 {
-        (self.activity_value.powf(4.0) + (1.0 - self.local_similarity_value).powf(2.0)) / 2.0
+impl UpdateStat {
+    pub fn should_mutate<R: Rng + ?Sized>(&self, rng: &mut R) -> bool {
+        (rng.gen::<f64>() * self.mutation_likelihood() * CONSTS.cell_array_lerp_length as f64
+            / CONSTS.target_fps as f64)
+            .powf(5.0)
+            > rng.gen::<f64>() * (1.0 - self.graph_stability)
+            || self.is_out_of_bounds()
     }
 
-    pub fn stagnation(&self) -> f64 {
-        (1.0 - self.activity_value).powf(4.0)
+    /// Whether any heuristic is outside its configured bounds, which always warrants a mutation
+    pub fn is_out_of_bounds(&self) -> bool {
+        CONSTS
+            .heuristics
+            .iter()
+            .any(|(heuristic, config)| config.is_out_of_bounds(heuristic.value(self)))
     }
 
-    pub fn transparency(&self) -> f64 {
-        (1.0 - self.alpha_value).powf(4.0)
+    /// The weighted average undesirability of every configured heuristic
+    pub fn mutation_likelihood(&self) -> f64 {
+        let (total, total_weight) = CONSTS.heuristics.iter().fold(
+            (0.0, 0.0),
+            |(total, total_weight), (heuristic, config)| {
+                (
+                    total + config.weight * config.undesirability(heuristic.value(self)),
+                    total_weight + config.weight,
+                )
+            },
+        );
+
+        if total_weight > 0.0 {
+            total / total_weight
+        } else {
+            0.0
+        }
     }
 
     //Function for dealing with floating point precision issues.
@@ -81,6 +124,9 @@ impl UpdateStat {
             alpha_value: self.alpha_value.min(1.0).max(0.0),
             local_similarity_value: self.local_similarity_value.min(1.0).max(0.0),
             global_similarity_value: self.global_similarity_value.min(1.0).max(0.0),
+            sharpness_value: self.sharpness_value.min(1.0).max(0.0),
+            colourfulness_value: self.colourfulness_value.min(1.0).max(0.0),
+            symmetry_value: self.symmetry_value.min(1.0).max(0.0),
             graph_stability: self.graph_stability.min(1.0).max(0.0),
             cpu_usage: self.cpu_usage.min(1.0).max(0.0),
         }
@@ -96,6 +142,9 @@ impl Add<UpdateStat> for UpdateStat {
             alpha_value: self.alpha_value + other.alpha_value,
             local_similarity_value: self.local_similarity_value + other.local_similarity_value,
             global_similarity_value: self.global_similarity_value + other.global_similarity_value,
+            sharpness_value: self.sharpness_value + other.sharpness_value,
+            colourfulness_value: self.colourfulness_value + other.colourfulness_value,
+            symmetry_value: self.symmetry_value + other.symmetry_value,
             graph_stability: self.graph_stability + other.graph_stability,
             cpu_usage: self.cpu_usage + other.cpu_usage,
         }
@@ -111,6 +160,9 @@ impl Div<f64> for UpdateStat {
             alpha_value: self.alpha_value / other,
             local_similarity_value: self.local_similarity_value / other,
             global_similarity_value: self.global_similarity_value / other,
+            sharpness_value: self.sharpness_value / other,
+            colourfulness_value: self.colourfulness_value / other,
+            symmetry_value: self.symmetry_value / other,
             graph_stability: self.graph_stability / other,
             cpu_usage: self.cpu_usage / other,
         }
@@ -126,6 +178,9 @@ impl Mul<f64> for UpdateStat {
             alpha_value: self.alpha_value * other,
             local_similarity_value: self.local_similarity_value * other,
             global_similarity_value: self.global_similarity_value * other,
+            sharpness_value: self.sharpness_value * other,
+            colourfulness_value: self.colourfulness_value * other,
+            symmetry_value: self.symmetry_value * other,
             graph_stability: self.graph_stability * other,
             cpu_usage: self.cpu_usage * other,
         }
@@ -146,3 +201,25 @@ impl AddAssign<UpdateStat> for UpdateStat {
         *self = *self + other;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undesirability_scales_towards_bounds() {
+        let config = HeuristicConfig {
+            weight: 1.0,
+            target: 0.5,
+            lower_bound: 0.25,
+            upper_bound: 1.0,
+        };
+
+        assert_eq!(config.undesirability(0.5), 0.0);
+        assert_eq!(config.undesirability(0.375), 0.5);
+        assert_eq!(config.undesirability(0.75), 0.5);
+        assert_eq!(config.undesirability(0.0), 1.0);
+        assert!(config.is_out_of_bounds(0.0));
+        assert!(!config.is_out_of_bounds(1.0));
+    }
+}
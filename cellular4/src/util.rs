@@ -43,6 +43,24 @@ lazy_static! {
         Mutex::new(SystemTime::UNIX_EPOCH.elapsed().unwrap().as_millis());
 }
 
+#[cfg(test)]
+lazy_static! {
+    static ref RNG_SEED_TEST_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Runs `f` with [RNG_SEED] set to `seed`. Tests run in parallel, so any test that generates or
+/// mutates a tree goes through this to keep others from changing the seed under it.
+#[cfg(test)]
+pub fn with_rng_seed<F: FnOnce() -> R, R>(seed: u128, f: F) -> R {
+    // A failed test still leaves the seed usable
+    let _guard = RNG_SEED_TEST_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    *RNG_SEED.lock().unwrap() = seed;
+
+    f()
+}
+
 #[cfg(target_pointer_width = "64")]
 type DeterministicRngImpl = rand_pcg::Pcg64Mcg;
 
@@ -96,6 +114,29 @@ impl DeterministicRng {
         debug!("Initializing RNG with seed {}", seed);
         Self::from_seed(seed.to_le_bytes())
     }
+
+    /// An rng that always produces the same stream for the same key, regardless of [RNG_SEED].
+    /// Compute runs in parallel and can't share a mutable rng, so randomness there is keyed off whatever
+    /// is being computed instead. The tree itself still depends on the seed.
+    pub fn from_key(key: u64) -> Self {
+        let high = mix_key(key);
+        let low = mix_key(high ^ key);
+
+        Self::from_seed((u128::from(high) << 64 | u128::from(low)).to_le_bytes())
+    }
+}
+
+/// Folds `values` into a single well distributed key for [DeterministicRng::from_key]
+pub fn hash_key(values: &[u64]) -> u64 {
+    values.iter().fold(0, |key, &value| mix_key(key ^ value))
+}
+
+// splitmix64's finaliser, so nearby inputs don't give correlated streams
+fn mix_key(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
 }
 
 #[inline(always)]
@@ -1,6 +1,8 @@
 mod error;
 mod ser;
+mod shared;
 mod util;
 
 pub use error::{Error, Result};
 pub use ser::{to_string, to_vec, to_writer, Serializer};
+pub use shared::Shared;
@@ -1,9 +1,10 @@
-use std::{borrow::Cow, io::Write};
+use std::{borrow::Cow, collections::HashMap, io::Write};
 
 use serde::{ser, Serialize};
 
 use crate::{
     error::{Error, Result},
+    shared::SHARED_NAME,
     util::Counter,
 };
 
@@ -42,6 +43,8 @@ pub struct Serializer<W> {
     writer: W,
     current_node: Option<Node>,
     node_counter: Counter,
+    /// Node ids of shared values that have already been emitted, by their shared id
+    shared_nodes: HashMap<u64, u64>,
 }
 
 impl<W: Write> Serializer<W> {
@@ -52,6 +55,7 @@ impl<W: Write> Serializer<W> {
             writer,
             current_node: None,
             node_counter: Counter::new(),
+            shared_nodes: HashMap::new(),
         })
     }
 
@@ -143,6 +147,39 @@ impl<W: Write> Serializer<W> {
 
         Ok(())
     }
+
+    /// Adds an edge from the current node to an already emitted node, instead of emitting a new one
+    fn reference_node(&mut self, id: u64) -> Result<()> {
+        if let Some(node) = self.current_node.as_mut() {
+            let key = node.take_key();
+            writeln!(
+                self.writer,
+                "node_{} -> node_{} [label=<{}>]",
+                node.id, id, key,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn serialize_shared<T>(&mut self, shared_id: u64, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        if let Some(&id) = self.shared_nodes.get(&shared_id) {
+            return self.reference_node(id);
+        }
+
+        let id = self.node_counter.peek();
+        value.serialize(&mut *self)?;
+
+        // Values that don't become nodes of their own are just fields of their parent, and can't be referenced
+        if self.node_counter.peek() != id {
+            self.shared_nodes.insert(shared_id, id);
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -595,15 +632,26 @@ impl<'a, W: Write> ser::SerializeMap for SerializerMap<'a, W> {
 
 pub struct SerializerStruct<'a, W> {
     ser: &'a mut Serializer<W>,
-    prev_node: Option<Node>,
+    kind: StructKind,
+}
+
+enum StructKind {
+    Node { prev_node: Option<Node> },
+    /// A [crate::Shared] value, which doesn't get a node of its own
+    Shared { shared_id: Option<u64> },
 }
 
 impl<'a, W: Write> SerializerStruct<'a, W> {
     fn start(ser: &'a mut Serializer<W>, name: &'static str) -> Result<Self> {
-        Ok(Self {
-            prev_node: ser.start_node(Cow::Borrowed(name))?,
-            ser,
-        })
+        let kind = if name == SHARED_NAME {
+            StructKind::Shared { shared_id: None }
+        } else {
+            StructKind::Node {
+                prev_node: ser.start_node(Cow::Borrowed(name))?,
+            }
+        };
+
+        Ok(Self { ser, kind })
     }
 }
 
@@ -615,14 +663,40 @@ impl<'a, W: Write> ser::SerializeStruct for SerializerStruct<'a, W> {
     where
         T: ?Sized + Serialize,
     {
-        self.ser.serialize_key(Cow::Borrowed(key))?;
-        value.serialize(&mut *self.ser)?;
+        match &mut self.kind {
+            StructKind::Node { .. } => {
+                self.ser.serialize_key(Cow::Borrowed(key))?;
+                value.serialize(&mut *self.ser)?;
+            }
+            StructKind::Shared { shared_id } => match key {
+                "id" => {
+                    // HACK Same as map keys, serde_json is the easiest way to get the id back out
+                    *shared_id = Some(
+                        serde_json::to_string(value)
+                            .map_err(<Error as ser::Error>::custom)?
+                            .parse()
+                            .map_err(<Error as ser::Error>::custom)?,
+                    );
+                }
+                "value" => {
+                    let shared_id = shared_id.unwrap_or_else(|| {
+                        panic!("Missing id when attempting to serialize shared value")
+                    });
+
+                    self.ser.serialize_shared(shared_id, value)?;
+                }
+                _ => panic!("Unexpected field {} in shared value", key),
+            },
+        }
 
         Ok(())
     }
 
     fn end(self) -> Result<()> {
-        self.ser.end_node(self.prev_node)
+        match self.kind {
+            StructKind::Node { prev_node } => self.ser.end_node(prev_node),
+            StructKind::Shared { .. } => Ok(()),
+        }
     }
 }
 
@@ -726,6 +800,33 @@ mod test {
         graph(&E::Struct { a: 1 }, "enum_struct");
     }
 
+    #[test]
+    fn test_shared() {
+        use crate::Shared;
+
+        #[derive(Serialize)]
+        struct Leaf {
+            a: u32,
+        }
+
+        #[derive(Serialize)]
+        struct Parent<'a> {
+            left: Shared<'a, Leaf>,
+            right: Shared<'a, Leaf>,
+        }
+
+        let leaf = Leaf { a: 1 };
+        let parent = Parent {
+            left: Shared::new(0, &leaf),
+            right: Shared::new(0, &leaf),
+        };
+
+        let graph = to_string(&parent).unwrap();
+
+        assert_eq!(graph.matches(">Leaf<").count(), 1);
+        assert_eq!(graph.matches(" -> ").count(), 2);
+    }
+
     #[test]
     fn test_nested() {
         #[derive(Serialize)]
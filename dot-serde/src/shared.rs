@@ -0,0 +1,34 @@
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+
+/// Struct name used to recognise [Shared] values while serializing
+pub(crate) const SHARED_NAME: &str = "$dot_serde::Shared";
+
+/// A value that can be reached from more than one parent.
+///
+/// The first time an `id` is serialized the value is drawn as normal, after that every parent just gets
+/// an edge to the existing node, so DAGs aren't flattened into trees. Values with the same `id` are
+/// assumed to be the same value.
+///
+/// Other serializers see this as a struct with `id` and `value` fields.
+pub struct Shared<'a, T: ?Sized> {
+    id: u64,
+    value: &'a T,
+}
+
+impl<'a, T: ?Sized> Shared<'a, T> {
+    pub fn new(id: u64, value: &'a T) -> Self {
+        Self { id, value }
+    }
+}
+
+impl<'a, T> Serialize for Shared<'a, T>
+where
+    T: ?Sized + Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct(SHARED_NAME, 2)?;
+        state.serialize_field("id", &self.id)?;
+        state.serialize_field("value", self.value)?;
+        state.end()
+    }
+}
@@ -9,6 +9,10 @@ impl Counter {
         Self { current: 0 }
     }
 
+    pub fn peek(&self) -> u64 {
+        self.current
+    }
+
     pub fn next(&mut self) -> Result<u64> {
         if let Some(next) = self.current.checked_add(1) {
             let i = self.current;
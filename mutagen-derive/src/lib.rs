@@ -27,12 +27,15 @@ mod a {
     pub const MUT_ARG: &str = "mut_arg";
     pub const MUT_WEIGHT: &str = "mut_weight";
     pub const SKIP: &str = "skip";
+    pub const CROSS_ARG: &str = "cross_arg";
+    pub const CROSS_WEIGHT: &str = "cross_weight";
+    pub const DIST_ARG: &str = "dist_arg";
 
     // Allowed keys for each item
-    pub const ENUM: &[&str] = &[MUT_REROLL, MUT_ARG, GEN_ARG];
-    pub const ENUM_VARIANT: &[&str] = &[GEN_WEIGHT, GEN_PREFERRED, MUT_REROLL];
-    pub const FIELD: &[&str] = &[MUT_WEIGHT, SKIP];
-    pub const TYPE: &[&str] = &[MUT_REROLL, MUT_ARG, GEN_ARG];
+    pub const ENUM: &[&str] = &[MUT_REROLL, MUT_ARG, GEN_ARG, CROSS_ARG, DIST_ARG];
+    pub const ENUM_VARIANT: &[&str] = &[GEN_WEIGHT, GEN_PREFERRED, MUT_REROLL, CROSS_WEIGHT];
+    pub const FIELD: &[&str] = &[MUT_WEIGHT, CROSS_WEIGHT, SKIP];
+    pub const TYPE: &[&str] = &[MUT_REROLL, MUT_ARG, GEN_ARG, CROSS_ARG, DIST_ARG];
 }
 
 #[proc_macro_derive(Generatable, attributes(mutagen))]
@@ -387,6 +390,498 @@ fn mutatable_fields(fields: &[&Field], path: &str, span: Span) -> Result<TokenSt
     )
 }
 
+#[proc_macro_derive(Crossover, attributes(mutagen))]
+pub fn derive_crossover(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+    let output = crossover_type(input).unwrap_or_else(|e| e.to_compile_error());
+    proc_macro::TokenStream::from(output)
+}
+
+fn crossover_type(input: syn::DeriveInput) -> Result<TokenStream2> {
+    let span = input.span();
+
+    let cross_arg = parse_attrs(&input.attrs, a::TYPE)?
+        .get(a::CROSS_ARG)
+        .cloned()
+        .ok_or_else(|| Error::new(span, "Missing cross_arg attribute"))?
+        .to_type()?;
+
+    let (crossover_body, transplant_body) = match &input.data {
+        Data::Struct(s) => crossover_struct(&input.ident, s, &input.attrs, span)?,
+        Data::Enum(e) => crossover_enum(&input.ident, e, &input.attrs, span)?,
+        Data::Union(_) => panic!("#[derive(Crossover)] is not yet implemented for unions"),
+    };
+
+    let ident = input.ident;
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl<'a> ::mutagen::Crossover<'a> for #ident {
+            type CrossArg = #cross_arg;
+
+            #[allow(unused_mut, unused_variables)]
+            fn crossover_rng<R: ::mutagen::rand::Rng + ?Sized>(
+                &mut self,
+                rng: &mut R,
+                other: &Self,
+                mut arg: Self::CrossArg
+            ) {
+                #crossover_body
+            }
+
+            #[allow(unused_mut, unused_variables)]
+            fn transplant(&self, mut arg: Self::CrossArg) -> Self {
+                #transplant_body
+            }
+        }
+    })
+}
+
+fn crossover_struct(
+    ident: &Ident,
+    s: &DataStruct,
+    _attrs: &[Attribute],
+    _span: Span,
+) -> Result<(TokenStream2, TokenStream2)> {
+    let bindings = fields_bindings(&s.fields)?;
+    let other_bindings = other_fields_bindings(&s.fields)?;
+    let fields = flatten_fields(&s.fields);
+    let body = crossover_fields(&fields, &ident.to_string(), s.fields.span())?;
+    let transplanted = transplant_fields(&s.fields)?;
+
+    Ok((
+        quote! {
+            let #ident #bindings = self;
+            let #ident #other_bindings = other;
+            #body
+        },
+        quote! {
+            let #ident #bindings = self;
+            #ident #transplanted
+        },
+    ))
+}
+
+fn crossover_enum(
+    enum_ident: &Ident,
+    e: &DataEnum,
+    _attrs: &[Attribute],
+    span: Span,
+) -> Result<(TokenStream2, TokenStream2)> {
+    if e.variants.is_empty() {
+        panic!("Cannot derive Crossover for enum with no variants");
+    }
+
+    let crossed: Vec<_> = e
+        .variants
+        .iter()
+        .map(|variant| {
+            let ident = &variant.ident;
+            let bindings = fields_bindings(&variant.fields)?;
+            let other_bindings = other_fields_bindings(&variant.fields)?;
+            let fields_body = crossover_fields(
+                &flatten_fields(&variant.fields),
+                &format!("{}::{}", &enum_ident, &variant.ident),
+                variant.fields.span(),
+            )?;
+
+            Ok(quote! {
+                (#enum_ident::#ident #bindings, #enum_ident::#ident #other_bindings) => {
+                    #fields_body
+                }
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    let variant_weights: Vec<_> = e
+        .variants
+        .iter()
+        .map(|variant| {
+            let ident = &variant.ident;
+            let wildcard = fields_wildcard(&variant.fields);
+            let weight = parse_attrs(&variant.attrs, a::ENUM_VARIANT)?
+                .get(a::CROSS_WEIGHT)
+                .cloned()
+                .unwrap_or_else(|| Value::None(span))
+                .to_weight()?
+                .unwrap_or_else(|| quote!(0.0));
+
+            Ok(quote! {
+                #enum_ident::#ident #wildcard => #weight,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    let transplanted: Vec<_> = e
+        .variants
+        .iter()
+        .map(|variant| {
+            let ident = &variant.ident;
+            let bindings = fields_bindings(&variant.fields)?;
+            let fields = transplant_fields(&variant.fields)?;
+
+            Ok(quote! {
+                #enum_ident::#ident #bindings => #enum_ident::#ident #fields,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok((
+        quote! {
+            if ::std::mem::discriminant(self) == ::std::mem::discriminant(other) {
+                #[allow(unreachable_patterns)]
+                match (self, other) {
+                    #( #crossed )*
+                    _ => unreachable!(),
+                }
+            } else {
+                let weight: f64 = match self {
+                    #( #variant_weights )*
+                };
+                let other_weight: f64 = match other {
+                    #( #variant_weights )*
+                };
+
+                if other_weight > 0.0 && rng.gen_bool(other_weight / (weight + other_weight)) {
+                    *self = ::mutagen::Crossover::transplant(other, arg);
+                }
+            }
+        },
+        quote! {
+            match self {
+                #( #transplanted )*
+            }
+        },
+    ))
+}
+
+fn fields_wildcard(fields: &Fields) -> TokenStream2 {
+    match fields {
+        Fields::Named(_) => quote!({ .. }),
+        Fields::Unnamed(_) => quote!((..)),
+        Fields::Unit => TokenStream2::new(),
+    }
+}
+
+fn other_fields_bindings(fields: &Fields) -> Result<TokenStream2> {
+    match fields {
+        Fields::Named(fields) => {
+            let bindings: Vec<TokenStream2> = fields
+                .named
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    let ident = &field.ident;
+                    if parse_attrs(&field.attrs, a::FIELD)?.contains_key(a::SKIP) {
+                        Ok(quote! { #ident: _ })
+                    } else {
+                        let other = other_field_ident(field, i);
+                        Ok(quote! { #ident: #other })
+                    }
+                })
+                .collect::<Result<_>>()?;
+
+            Ok(quote! {
+                { #(#bindings),* }
+            })
+        }
+
+        Fields::Unnamed(fields) => {
+            let fields: Vec<TokenStream2> = fields
+                .unnamed
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    if parse_attrs(&field.attrs, a::FIELD)?.contains_key(a::SKIP) {
+                        Ok(quote!(_))
+                    } else {
+                        Ok(other_field_ident(field, i).to_token_stream())
+                    }
+                })
+                .collect::<Result<_>>()?;
+
+            Ok(quote! {
+                ( #(#fields),* )
+            })
+        }
+
+        Fields::Unit => Ok(TokenStream2::new()),
+    }
+}
+
+fn crossover_fields(fields: &[&Field], path: &str, span: Span) -> Result<TokenStream2> {
+    if fields.is_empty() {
+        return Ok(TokenStream2::new());
+    }
+
+    roll(
+        fields,
+        |field| cross_weight(field, span),
+        |field, i| {
+            let ident = field_ident(field, i);
+            let other = other_field_ident(field, i);
+            Ok(quote! {
+                ::mutagen::Crossover::crossover_rng(#ident, rng, #other, ::mutagen::State::deepened(::std::convert::From::from(::mutagen::Reborrow::reborrow(&mut arg))));
+            })
+        },
+        &format!("crossover for {}", path),
+    )
+}
+
+fn cross_weight(field: &Field, span: Span) -> Result<Value> {
+    let attrs = parse_attrs(&field.attrs, a::FIELD)?;
+
+    Ok(if attrs.contains_key(a::SKIP) {
+        Value::Val(0.0)
+    } else {
+        attrs
+            .get(a::CROSS_WEIGHT)
+            .cloned()
+            .unwrap_or_else(|| Value::None(span))
+    })
+}
+
+fn transplant_fields(fields: &Fields) -> Result<TokenStream2> {
+    let transplant = |field: &Field, i: usize| -> Result<TokenStream2> {
+        if parse_attrs(&field.attrs, a::FIELD)?.contains_key(a::SKIP) {
+            Ok(quote! {
+                ::std::default::Default::default()
+            })
+        } else {
+            let ident = field_ident(field, i);
+            Ok(quote! {
+                ::mutagen::Crossover::transplant(#ident, ::mutagen::State::deepened(::std::convert::From::from(::mutagen::Reborrow::reborrow(&mut arg))))
+            })
+        }
+    };
+
+    match fields {
+        Fields::Named(f) => {
+            let item: Vec<TokenStream2> = f
+                .named
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    let name = &field.ident;
+                    let value = transplant(field, i)?;
+                    Ok(quote! { #name: #value })
+                })
+                .collect::<Result<_>>()?;
+
+            Ok(quote! {{ #(#item),* }})
+        }
+        Fields::Unnamed(f) => {
+            let item: Vec<TokenStream2> = f
+                .unnamed
+                .iter()
+                .enumerate()
+                .map(|(i, field)| transplant(field, i))
+                .collect::<Result<_>>()?;
+
+            Ok(quote! {( #(#item),* )})
+        }
+        Fields::Unit => Ok(TokenStream2::new()),
+    }
+}
+
+#[proc_macro_derive(TreeDistance, attributes(mutagen))]
+pub fn derive_tree_distance(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+    let output = tree_distance_type(input).unwrap_or_else(|e| e.to_compile_error());
+    proc_macro::TokenStream::from(output)
+}
+
+fn tree_distance_type(input: syn::DeriveInput) -> Result<TokenStream2> {
+    let span = input.span();
+
+    let dist_arg = parse_attrs(&input.attrs, a::TYPE)?
+        .get(a::DIST_ARG)
+        .cloned()
+        .ok_or_else(|| Error::new(span, "Missing dist_arg attribute"))?
+        .to_type()?;
+
+    let body = match &input.data {
+        Data::Struct(s) => tree_distance_struct(&input.ident, s, &input.attrs, span)?,
+        Data::Enum(e) => tree_distance_enum(&input.ident, e, &input.attrs, span)?,
+        Data::Union(_) => panic!("#[derive(TreeDistance)] is not yet implemented for unions"),
+    };
+
+    let size_body = match &input.data {
+        Data::Struct(s) => tree_size_struct(&input.ident, s)?,
+        Data::Enum(e) => tree_size_enum(&input.ident, e)?,
+        Data::Union(_) => unreachable!(),
+    };
+
+    let ident = input.ident;
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl<'a> ::mutagen::TreeDistance<'a> for #ident {
+            type DistArg = #dist_arg;
+
+            #[allow(unused_mut, unused_variables)]
+            fn tree_distance(&self, other: &Self, mut arg: Self::DistArg) -> f64 {
+                #body
+            }
+
+            #[allow(unused_mut, unused_variables)]
+            fn tree_size(&self, mut arg: Self::DistArg) -> usize {
+                #size_body
+            }
+        }
+    })
+}
+
+fn tree_distance_struct(
+    ident: &Ident,
+    s: &DataStruct,
+    _attrs: &[Attribute],
+    _span: Span,
+) -> Result<TokenStream2> {
+    let bindings = fields_bindings(&s.fields)?;
+    let other_bindings = other_fields_bindings(&s.fields)?;
+    let body = tree_distance_fields(&flatten_fields(&s.fields), s.fields.span())?;
+
+    Ok(quote! {
+        let #ident #bindings = self;
+        let #ident #other_bindings = other;
+        #body
+    })
+}
+
+fn tree_distance_enum(
+    enum_ident: &Ident,
+    e: &DataEnum,
+    _attrs: &[Attribute],
+    _span: Span,
+) -> Result<TokenStream2> {
+    let variants: Vec<_> = e
+        .variants
+        .iter()
+        .map(|variant| {
+            let ident = &variant.ident;
+            let bindings = fields_bindings(&variant.fields)?;
+            let other_bindings = other_fields_bindings(&variant.fields)?;
+            let fields_body =
+                tree_distance_fields(&flatten_fields(&variant.fields), variant.fields.span())?;
+
+            Ok(quote! {
+                (#enum_ident::#ident #bindings, #enum_ident::#ident #other_bindings) => {
+                    #fields_body
+                }
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(quote! {
+        #[allow(unreachable_patterns)]
+        match (self, other) {
+            #( #variants )*
+            _ => 1.0,
+        }
+    })
+}
+
+fn tree_distance_fields(fields: &[&Field], span: Span) -> Result<TokenStream2> {
+    let weighted: Vec<TokenStream2> = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            Ok(cross_weight(field, span)?.to_weight()?.map(|weight| {
+                let ident = field_ident(field, i);
+                let other = other_field_ident(field, i);
+
+                quote! {
+                    {
+                        let weight: f64 = #weight;
+                        total_weight += weight;
+                        total_distance += weight * ::mutagen::TreeDistance::tree_distance(#ident, #other, ::mutagen::State::deepened(::std::convert::From::from(::mutagen::Reborrow::reborrow(&mut arg))));
+                    }
+                }
+            }))
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    if weighted.is_empty() {
+        return Ok(quote!(0.0));
+    }
+
+    Ok(quote! {
+        let mut total_weight: f64 = 0.0;
+        let mut total_distance: f64 = 0.0;
+        #( #weighted )*
+
+        if total_weight > 0.0 {
+            total_distance / total_weight
+        } else {
+            0.0
+        }
+    })
+}
+
+fn tree_size_struct(ident: &Ident, s: &DataStruct) -> Result<TokenStream2> {
+    let bindings = fields_bindings(&s.fields)?;
+    let body = tree_size_fields(&flatten_fields(&s.fields), s.fields.span())?;
+
+    Ok(quote! {
+        let #ident #bindings = self;
+        #body
+    })
+}
+
+fn tree_size_enum(enum_ident: &Ident, e: &DataEnum) -> Result<TokenStream2> {
+    let variants: Vec<_> = e
+        .variants
+        .iter()
+        .map(|variant| {
+            let ident = &variant.ident;
+            let bindings = fields_bindings(&variant.fields)?;
+            let fields_body =
+                tree_size_fields(&flatten_fields(&variant.fields), variant.fields.span())?;
+
+            Ok(quote! {
+                #enum_ident::#ident #bindings => {
+                    #fields_body
+                }
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(quote! {
+        #[allow(unreachable_patterns)]
+        match self {
+            #( #variants )*
+            _ => 0,
+        }
+    })
+}
+
+fn tree_size_fields(fields: &[&Field], span: Span) -> Result<TokenStream2> {
+    let sizes: Vec<TokenStream2> = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            Ok(cross_weight(field, span)?.to_weight()?.map(|_| {
+                let ident = field_ident(field, i);
+
+                quote! {
+                    + ::mutagen::TreeDistance::tree_size(#ident, ::mutagen::State::deepened(::std::convert::From::from(::mutagen::Reborrow::reborrow(&mut arg))))
+                }
+            }))
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    Ok(quote! {
+        0 #( #sizes )*
+    })
+}
+
 #[proc_macro_derive(UpdatableRecursively, attributes(mutagen))]
 pub fn derive_updatable(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = parse_macro_input!(input as syn::DeriveInput);
@@ -470,8 +965,10 @@ fn updatable_enum(
         .collect::<Result<_>>()?;
 
     Ok(quote! {
+        #[allow(unreachable_patterns)]
         match self {
             #( #variants )*
+            _ => 0,
         }
     })
 }
@@ -590,6 +1087,10 @@ fn field_ident(field: &Field, i: usize) -> Cow<'_, Ident> {
     }
 }
 
+fn other_field_ident(field: &Field, i: usize) -> Ident {
+    format_ident!("other_{}", field_ident(field, i).as_ref())
+}
+
 fn tuple_field_ident(i: usize) -> Ident {
     Ident::new(&format!("_{}", i), Span::call_site())
 }
@@ -750,9 +1251,10 @@ impl Value {
 
 impl Parse for Value {
     fn parse(input: ParseStream) -> Result<Self> {
-        // TODO Add support for closure variant
         let lookahead = input.lookahead1();
-        if lookahead.peek(Bracket) {
+        if lookahead.peek(Token![|]) {
+            input.parse().map(Value::Closure)
+        } else if lookahead.peek(Bracket) {
             input.parse().map(Value::Values)
         } else if lookahead.peek(Token![type]) {
             let _type: Token![type] = input.parse()?;
@@ -13,12 +13,32 @@
 //! When derived on an enum, it requires [Generatable] to also be implemented for all fields, unless mut_reroll is 0.
 //! It will then choose whether to re-roll a new variant with probability mut_reroll, or to mutate its current variant.
 //!
+//! # Crossover
+//!
+//! When derived on a struct, it will pick a field at random, weighted by cross_weight, and cross it
+//! with the same field of the other value.
+//!
+//! When derived on an enum, it will cross a field picked the same way if both values are the same variant.
+//! Otherwise, it will either keep its own variant or take a [transplant](crate::Crossover::transplant) of
+//! the other value's, in proportion to the cross_weight of each variant.
+//!
+//! # TreeDistance
+//!
+//! When derived on a struct, it will average the distances between each pair of fields, weighted by cross_weight.
+//!
+//! When derived on an enum, values of different variants are as far apart as possible.
+//! Values of the same variant are compared like a struct.
+//!
+//! The derived [tree_size](crate::TreeDistance::tree_size) adds up the sizes of the same fields.
+//!
 //! # Attributes
 //!
 //! This crate makes extensive use of key-value pairs in attributes to customize the behaviour of its derive macros.
 //! Key-value pairs are always contained inside a #[mutagen()], as shown in the example below.
-//! Both floating point literals and function names are allowed as values.
+//! Floating point literals, function names and closures are allowed as values.
 //! When a function name is used, its signature should be `fn(&mutagen::State) -> f64`
+//! Closures take the same argument, which needs its type written out. Closures on struct fields can
+//! also refer to the fields by name, e.g. `#[mutagen(mut_weight = |arg: Arg| arg.weight_of(child))]`
 //!
 //! ```rust
 //! use mutagen::{Generatable, Mutatable};
@@ -104,7 +124,24 @@
 //! **`#[mutagen(skip)]`**
 //!
 //! When applied to a field, it is equivalent to `#[mutagen(mut_weight = 0.0)]`, and in addition its
-//! type does not need to implement Generatable. Instead, the derived impl will use the type's `Default` impl.
+//! type does not need to implement Generatable nor Crossover. Instead, the derived impl will use the type's `Default` impl.
+//!
+//! **`#[mutagen(cross_weight = 1.0)]`**
+//!
+//! When applied to a struct field, it affects how often that field is crossed, and how much it counts
+//! towards the distance between two values.
+//! When applied to an enum variant, it affects how likely that variant is to be kept when crossed with
+//! a value of a different variant.
+//! By default, all fields and variants have weight 1.
+//!
+//! Note that when a field has a weight of 0, it is never crossed nor compared, so the derived TreeDistance
+//! impl will not expect it to implement TreeDistance. It still needs to implement Crossover to be
+//! transplanted, unless it is skipped.
+//!
+//! **`#[mutagen(cross_arg = type (), dist_arg = type ())]`**
+//!
+//! When applied to a struct or enum, they set the argument types of the derived [Crossover] and
+//! [TreeDistance] impls.
 
 #[doc(no_inline)]
 /// The `rand` dependency, re-exported for ease of access
@@ -113,7 +150,12 @@ pub use rand;
 #[doc(hidden)]
 pub use mutagen_derive::*;
 
-use std::{borrow::Cow, ops::DerefMut, rc::Rc, sync::Arc};
+use std::{
+    borrow::Cow,
+    ops::{Deref, DerefMut},
+    rc::Rc,
+    sync::Arc,
+};
 
 use rand::Rng;
 
@@ -208,6 +250,68 @@ impl<'a, T: Mutatable<'a>> Mutatable<'a> for Box<T> {
     }
 }
 
+/// A trait denoting that the type may be recombined with another value of the same type
+///
+/// Crossover is one-sided: `self` takes a type-compatible part of `other`, and `other` is left as it was.
+/// Crossing each parent with the other gives both children of a swap.
+///
+/// For more information, consult the [crate docs](crate).
+pub trait Crossover<'a>: Sized {
+    type CrossArg: State + 'a;
+
+    /// Convenience shorthand for `self.crossover_rng(&mut rand::thread_rng(), other, arg)`
+    fn crossover(&mut self, other: &Self, arg: Self::CrossArg) {
+        self.crossover_rng(&mut rand::thread_rng(), other, arg)
+    }
+
+    /// Replaces part of `self` with the matching part of `other`
+    fn crossover_rng<R: Rng + ?Sized>(&mut self, rng: &mut R, other: &Self, arg: Self::CrossArg);
+
+    /// Copies `self`, which belongs to the other value, into the value described by `arg`.
+    /// Types that refer to data stored outside of themselves should copy that data across too.
+    fn transplant(&self, arg: Self::CrossArg) -> Self;
+}
+
+impl<'a, T: Crossover<'a>> Crossover<'a> for Box<T> {
+    type CrossArg = T::CrossArg;
+
+    fn crossover_rng<R: Rng + ?Sized>(&mut self, rng: &mut R, other: &Self, arg: Self::CrossArg) {
+        self.deref_mut().crossover_rng(rng, other.deref(), arg)
+    }
+
+    fn transplant(&self, arg: Self::CrossArg) -> Self {
+        Box::new(self.deref().transplant(arg))
+    }
+}
+
+/// A trait for measuring how structurally different two values of the same type are,
+/// e.g. to keep a population diverse
+///
+/// For more information, consult the [crate docs](crate).
+pub trait TreeDistance<'a> {
+    type DistArg: State + 'a;
+
+    /// Returns 0 for values with the same structure, up to 1 for values with nothing in common
+    fn tree_distance(&self, other: &Self, arg: Self::DistArg) -> f64;
+
+    /// Counts the nodes in `self` without changing or copying it. Leaf values have none.
+    fn tree_size(&self, _arg: Self::DistArg) -> usize {
+        0
+    }
+}
+
+impl<'a, T: TreeDistance<'a>> TreeDistance<'a> for Box<T> {
+    type DistArg = T::DistArg;
+
+    fn tree_distance(&self, other: &Self, arg: Self::DistArg) -> f64 {
+        self.deref().tree_distance(other.deref(), arg)
+    }
+
+    fn tree_size(&self, arg: Self::DistArg) -> usize {
+        self.deref().tree_size(arg)
+    }
+}
+
 /// A trait denoting that the type may be updated.
 ///
 /// # Derive
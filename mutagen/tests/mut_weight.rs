@@ -0,0 +1,58 @@
+use mutagen::Mutatable;
+use rand::{rngs::StdRng, SeedableRng};
+
+/// Counts how many times it's been mutated
+#[derive(Default)]
+struct Counter(u32);
+
+impl<'a> Mutatable<'a> for Counter {
+    type MutArg = ();
+
+    fn mutate_rng<R: rand::Rng + ?Sized>(&mut self, _rng: &mut R, _arg: ()) {
+        self.0 += 1;
+    }
+}
+
+#[derive(Mutatable)]
+#[mutagen(mut_arg = type ())]
+struct Weighted {
+    #[mutagen(mut_weight = 0.0)]
+    a_weight: f64,
+    #[mutagen(mut_weight = 0.0)]
+    b_weight: f64,
+
+    #[mutagen(mut_weight = |_arg: ()| *a_weight)]
+    a: Counter,
+    #[mutagen(mut_weight = |_arg: ()| *b_weight)]
+    b: Counter,
+}
+
+fn mutate_weighted(a_weight: f64, b_weight: f64) -> (u32, u32) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut weighted = Weighted {
+        a_weight,
+        b_weight,
+        a: Counter::default(),
+        b: Counter::default(),
+    };
+
+    for _ in 0..1000 {
+        weighted.mutate_rng(&mut rng, ());
+    }
+
+    (weighted.a.0, weighted.b.0)
+}
+
+#[test]
+fn closures_weigh_fields_by_other_fields() {
+    let (a, b) = mutate_weighted(3.0, 1.0);
+
+    assert_eq!(a + b, 1000);
+    assert!(a > 2 * b, "a mutated {} times and b {} times", a, b);
+}
+
+#[test]
+fn zero_weight_closures_never_mutate() {
+    assert_eq!(mutate_weighted(1.0, 0.0), (1000, 0));
+    assert_eq!(mutate_weighted(0.0, 1.0), (0, 1000));
+}
@@ -0,0 +1,49 @@
+use mutagen::TreeDistance;
+
+/// Stands in for a node, counting as one
+struct Node;
+
+impl<'a> TreeDistance<'a> for Node {
+    type DistArg = ();
+
+    fn tree_distance(&self, _other: &Self, _arg: ()) -> f64 {
+        0.0
+    }
+
+    fn tree_size(&self, _arg: ()) -> usize {
+        1
+    }
+}
+
+#[derive(TreeDistance)]
+#[mutagen(dist_arg = type ())]
+struct Pair {
+    a: Node,
+    b: Box<Node>,
+
+    // Never compared, so never counted either
+    #[mutagen(cross_weight = 0.0)]
+    not_compared: Node,
+}
+
+#[derive(TreeDistance)]
+#[mutagen(dist_arg = type ())]
+enum Tree {
+    Empty,
+    One(Node),
+    Pairs(Pair, Pair),
+}
+
+#[test]
+fn derived_tree_size_adds_up_fields() {
+    let pair = || Pair {
+        a: Node,
+        b: Box::new(Node),
+        not_compared: Node,
+    };
+
+    assert_eq!(pair().tree_size(()), 2);
+    assert_eq!(Tree::Empty.tree_size(()), 0);
+    assert_eq!(Tree::One(Node).tree_size(()), 1);
+    assert_eq!(Tree::Pairs(pair(), pair()).tree_size(()), 4);
+}
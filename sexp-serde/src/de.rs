@@ -0,0 +1,431 @@
+use std::{fmt::Display, str::FromStr, vec};
+
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, VariantAccess,
+    Visitor,
+};
+
+use crate::{
+    error::{Error, Result},
+    value::{self, Sexp},
+};
+
+/// Reads a value from S-expressions, as written by [crate::to_string] or by hand
+pub fn from_str<T: DeserializeOwned>(text: &str) -> Result<T> {
+    T::deserialize(value::parse(text)?)
+}
+
+/// Reads a value from an [Sexp], e.g. one built by [crate::to_value] and picked apart
+pub fn from_value<T: DeserializeOwned>(value: Sexp) -> Result<T> {
+    T::deserialize(value)
+}
+
+impl Sexp {
+    fn invalid(&self, expected: &str) -> Error {
+        Error::Message(format!("Expected {}, found {}", expected, self.describe()))
+    }
+
+    fn parse_number<T>(self) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match &self {
+            Sexp::Number(n) => n
+                .parse()
+                .map_err(|e| Error::Message(format!("Invalid number {}: {}", n, e))),
+            _ => Err(self.invalid("a number")),
+        }
+    }
+
+    /// Splits a list into its name and the rest of its items
+    fn into_headed(self, expected: &str) -> Result<(String, Vec<Sexp>)> {
+        match self {
+            Sexp::List(items) => {
+                let mut items = items.into_iter();
+
+                match items.next() {
+                    Some(Sexp::Symbol(head)) => Ok((head, items.collect())),
+                    _ => Err(Error::Message(format!(
+                        "Expected {}, found a list without a name",
+                        expected
+                    ))),
+                }
+            }
+            _ => Err(self.invalid(expected)),
+        }
+    }
+
+    /// Like [Sexp::into_headed], but the name has to be `name`
+    fn into_named(self, name: &str) -> Result<Vec<Sexp>> {
+        let (head, items) = self.into_headed(&format!("({} ...)", name))?;
+
+        if head == name {
+            Ok(items)
+        } else {
+            Err(Error::Message(format!(
+                "Expected ({} ...), found ({} ...)",
+                name, head
+            )))
+        }
+    }
+}
+
+/// Reads the items after a struct or variant's name, which are either `:name value` pairs or
+/// values in field order
+fn visit_fields<'de, V: Visitor<'de>>(fields: Vec<Sexp>, visitor: V) -> Result<V::Value> {
+    if let Some(Sexp::Keyword(_)) = fields.first() {
+        let mut pairs = Vec::with_capacity(fields.len() / 2);
+        let mut fields = fields.into_iter();
+
+        while let Some(key) = fields.next() {
+            match (key, fields.next()) {
+                (Sexp::Keyword(name), Some(value)) => pairs.push((Sexp::Str(name), value)),
+                (Sexp::Keyword(name), None) => {
+                    return Err(Error::Message(format!("Missing value for :{}", name)))
+                }
+                (key, _) => return Err(key.invalid("a :field name")),
+            }
+        }
+
+        visit_map(pairs, visitor)
+    } else {
+        visit_seq(fields, visitor)
+    }
+}
+
+fn visit_seq<'de, V: Visitor<'de>>(items: Vec<Sexp>, visitor: V) -> Result<V::Value> {
+    let mut seq = SeqDeserializer {
+        items: items.into_iter(),
+    };
+    let value = visitor.visit_seq(&mut seq)?;
+
+    match seq.items.len() {
+        0 => Ok(value),
+        extra => Err(Error::Message(format!(
+            "{} more values than expected, starting at {}",
+            extra,
+            seq.items.as_slice()[0].describe()
+        ))),
+    }
+}
+
+fn visit_map<'de, V: Visitor<'de>>(pairs: Vec<(Sexp, Sexp)>, visitor: V) -> Result<V::Value> {
+    visitor.visit_map(MapDeserializer {
+        pairs: pairs.into_iter(),
+        next_value: None,
+    })
+}
+
+macro_rules! deserialize_number {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                visitor.$visit(self.parse_number()?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Sexp {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Sexp::Symbol(s) => match s.as_str() {
+                "true" => visitor.visit_bool(true),
+                "false" => visitor.visit_bool(false),
+                "nil" => visitor.visit_unit(),
+                _ => visitor.visit_string(s),
+            },
+            Sexp::Keyword(s) | Sexp::Str(s) => visitor.visit_string(s),
+            Sexp::Number(ref n) => {
+                if let Ok(n) = n.parse() {
+                    visitor.visit_u64(n)
+                } else if let Ok(n) = n.parse() {
+                    visitor.visit_i64(n)
+                } else {
+                    visitor.visit_f64(self.parse_number()?)
+                }
+            }
+            Sexp::List(_) => visit_fields(self.into_headed("a list")?.1, visitor),
+            Sexp::Seq(items) => visit_seq(items, visitor),
+            Sexp::Map(pairs) => visit_map(pairs, visitor),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match &self {
+            Sexp::Symbol(s) if s == "true" => visitor.visit_bool(true),
+            Sexp::Symbol(s) if s == "false" => visitor.visit_bool(false),
+            _ => Err(self.invalid("true or false")),
+        }
+    }
+
+    deserialize_number! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match &self {
+            Sexp::Str(s) if s.chars().count() == 1 => visitor.visit_char(s.chars().next().unwrap()),
+            _ => Err(self.invalid("a one character string")),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Sexp::Str(s) | Sexp::Symbol(s) => visitor.visit_string(s),
+            _ => Err(self.invalid("a string")),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.is_nil() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.is_nil() {
+            visitor.visit_unit()
+        } else {
+            Err(self.invalid("nil"))
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        if self.is_nil() {
+            return visitor.visit_unit();
+        }
+
+        match self.into_named(name)?.first() {
+            None => visitor.visit_unit(),
+            Some(extra) => Err(extra.invalid(&format!("the end of ({})", name))),
+        }
+    }
+
+    /// Newtype structs can be written with or without their name
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        match &self {
+            Sexp::List(items) if items.first() == Some(&Sexp::Symbol(name.to_owned())) => {
+                let mut items = self.into_named(name)?;
+
+                match items.len() {
+                    1 => visitor.visit_newtype_struct(items.remove(0)),
+                    n => Err(Error::Message(format!(
+                        "Expected 1 value in ({} ...), found {}",
+                        name, n
+                    ))),
+                }
+            }
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Sexp::Seq(items) => visit_seq(items, visitor),
+            _ => Err(self.invalid("[...]")),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visit_seq(self.into_named(name)?, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Sexp::Map(pairs) => visit_map(pairs, visitor),
+            _ => Err(self.invalid("{...}")),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visit_fields(self.into_named(name)?, visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self {
+            Sexp::Symbol(variant) => visitor.visit_enum(EnumDeserializer {
+                variant,
+                fields: Vec::new(),
+            }),
+            _ => {
+                let (variant, fields) = self.into_headed(&format!("a {} variant", name))?;
+                visitor.visit_enum(EnumDeserializer { variant, fields })
+            }
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+}
+
+struct SeqDeserializer {
+    items: vec::IntoIter<Sexp>,
+}
+
+impl<'de> SeqAccess<'de> for SeqDeserializer {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        self.items
+            .next()
+            .map(|item| seed.deserialize(item))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct MapDeserializer {
+    pairs: vec::IntoIter<(Sexp, Sexp)>,
+    next_value: Option<Sexp>,
+}
+
+impl<'de> MapAccess<'de> for MapDeserializer {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.pairs.next() {
+            Some((key, value)) => {
+                self.next_value = Some(value);
+                seed.deserialize(key).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let value = self
+            .next_value
+            .take()
+            .unwrap_or_else(|| panic!("Map value requested before its key"));
+
+        seed.deserialize(value)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.pairs.len())
+    }
+}
+
+struct EnumDeserializer {
+    variant: String,
+    fields: Vec<Sexp>,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+    type Error = Error;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self::Variant)> {
+        let variant = seed.deserialize(Sexp::Symbol(self.variant.clone()))?;
+
+        Ok((
+            variant,
+            VariantDeserializer {
+                variant: self.variant,
+                fields: self.fields,
+            },
+        ))
+    }
+}
+
+struct VariantDeserializer {
+    variant: String,
+    fields: Vec<Sexp>,
+}
+
+impl<'de> VariantAccess<'de> for VariantDeserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.fields.first() {
+            None => Ok(()),
+            Some(extra) => Err(extra.invalid(&format!("the end of ({})", self.variant))),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(mut self, seed: T) -> Result<T::Value> {
+        match self.fields.len() {
+            1 => seed.deserialize(self.fields.remove(0)),
+            n => Err(Error::Message(format!(
+                "Expected 1 value in ({} ...), found {}",
+                self.variant, n
+            ))),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        visit_seq(self.fields, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visit_fields(self.fields, visitor)
+    }
+}
@@ -0,0 +1,56 @@
+use std::fmt::{self, Display};
+
+use serde::{de, ser};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    // Created by data structures through the `ser::Error` and `de::Error` traits, or when a value
+    // doesn't have the shape the type being read expects
+    Message(String),
+
+    // The text isn't valid S-expressions
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl Error {
+    pub(crate) fn syntax<T: Display>(line: usize, column: usize, message: T) -> Self {
+        Error::Syntax {
+            line,
+            column,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Message(msg) => formatter.write_str(msg),
+            Error::Syntax {
+                line,
+                column,
+                message,
+            } => write!(formatter, "{} at line {}, column {}", message, line, column),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,154 @@
+//! A serde format for S-expressions, meant for values that people read and write by hand.
+//!
+//! - Structs and enum variants are lists headed by their name, e.g. `(Point :x 1.0 :y 2.0)`. Fields
+//!   are named with keywords, except when there's only one, e.g. `(Meters 5.0)`. Fields can be given
+//!   in order without their names when reading.
+//! - Unit variants are bare symbols, e.g. `Red`
+//! - Sequences and tuples are `[a b c]`, maps are `{key value ...}`
+//! - `None` and `()` are `nil`, `Some(x)` is just `x`
+//! - `;` starts a comment that runs to the end of the line
+//!
+//! [to_value] gives the [Sexp] itself, for looking things up in a value by field name.
+
+mod de;
+mod error;
+mod ser;
+mod value;
+
+pub use de::{from_str, from_value};
+pub use error::{Error, Result};
+pub use ser::{to_string, to_value};
+pub use value::Sexp;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Meters(f32);
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Shape {
+        Empty,
+        Circle(Meters),
+        Line(Meters, Meters),
+        Rect {
+            width: Meters,
+            height: Option<f32>,
+            #[serde(skip)]
+            area: f32,
+        },
+        Group {
+            shapes: Vec<Shape>,
+            names: BTreeMap<String, u8>,
+        },
+    }
+
+    fn round_trip(shape: &Shape) -> String {
+        let text = to_string(shape).unwrap();
+        assert_eq!(&from_str::<Shape>(&text).unwrap(), shape, "{}", text);
+        text
+    }
+
+    #[test]
+    fn round_trips() {
+        assert_eq!(round_trip(&Shape::Empty), "Empty\n");
+        assert_eq!(
+            round_trip(&Shape::Circle(Meters(0.5))),
+            "(Circle (Meters 0.5))\n"
+        );
+        assert_eq!(
+            round_trip(&Shape::Line(Meters(1.0), Meters(-2e-9))),
+            "(Line (Meters 1.0) (Meters -2e-9))\n"
+        );
+        assert_eq!(
+            round_trip(&Shape::Rect {
+                width: Meters(f32::INFINITY),
+                height: None,
+                area: 0.0,
+            }),
+            "(Rect :width (Meters inf) :height nil)\n"
+        );
+
+        let mut names = BTreeMap::new();
+        names.insert("a \"quoted\" name".to_owned(), 1);
+        round_trip(&Shape::Group {
+            shapes: (0..20).map(|i| Shape::Circle(Meters(i as f32))).collect(),
+            names,
+        });
+    }
+
+    #[test]
+    fn breaks_long_lines() {
+        let text = to_string(&Shape::Group {
+            shapes: (0..20).map(|i| Shape::Circle(Meters(i as f32))).collect(),
+            names: BTreeMap::new(),
+        })
+        .unwrap();
+
+        assert!(text.lines().all(|line| line.len() <= 100), "{}", text);
+        assert!(text.starts_with("(Group\n  :shapes [(Circle (Meters 0.0))\n"));
+    }
+
+    #[test]
+    fn reads_hand_written_values() {
+        let text = "
+            ; Fields can be left unnamed, in order
+            (Rect (Meters 2) 3.5)
+        ";
+
+        assert_eq!(
+            from_str::<Shape>(text).unwrap(),
+            Shape::Rect {
+                width: Meters(2.0),
+                height: Some(3.5),
+                area: 0.0,
+            }
+        );
+        assert_eq!(
+            from_str::<Shape>("(Circle 1.0)").unwrap(),
+            Shape::Circle(Meters(1.0))
+        );
+    }
+
+    #[test]
+    fn picks_values_apart() {
+        let value = to_value(&Shape::Group {
+            shapes: vec![Shape::Empty, Shape::Circle(Meters(2.0))],
+            names: BTreeMap::new(),
+        })
+        .unwrap();
+
+        let circle = value.get("shapes").and_then(|shapes| shapes.get("1"));
+        assert_eq!(circle.and_then(Sexp::name), Some("Circle"));
+        assert!(value.get("area").is_none());
+        assert!(value.get("shapes").unwrap().get("2").is_none());
+
+        let rect = to_value(&Shape::Rect {
+            width: Meters(3.0),
+            height: Some(4.0),
+            area: 0.0,
+        })
+        .unwrap();
+
+        let width: Meters = from_value(rect.get("width").unwrap().clone()).unwrap();
+        assert_eq!(width, Meters(3.0));
+    }
+
+    #[test]
+    fn reports_errors() {
+        match from_str::<Shape>("(Circle\n  (Meters 1.0)") {
+            Err(Error::Syntax { line, .. }) => assert_eq!(line, 2),
+            other => panic!("Expected a syntax error, got {:?}", other),
+        }
+
+        assert!(from_str::<Shape>("(Square (Meters 1.0))").is_err());
+        assert!(from_str::<Shape>("(Line (Meters 1.0))").is_err());
+        assert!(from_str::<Shape>("(Circle (Meters 1.0) (Meters 2.0))").is_err());
+        assert!(from_str::<Shape>("(Rect :width)").is_err());
+    }
+}
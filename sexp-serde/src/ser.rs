@@ -0,0 +1,407 @@
+use serde::{ser, Serialize};
+
+use crate::{error::Error, error::Result, value::Sexp};
+
+/// Serializes `value` as indented S-expressions
+pub fn to_string<T>(value: &T) -> Result<String>
+where
+    T: ?Sized + Serialize,
+{
+    let mut out = String::new();
+    value.serialize(Serializer)?.write_pretty(&mut out, 0);
+    out.push('\n');
+
+    Ok(out)
+}
+
+/// Builds the [Sexp] for `value`, to be picked apart rather than printed
+pub fn to_value<T>(value: &T) -> Result<Sexp>
+where
+    T: ?Sized + Serialize,
+{
+    value.serialize(Serializer)
+}
+
+/// Builds the [Sexp] for a value, which is only printed once it's complete so it knows how much
+/// room each part needs
+pub(crate) struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Sexp;
+
+    type Error = Error;
+
+    type SerializeSeq = SerializeItems;
+    type SerializeTuple = SerializeItems;
+    type SerializeTupleStruct = SerializeItems;
+    type SerializeTupleVariant = SerializeItems;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeFields;
+    type SerializeStructVariant = SerializeFields;
+
+    fn serialize_bool(self, v: bool) -> Result<Sexp> {
+        Ok(Sexp::Symbol(v.to_string()))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Sexp> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Sexp> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Sexp> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Sexp> {
+        Ok(Sexp::Number(v.to_string()))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Sexp> {
+        Ok(Sexp::Number(v.to_string()))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Sexp> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Sexp> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Sexp> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Sexp> {
+        Ok(Sexp::Number(v.to_string()))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Sexp> {
+        Ok(Sexp::Number(v.to_string()))
+    }
+
+    // Debug formatting is the shortest text that reads back as the same float, and always has a
+    // decimal point or exponent
+    fn serialize_f32(self, v: f32) -> Result<Sexp> {
+        Ok(Sexp::Number(format!("{:?}", v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Sexp> {
+        Ok(Sexp::Number(format!("{:?}", v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Sexp> {
+        Ok(Sexp::Str(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Sexp> {
+        Ok(Sexp::Str(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Sexp> {
+        Ok(Sexp::Seq(
+            v.iter()
+                .map(|byte| Sexp::Number(byte.to_string()))
+                .collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Sexp> {
+        Ok(Sexp::nil())
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Sexp>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Sexp> {
+        Ok(Sexp::nil())
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Sexp> {
+        Ok(Sexp::List(vec![Sexp::Symbol(name.to_owned())]))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Sexp> {
+        Ok(Sexp::Symbol(variant.to_owned()))
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Sexp>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(Sexp::List(vec![
+            Sexp::Symbol(name.to_owned()),
+            value.serialize(self)?,
+        ]))
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Sexp>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(Sexp::List(vec![
+            Sexp::Symbol(variant.to_owned()),
+            value.serialize(self)?,
+        ]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeItems> {
+        Ok(SerializeItems::new(None, len.unwrap_or(0)))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeItems> {
+        Ok(SerializeItems::new(None, len))
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, len: usize) -> Result<SerializeItems> {
+        Ok(SerializeItems::new(Some(name), len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeItems> {
+        Ok(SerializeItems::new(Some(variant), len))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeMap> {
+        Ok(SerializeMap {
+            pairs: Vec::with_capacity(len.unwrap_or(0)),
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<SerializeFields> {
+        Ok(SerializeFields::new(name, len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeFields> {
+        Ok(SerializeFields::new(variant, len))
+    }
+}
+
+/// Items of a sequence, or of a tuple struct or variant headed by its name
+pub(crate) struct SerializeItems {
+    head: Option<&'static str>,
+    items: Vec<Sexp>,
+}
+
+impl SerializeItems {
+    fn new(head: Option<&'static str>, len: usize) -> Self {
+        Self {
+            head,
+            items: Vec::with_capacity(len + 1),
+        }
+    }
+
+    fn push<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.items.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Sexp {
+        match self.head {
+            Some(head) => Sexp::List(
+                std::iter::once(Sexp::Symbol(head.to_owned()))
+                    .chain(self.items)
+                    .collect(),
+            ),
+            None => Sexp::Seq(self.items),
+        }
+    }
+}
+
+impl ser::SerializeSeq for SerializeItems {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Sexp> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTuple for SerializeItems {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Sexp> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeItems {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Sexp> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeItems {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Sexp> {
+        Ok(self.finish())
+    }
+}
+
+pub(crate) struct SerializeMap {
+    pairs: Vec<(Sexp, Sexp)>,
+    next_key: Option<Sexp>,
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.next_key = Some(key.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = self
+            .next_key
+            .take()
+            .unwrap_or_else(|| panic!("Missing key when serializing map value"));
+
+        self.pairs.push((key, value.serialize(Serializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Sexp> {
+        Ok(Sexp::Map(self.pairs))
+    }
+}
+
+/// Named fields of a struct or struct variant, headed by its name
+pub(crate) struct SerializeFields {
+    head: &'static str,
+    fields: Vec<(&'static str, Sexp)>,
+}
+
+impl SerializeFields {
+    fn new(head: &'static str, len: usize) -> Self {
+        Self {
+            head,
+            fields: Vec::with_capacity(len),
+        }
+    }
+
+    fn push<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.fields.push((key, value.serialize(Serializer)?));
+        Ok(())
+    }
+
+    fn finish(self) -> Sexp {
+        let mut items = vec![Sexp::Symbol(self.head.to_owned())];
+
+        for (key, value) in self.fields {
+            items.push(Sexp::Keyword(key.to_owned()));
+            items.push(value);
+        }
+
+        Sexp::List(items)
+    }
+}
+
+impl ser::SerializeStruct for SerializeFields {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Sexp> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeFields {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Sexp> {
+        Ok(self.finish())
+    }
+}
@@ -0,0 +1,380 @@
+use std::{iter::Peekable, str::Chars};
+
+use crate::error::{Error, Result};
+
+/// Lines are broken up until they fit in this many columns, where they can
+const LINE_WIDTH: usize = 100;
+
+/// An S-expression that's been parsed, or is about to be printed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sexp {
+    /// A bare word, e.g. a unit variant, `true`, `false` or `nil`
+    Symbol(String),
+    /// A field name, written `:name`
+    Keyword(String),
+    /// Kept as written, so it can be read as whichever number type is asked for
+    Number(String),
+    Str(String),
+    /// `(Name ...)`, a struct or enum variant. A lone field is printed without its name.
+    List(Vec<Sexp>),
+    /// `[...]`
+    Seq(Vec<Sexp>),
+    /// `{key value ...}`
+    Map(Vec<(Sexp, Sexp)>),
+}
+
+impl Sexp {
+    /// The name heading a list
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Sexp::List(items) => match items.first() {
+                Some(Sexp::Symbol(name)) => Some(name),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The value of a list's `:key` field, or the item at `key` in a sequence
+    pub fn get(&self, key: &str) -> Option<&Sexp> {
+        match self {
+            Sexp::List(items) => items
+                .iter()
+                .position(|item| matches!(item, Sexp::Keyword(name) if name == key))
+                .and_then(|i| items.get(i + 1)),
+            Sexp::Seq(items) => key.parse().ok().and_then(|i: usize| items.get(i)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn nil() -> Self {
+        Sexp::Symbol("nil".to_owned())
+    }
+
+    pub(crate) fn is_nil(&self) -> bool {
+        matches!(self, Sexp::Symbol(s) if s == "nil")
+    }
+
+    /// The whole expression on one line
+    pub(crate) fn to_flat(&self) -> String {
+        let mut out = String::new();
+        self.write_flat(&mut out);
+        out
+    }
+
+    /// A short description for error messages
+    pub(crate) fn describe(&self) -> String {
+        let flat = self.to_flat();
+
+        if flat.chars().count() > 40 {
+            format!("{}...", flat.chars().take(40).collect::<String>())
+        } else {
+            flat
+        }
+    }
+
+    fn write_flat(&self, out: &mut String) {
+        match self {
+            Sexp::Symbol(s) | Sexp::Number(s) => out.push_str(s),
+            Sexp::Keyword(s) => {
+                out.push(':');
+                out.push_str(s);
+            }
+            Sexp::Str(s) => write_str(s, out),
+            Sexp::List(items) => write_flat_items("(", ")", lone_field_unnamed(items), out),
+            Sexp::Seq(items) => write_flat_items("[", "]", items, out),
+            Sexp::Map(pairs) => {
+                out.push('{');
+
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        out.push(' ');
+                    }
+
+                    key.write_flat(out);
+                    out.push(' ');
+                    value.write_flat(out);
+                }
+
+                out.push('}');
+            }
+        }
+    }
+
+    /// Writes the expression starting at column `indent`, breaking lists that don't fit across
+    /// several lines with their items indented under them
+    pub(crate) fn write_pretty(&self, out: &mut String, indent: usize) {
+        let flat = self.to_flat();
+
+        if indent + flat.len() <= LINE_WIDTH {
+            out.push_str(&flat);
+            return;
+        }
+
+        match self {
+            Sexp::List(items) if !items.is_empty() => {
+                let items = lone_field_unnamed(items);
+                out.push('(');
+                items[0].write_flat(out);
+
+                let mut rest = items[1..].iter().copied();
+
+                while let Some(item) = rest.next() {
+                    new_line(out, indent + 2);
+                    item.write_pretty(out, indent + 2);
+
+                    // Keep each field's value on the line with its name
+                    if let Sexp::Keyword(name) = item {
+                        if let Some(value) = rest.next() {
+                            out.push(' ');
+                            value.write_pretty(out, indent + 2 + name.len() + 2);
+                        }
+                    }
+                }
+
+                out.push(')');
+            }
+            Sexp::Seq(items) => {
+                out.push('[');
+
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        new_line(out, indent + 1);
+                    }
+
+                    item.write_pretty(out, indent + 1);
+                }
+
+                out.push(']');
+            }
+            Sexp::Map(pairs) => {
+                out.push('{');
+
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        new_line(out, indent + 1);
+                    }
+
+                    let key = key.to_flat();
+                    out.push_str(&key);
+                    out.push(' ');
+                    value.write_pretty(out, indent + 1 + key.len() + 1);
+                }
+
+                out.push('}');
+            }
+            _ => out.push_str(&flat),
+        }
+    }
+}
+
+/// Leaves out the name of a list's only field, it doesn't tell the reader anything the list's name
+/// doesn't
+fn lone_field_unnamed(items: &[Sexp]) -> Vec<&Sexp> {
+    match items {
+        [name, Sexp::Keyword(_), value] => vec![name, value],
+        _ => items.iter().collect(),
+    }
+}
+
+fn write_flat_items<'a>(
+    open: &str,
+    close: &str,
+    items: impl IntoIterator<Item = &'a Sexp>,
+    out: &mut String,
+) {
+    out.push_str(open);
+
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+
+        item.write_flat(out);
+    }
+
+    out.push_str(close);
+}
+
+fn write_str(s: &str, out: &mut String) {
+    out.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+}
+
+fn new_line(out: &mut String, indent: usize) {
+    out.push('\n');
+    out.push_str(&" ".repeat(indent));
+}
+
+/// Parses `text` as exactly one expression
+pub(crate) fn parse(text: &str) -> Result<Sexp> {
+    let mut parser = Parser {
+        chars: text.chars().peekable(),
+        line: 1,
+        column: 1,
+    };
+
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+
+    match parser.chars.peek() {
+        Some(_) => Err(parser.error("Expected the end of the text")),
+        None => Ok(value),
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> Error {
+        Error::syntax(self.line, self.column, message)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(&c) = self.chars.peek() {
+            if c == ';' {
+                while !matches!(self.next(), Some('\n') | None) {}
+            } else if c.is_whitespace() {
+                self.next();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Sexp> {
+        self.skip_whitespace();
+
+        match self.chars.peek() {
+            None => Err(self.error("Unexpected end of text")),
+            Some('(') => {
+                self.next();
+                Ok(Sexp::List(self.parse_items(')')?))
+            }
+            Some('[') => {
+                self.next();
+                Ok(Sexp::Seq(self.parse_items(']')?))
+            }
+            Some('{') => {
+                self.next();
+                let (line, column) = (self.line, self.column);
+                let items = self.parse_items('}')?;
+
+                if items.len() % 2 != 0 {
+                    return Err(Error::syntax(line, column, "Map is missing a value"));
+                }
+
+                let mut items = items.into_iter();
+                let mut pairs = Vec::new();
+
+                while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                    pairs.push((key, value));
+                }
+
+                Ok(Sexp::Map(pairs))
+            }
+            Some('"') => {
+                self.next();
+                self.parse_str()
+            }
+            Some(')') | Some(']') | Some('}') => Err(self.error("Unexpected closing bracket")),
+            Some(_) => self.parse_atom(),
+        }
+    }
+
+    fn parse_items(&mut self, close: char) -> Result<Vec<Sexp>> {
+        let mut items = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+
+            match self.chars.peek() {
+                Some(&c) if c == close => {
+                    self.next();
+                    return Ok(items);
+                }
+                None => return Err(self.error(&format!("Expected '{}'", close))),
+                Some(_) => items.push(self.parse_value()?),
+            }
+        }
+    }
+
+    fn parse_str(&mut self) -> Result<Sexp> {
+        let mut s = String::new();
+
+        loop {
+            match self.next() {
+                None => return Err(self.error("Unterminated string")),
+                Some('"') => return Ok(Sexp::Str(s)),
+                Some('\\') => match self.next() {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some(c @ '"') | Some(c @ '\\') => s.push(c),
+                    _ => return Err(self.error("Invalid escape in string")),
+                },
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<Sexp> {
+        let mut token = String::new();
+
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() || "()[]{}\";".contains(c) {
+                break;
+            }
+
+            token.push(c);
+            self.next();
+        }
+
+        if let Some(name) = token.strip_prefix(':') {
+            if name.is_empty() {
+                return Err(self.error("Missing name after ':'"));
+            }
+
+            Ok(Sexp::Keyword(name.to_owned()))
+        } else if is_number(&token) {
+            Ok(Sexp::Number(token))
+        } else {
+            Ok(Sexp::Symbol(token))
+        }
+    }
+}
+
+fn is_number(token: &str) -> bool {
+    let unsigned = token.trim_start_matches(&['-', '+'][..]);
+
+    matches!(unsigned, "inf" | "NaN")
+        || unsigned.starts_with(|c: char| c.is_ascii_digit())
+        || (unsigned.starts_with('.') && unsigned[1..].starts_with(|c: char| c.is_ascii_digit()))
+}